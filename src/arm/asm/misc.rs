@@ -25,3 +25,11 @@ pub fn r#yield() {
 pub fn dbg() {
     unsafe { asm!("dbg") }
 }
+
+/// Reads the Interrupt Program Status Register (active exception number).
+#[inline(always)]
+pub fn ipsr() -> u32 {
+    let r: u32;
+    unsafe { asm!("mrs {}, IPSR", out(reg) r) }
+    r
+}
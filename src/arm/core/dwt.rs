@@ -0,0 +1,44 @@
+//! Data Watchpoint and Trace peripheral.
+
+
+
+use crate::reg::{ DefaultRegister, Register };
+
+
+
+/// The `CycleCounter` trait includes all the necessary methods to interact with
+/// the DWT cycle counter. This trait must be implemented by an empty struct in
+/// the HAL crates. Not available in ARMv6-M cores.
+pub trait CycleCounter {
+    /// Enables the cycle counter.
+    #[inline(always)]
+    fn enable(&mut self) {
+        // Enable the trace subsystem in the Debug Exception and Monitor Control Register (DEMCR).
+        DefaultRegister::at(0xE000EDFC).set(1u32 << 24);
+
+        // Set the CYCCNTENA bit in the Control Register (CTRL).
+        DefaultRegister::at(0xE0001000).set(1u32);
+    }
+
+    /// Disables the cycle counter.
+    #[inline(always)]
+    fn disable(&mut self) {
+        // Clear the CYCCNTENA bit in the Control Register (CTRL).
+        DefaultRegister::at(0xE0001000).clear(1u32);
+    }
+
+    /// Reads the current cycle count.
+    #[inline(always)]
+    fn cycles(&self) -> u32 {
+        // Read the Cycle Count Register (CYCCNT).
+        DefaultRegister::<u32>::at(0xE0001004).read()
+    }
+}
+
+#[cfg(feature = "stats")]
+impl<T: CycleCounter> crate::int::Clock for T {
+    #[inline(always)]
+    fn cycles(&self) -> u32 {
+        CycleCounter::cycles(self)
+    }
+}
@@ -4,6 +4,7 @@
 use crate::reg::{ DefaultRegister, Register };
 
 
+pub use self::dwt::CycleCounter;
 pub use self::int::InterruptControl;
 pub use self::mpu::{ MPU, MPUConfiguration, MPURegionSize, MPUPermissions };
 pub use self::scb::SystemControl;
@@ -11,6 +12,9 @@ pub use self::systick::Systick;
 
 
 
+/// Data Watchpoint and Trace peripheral (DWT).
+mod dwt;
+
 /// NVIC and Interrupt Control peripherals.
 mod int;
 
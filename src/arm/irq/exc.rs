@@ -245,7 +245,8 @@ impl ExceptionControl {
         };
 
         // Read the corresponding SHPR register.
-        let mut r = unsafe { core::ptr::read_volatile( (0xE000ED14 + r) as *const u32 ) };
+        let a = 0xE000ED14u32 + (4 * r);
+        let mut r = unsafe { core::ptr::read_volatile( a as *const u32 ) };
 
         // Clear the previous priority.
         r &= !(0xFF << o);
@@ -254,7 +255,7 @@ impl ExceptionControl {
         r |= (prio as u32) << o;
 
         // Write back the SHPR register.
-        unsafe { core::ptr::write_volatile( (0xED000ED14 + r) as *mut u32, r ) };
+        unsafe { core::ptr::write_volatile( a as *mut u32, r ) };
 
         // Memory and instruction barriers.
         dsb();
@@ -264,6 +265,37 @@ impl ExceptionControl {
         // Restore interrupts.
         cpsie_i();
     }
+
+    /// Sets the pending status of the given exception.
+    /// Only NMI, PendSV and Systick can be pended by software.
+    pub fn pend(&mut self, e: Exception) {
+        let bit = match e {
+            Exception::NMI => 31,
+            Exception::PendSV => 28,
+            Exception::Systick => 26,
+            _ => return,
+        };
+
+        // Write the Interrupt Control and State Register (ICSR).
+        unsafe { core::ptr::write_volatile( 0xE000ED04 as *mut u32, 1 << bit ) };
+
+        // Memory and instruction barriers.
+        dsb();
+        isb();
+    }
+
+    /// Clears the pending status of the given exception.
+    /// Only PendSV and Systick can be cleared by software.
+    pub fn unpend(&mut self, e: Exception) {
+        let bit = match e {
+            Exception::PendSV => 27,
+            Exception::Systick => 25,
+            _ => return,
+        };
+
+        // Write the Interrupt Control and State Register (ICSR).
+        unsafe { core::ptr::write_volatile( 0xE000ED04 as *mut u32, 1 << bit ) };
+    }
 }
 
 
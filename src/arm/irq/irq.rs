@@ -13,6 +13,11 @@ impl<const LEN: usize> IRQTable<LEN> {
         unsafe { &mut *(addr as *mut _) }
     }
 
+    /// Static initializer. Useful to create a shadow table in RAM.
+    pub const fn new() -> Self {
+        Self { table: [default; LEN] }
+    }
+
     /// Initializes the table.
     pub fn init(&mut self) {
         self.table = [default; LEN];
@@ -31,6 +36,30 @@ impl<const LEN: usize> IRQTable<LEN> {
     }
 }
 
+impl<const LEN: usize> Default for IRQTable<LEN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "stats")]
+impl<const LEN: usize> IRQTable<LEN> {
+    /// Dispatches the active IRQ to its handler in this table, measuring its execution.
+    /// Intended to be called from a trampoline installed in the vector table,
+    /// with this table acting as a shadow table of the real handlers.
+    pub fn dispatch<C: crate::int::Clock, const N: usize>(&self, stats: &crate::int::StatsTable<N>, clock: &C) {
+        // External IRQs start at exception 16.
+        let irq = match (crate::asm::ipsr() & 0x1FF).checked_sub(16) {
+            Some(irq) => irq as usize,
+            _ => return,
+        };
+
+        if let Some(f) = self.table.get(irq) {
+            stats.measure(irq, clock, f)
+        }
+    }
+}
+
 
 /// Default IRQ is just to return to execution.
 /// Adds some cycles of latency.
@@ -1,6 +1,14 @@
+//! Buffer utilities.
 
 
 
+mod ring;
+
+
+
+pub use self::ring::RingBuffer;
+
+
 
 #[repr(C)]
 pub struct Buffer<T: 'static> {
@@ -56,6 +64,21 @@ pub struct BufferWriter<T: 'static> {
     pub actual: usize,
 }
 
+impl<T: 'static> BufferWriter<T> {
+    /// Appends an item and updates the expected count.
+    /// Returns the item back if the buffer is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        match self.buffer.get_mut(self.expected) {
+            Some(slot) => *slot = item,
+            _ => return Err(item),
+        }
+
+        self.expected += 1;
+
+        Ok(())
+    }
+}
+
 impl<T: 'static> core::ops::Index<usize> for BufferWriter<T> {
     type Output = T;
 
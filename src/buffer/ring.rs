@@ -0,0 +1,226 @@
+//! Lock-free single producer / single consumer ring buffer.
+//! One context (e.g. an ISR) pushes and another one pops, without critical
+//! sections. Only atomic loads and stores are used, so it also works on cores
+//! without compare-and-swap (ARMv6-M).
+
+
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{ AtomicUsize, Ordering };
+
+
+
+/// Preallocated ring buffer of `N` items.
+/// `push` must only be called from a single producer context and `pop` /
+/// `peek` from a single consumer context.
+pub struct RingBuffer<T: Copy, const N: usize> {
+    /// Storage.
+    buffer: UnsafeCell<MaybeUninit<[T; N]>>,
+
+    /// Write index, in the range `0..2N`. Owned by the producer.
+    head: AtomicUsize,
+
+    /// Read index, in the range `0..2N`. Owned by the consumer.
+    tail: AtomicUsize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self { buffer: UnsafeCell::new(MaybeUninit::uninit()), head: AtomicUsize::new(0), tail: AtomicUsize::new(0) }
+    }
+
+    /// Maximum number of items.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Number of items in the buffer.
+    pub fn len(&self) -> usize {
+        distance::<N>(self.tail.load(Ordering::Acquire), self.head.load(Ordering::Acquire))
+    }
+
+    /// Returns `true` if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the buffer is full.
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Pushes an item. Returns the item back if the buffer is full.
+    /// Producer side.
+    pub fn push(&self, item: T) -> Result<(), T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+
+        if distance::<N>(tail, head) == N { return Err(item) }
+
+        unsafe { self.slot(head).write(item) }
+
+        self.head.store(next::<N>(head), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Pushes as many items as fit. Returns the number of items pushed.
+    /// Producer side.
+    pub fn push_slice(&self, items: &[T]) -> usize {
+        items.iter().take_while(|item| self.push(**item).is_ok()).count()
+    }
+
+    /// Pops the oldest item.
+    /// Consumer side.
+    pub fn pop(&self) -> Option<T> {
+        let item = self.peek()?;
+
+        self.tail.store(next::<N>(self.tail.load(Ordering::Relaxed)), Ordering::Release);
+
+        Some(item)
+    }
+
+    /// Pops items until the slice is full or the buffer is empty.
+    /// Returns the number of items popped. Consumer side.
+    pub fn pop_slice(&self, items: &mut [T]) -> usize {
+        let mut n = 0;
+
+        for item in items.iter_mut() {
+            match self.pop() {
+                Some(i) => *item = i,
+                _ => break,
+            }
+
+            n += 1;
+        }
+
+        n
+    }
+
+    /// Returns the oldest item without removing it.
+    /// Consumer side.
+    pub fn peek(&self) -> Option<T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+
+        if tail == head { return None }
+
+        Some(unsafe { self.slot(tail).read() })
+    }
+
+    /// Discards all the items.
+    /// Consumer side.
+    pub fn clear(&self) {
+        self.tail.store(self.head.load(Ordering::Acquire), Ordering::Release);
+    }
+
+    /// Pointer to the slot of the given index.
+    unsafe fn slot(&self, index: usize) -> *mut T {
+        (*self.buffer.get()).as_mut_ptr().cast::<T>().add(index % N)
+    }
+}
+
+impl<T: Copy, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<T: Copy + Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+
+
+/// Next index in the range `0..2N`.
+#[inline(always)]
+fn next<const N: usize>(index: usize) -> usize {
+    if index + 1 == 2 * N { 0 } else { index + 1 }
+}
+
+/// Number of items between the read and write indices.
+#[inline(always)]
+fn distance<const N: usize>(tail: usize, head: usize) -> usize {
+    if head >= tail { head - tail } else { head + (2 * N) - tail }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_and_empty() {
+        let ring = RingBuffer::<u8, 3>::new();
+
+        assert!(ring.is_empty());
+        assert_eq!(ring.peek(), None);
+        assert_eq!(ring.pop(), None);
+
+        assert_eq!(ring.push(1), Ok(()));
+        assert_eq!(ring.push(2), Ok(()));
+        assert_eq!(ring.push(3), Ok(()));
+
+        assert!(ring.is_full());
+        assert_eq!(ring.len(), ring.capacity());
+        assert_eq!(ring.push(4), Err(4));
+
+        assert_eq!(ring.peek(), Some(1));
+        assert_eq!(ring.pop(), Some(1));
+        assert!(!ring.is_full());
+
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let ring = RingBuffer::<u32, 3>::new();
+
+        // The indices wrap at 2N, go around several times with every fill level.
+        for i in 0..20 {
+            assert_eq!(ring.push(i), Ok(()));
+            assert_eq!(ring.push(i + 100), Ok(()));
+            assert_eq!(ring.len(), 2);
+
+            assert_eq!(ring.pop(), Some(i));
+            assert_eq!(ring.pop(), Some(i + 100));
+            assert!(ring.is_empty());
+
+            assert_eq!(ring.push(i), Ok(()));
+            assert_eq!(ring.pop(), Some(i));
+        }
+
+        for i in 0..20 {
+            assert_eq!(ring.push_slice(&[i, i + 1, i + 2]), 3);
+            assert!(ring.is_full());
+
+            let mut items = [0; 3];
+            assert_eq!(ring.pop_slice(&mut items), 3);
+            assert_eq!(items, [i, i + 1, i + 2]);
+        }
+    }
+
+    #[test]
+    fn partial_slices() {
+        let ring = RingBuffer::<u8, 4>::new();
+
+        assert_eq!(ring.push_slice(&[1, 2, 3]), 3);
+        assert_eq!(ring.push_slice(&[4, 5, 6]), 1);
+        assert_eq!(ring.push_slice(&[7]), 0);
+
+        let mut items = [0; 3];
+        assert_eq!(ring.pop_slice(&mut items), 3);
+        assert_eq!(items, [1, 2, 3]);
+
+        // The remaining item and the next ones span the end of the storage.
+        assert_eq!(ring.push_slice(&[8, 9]), 2);
+
+        let mut items = [0; 6];
+        assert_eq!(ring.pop_slice(&mut items), 3);
+        assert_eq!(items, [4, 8, 9, 0, 0, 0]);
+        assert_eq!(ring.pop_slice(&mut items), 0);
+    }
+}
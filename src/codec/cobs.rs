@@ -0,0 +1,356 @@
+//! Consistent Overhead Byte Stuffing (COBS).
+//! Frames are encoded without zero bytes and delimited by a single `0x00`.
+//! The overhead is one byte per 254 bytes of payload plus the delimiter.
+
+
+
+use super::{ CodecError, Decoder, Encoder, Frame, Sink };
+
+
+
+/// Frame delimiter.
+pub const DELIMITER: u8 = 0x00;
+
+/// Maximum number of non-zero bytes in a block.
+const BLOCK: usize = 254;
+
+
+
+/// Maximum length of an encoded frame, including the delimiter.
+pub const fn max_encoded_len(len: usize) -> usize {
+    len + (len / BLOCK) + 2
+}
+
+
+
+/// Streaming COBS encoder.
+/// Buffers up to one block (254 bytes) of the current frame.
+pub struct CobsEncoder {
+    /// Non-zero bytes of the current block.
+    block: [u8; BLOCK],
+
+    /// Number of bytes in the current block.
+    len: usize,
+
+    /// Set when the last block written was full and nothing followed it.
+    full: bool,
+}
+
+impl CobsEncoder {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self { block: [0; BLOCK], len: 0, full: false }
+    }
+
+    /// Writes the current block with its code byte.
+    fn block<S: Sink>(&mut self, sink: &mut S) -> Result<(), CodecError> {
+        let len = self.len;
+        self.len = 0;
+        self.full = len == BLOCK;
+
+        sink.put(len as u8 + 1)?;
+
+        for byte in &self.block[..len] {
+            sink.put(*byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Encoder for CobsEncoder {
+    fn push<S: Sink>(&mut self, data: &[u8], sink: &mut S) -> Result<(), CodecError> {
+        for byte in data {
+            if *byte == 0 {
+                self.block(sink)?;
+                continue;
+            }
+
+            self.block[self.len] = *byte;
+            self.len += 1;
+            self.full = false;
+
+            // A full block has no implicit zero.
+            if self.len == BLOCK { self.block(sink)? }
+        }
+
+        Ok(())
+    }
+
+    fn finish<S: Sink>(&mut self, sink: &mut S) -> Result<(), CodecError> {
+        // A frame ending with a full block needs no empty block after it.
+        if !self.full { self.block(sink)? }
+
+        self.full = false;
+        sink.put(DELIMITER)
+    }
+}
+
+impl Default for CobsEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+/// Streaming COBS decoder for frames of up to `N` bytes.
+/// Empty input between delimiters is skipped.
+pub struct CobsDecoder<const N: usize> {
+    /// Frame being decoded.
+    frame: Frame<N>,
+
+    /// Code of the current block (0 before the first block of a frame).
+    code: u8,
+
+    /// Number of bytes left in the current block.
+    remaining: u8,
+}
+
+impl<const N: usize> CobsDecoder<N> {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self { frame: Frame::new(), code: 0, remaining: 0 }
+    }
+}
+
+impl<const N: usize> Decoder for CobsDecoder<N> {
+    fn feed(&mut self, byte: u8) -> Option<Result<usize, CodecError>> {
+        self.frame.next();
+
+        if byte == DELIMITER {
+            if self.code == 0 { return None }
+
+            if self.remaining != 0 { self.frame.fail(CodecError::Truncated) }
+
+            self.code = 0;
+            self.remaining = 0;
+
+            return Some(self.frame.end());
+        }
+
+        if self.remaining == 0 {
+            // Blocks shorter than the maximum are followed by a zero, unless they end the frame.
+            if (self.code != 0) && (self.code as usize != BLOCK + 1) { self.frame.append(0) }
+
+            self.code = byte;
+            self.remaining = byte - 1;
+        } else {
+            self.frame.append(byte);
+            self.remaining -= 1;
+        }
+
+        None
+    }
+
+    fn frame(&self) -> &[u8] {
+        self.frame.data()
+    }
+
+    fn reset(&mut self) {
+        self.frame.clear();
+        self.code = 0;
+        self.remaining = 0;
+    }
+}
+
+impl<const N: usize> Default for CobsDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::SliceSink;
+
+    /// Encodes a whole frame.
+    fn encode(data: &[u8]) -> Vec<u8> {
+        let mut buffer = [0; 1024];
+        let mut sink = SliceSink::new(&mut buffer);
+
+        CobsEncoder::new().encode(data, &mut sink).unwrap();
+
+        sink.data().to_vec()
+    }
+
+    /// Decodes a single frame.
+    fn decode(encoded: &[u8]) -> Result<Vec<u8>, CodecError> {
+        let mut decoder = CobsDecoder::<1024>::new();
+        let mut input = encoded;
+
+        let result = decoder.decode(&mut input).expect("frame not completed").map(|f| f.to_vec());
+        assert!(input.is_empty());
+
+        result
+    }
+
+    /// Payload with a zero every `period` bytes (never if 0).
+    fn payload(len: usize, period: usize) -> Vec<u8> {
+        (0..len).map(|i| match period {
+            0 => (i % 255) as u8 + 1,
+            p if i % p == 0 => 0,
+            _ => i as u8 | 1,
+        }).collect()
+    }
+
+    #[test]
+    fn reference_vectors() {
+        let block: Vec<u8> = (1..=254).collect();
+
+        assert_eq!(encode(&[]), [0x01, 0x00]);
+        assert_eq!(encode(&[0x00]), [0x01, 0x01, 0x00]);
+        assert_eq!(encode(&[0x00, 0x00]), [0x01, 0x01, 0x01, 0x00]);
+        assert_eq!(encode(&[0x11, 0x22, 0x00, 0x33]), [0x03, 0x11, 0x22, 0x02, 0x33, 0x00]);
+        assert_eq!(encode(&[0x11, 0x22, 0x33, 0x44]), [0x05, 0x11, 0x22, 0x33, 0x44, 0x00]);
+        assert_eq!(encode(&[0x11, 0x00, 0x00, 0x00]), [0x02, 0x11, 0x01, 0x01, 0x01, 0x00]);
+
+        // 254 non-zero bytes: a single full block.
+        let expected: Vec<u8> = [0xFF].iter().chain(&block).chain(&[0x00]).copied().collect();
+        assert_eq!(encode(&block), expected);
+
+        // A leading zero, then a full block.
+        let data: Vec<u8> = [0x00].iter().chain(&block).copied().collect();
+        let expected: Vec<u8> = [0x01, 0xFF].iter().chain(&block).chain(&[0x00]).copied().collect();
+        assert_eq!(encode(&data), expected);
+
+        // 255 non-zero bytes: a full block and a block of one byte.
+        let data: Vec<u8> = (1..=255).collect();
+        let expected: Vec<u8> = [0xFF].iter().chain(&block).chain(&[0x02, 0xFF, 0x00]).copied().collect();
+        assert_eq!(encode(&data), expected);
+    }
+
+    #[test]
+    fn round_trip_all_lengths() {
+        for period in [0, 1, 3, 253, 254, 255, 256] {
+            for len in 0..=600 {
+                let data = payload(len, period);
+                let encoded = encode(&data);
+
+                assert!(encoded.len() <= max_encoded_len(len), "len {} period {}", len, period);
+                assert!(!encoded[..encoded.len() - 1].contains(&0), "len {} period {}", len, period);
+                assert_eq!(decode(&encoded), Ok(data), "len {} period {}", len, period);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_free_runs_at_block_boundary() {
+        for run in [253, 254, 255, 508, 509] {
+            let data = payload(run, 0);
+            let encoded = encode(&data);
+
+            assert_eq!(encoded.len(), run + run.div_ceil(BLOCK) + 1, "run {}", run);
+            assert_eq!(decode(&encoded), Ok(data.clone()), "run {}", run);
+
+            // Followed by a zero.
+            let mut zero = data.clone();
+            zero.push(0);
+            assert_eq!(decode(&encode(&zero)), Ok(zero), "run {}", run);
+        }
+    }
+
+    #[test]
+    fn non_canonical_trailing_block_is_accepted() {
+        let block: Vec<u8> = (1..=254).collect();
+        let encoded: Vec<u8> = [0xFF].iter().chain(&block).chain(&[0x01, 0x00]).copied().collect();
+
+        assert_eq!(decode(&encoded), Ok(block));
+    }
+
+    #[test]
+    fn chunked_encoding_and_decoding() {
+        let data = payload(600, 17);
+        let expected = encode(&data);
+
+        for chunk in [1, 2, 7, 253, 254, 255] {
+            let mut buffer = [0; 1024];
+            let mut sink = SliceSink::new(&mut buffer);
+            let mut encoder = CobsEncoder::new();
+
+            for part in data.chunks(chunk) {
+                encoder.push(part, &mut sink).unwrap();
+            }
+            encoder.finish(&mut sink).unwrap();
+
+            assert_eq!(sink.data(), &expected[..], "chunk {}", chunk);
+
+            let mut decoder = CobsDecoder::<600>::new();
+            let mut frames = Vec::new();
+
+            for part in expected.chunks(chunk) {
+                let mut input = part;
+
+                while let Some(frame) = decoder.decode(&mut input) {
+                    frames.push(frame.unwrap().to_vec());
+                }
+            }
+
+            assert_eq!(frames, core::slice::from_ref(&data), "chunk {}", chunk);
+        }
+    }
+
+    #[test]
+    fn back_to_back_frames_and_empty_input() {
+        let mut stream = std::vec![0x00, 0x00];
+        stream.extend(encode(&[1, 2]));
+        stream.extend(encode(&[]));
+        stream.push(0x00);
+        stream.extend(encode(&[0, 3]));
+
+        let mut decoder = CobsDecoder::<8>::new();
+        let mut input = &stream[..];
+        let mut frames = Vec::new();
+
+        while let Some(frame) = decoder.decode(&mut input) {
+            frames.push(frame.unwrap().to_vec());
+        }
+
+        assert_eq!(frames, [std::vec![1, 2], std::vec![], std::vec![0, 3]]);
+    }
+
+    #[test]
+    fn truncated_frame() {
+        assert_eq!(decode(&[0x05, 0x11, 0x22, 0x00]), Err(CodecError::Truncated));
+    }
+
+    #[test]
+    fn oversize_frame_and_resync() {
+        let mut stream = encode(&[1, 2, 3, 4, 5]);
+        stream.extend(encode(&[6, 7]));
+
+        let mut decoder = CobsDecoder::<4>::new();
+        let mut input = &stream[..];
+
+        assert_eq!(decoder.decode(&mut input), Some(Err(CodecError::Oversize)));
+        assert_eq!(decoder.decode(&mut input), Some(Ok(&[6, 7][..])));
+        assert_eq!(decoder.decode(&mut input), None);
+    }
+
+    #[test]
+    fn encoder_reports_full_sink() {
+        let mut buffer = [0; 4];
+        let mut sink = SliceSink::new(&mut buffer);
+
+        assert_eq!(CobsEncoder::new().encode(&[1, 2, 3, 4], &mut sink), Err(CodecError::Oversize));
+    }
+
+    #[test]
+    fn reset_discards_partial_frame() {
+        let mut decoder = CobsDecoder::<8>::new();
+
+        for byte in [0x05, 0x11, 0x22] {
+            assert_eq!(decoder.feed(byte), None);
+        }
+
+        decoder.reset();
+
+        let mut input = &[0x02, 0x33, 0x00][..];
+        assert_eq!(decoder.decode(&mut input), Some(Ok(&[0x33][..])));
+    }
+}
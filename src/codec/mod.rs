@@ -0,0 +1,284 @@
+//! Framing codecs for byte streams.
+//! Streaming, allocation-free encoders and decoders that delimit packets on
+//! links without framing of their own (UART, USB CDC).
+
+
+
+pub mod cobs;
+pub mod slip;
+
+
+
+pub use self::cobs::{ CobsDecoder, CobsEncoder };
+pub use self::slip::{ SlipDecoder, SlipEncoder };
+
+
+
+use crate::buffer::{ BufferWriter, RingBuffer };
+
+
+
+/// Errors of the framing codecs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecError {
+    /// The frame ended in the middle of an encoded sequence.
+    Truncated,
+
+    /// The frame does not fit in the buffer.
+    Oversize,
+
+    /// The frame contains an invalid encoded sequence.
+    Malformed,
+}
+
+
+
+/// Destination of encoded bytes.
+pub trait Sink {
+    /// Writes a byte. Fails with `Oversize` if the sink is full.
+    fn put(&mut self, byte: u8) -> Result<(), CodecError>;
+}
+
+impl<S: Sink> Sink for &mut S {
+    fn put(&mut self, byte: u8) -> Result<(), CodecError> {
+        S::put(self, byte)
+    }
+}
+
+impl<const N: usize> Sink for RingBuffer<u8, N> {
+    fn put(&mut self, byte: u8) -> Result<(), CodecError> {
+        self.push(byte).map_err(|_| CodecError::Oversize)
+    }
+}
+
+impl Sink for BufferWriter<u8> {
+    fn put(&mut self, byte: u8) -> Result<(), CodecError> {
+        self.push(byte).map_err(|_| CodecError::Oversize)
+    }
+}
+
+
+
+/// Sink writing into a slice.
+pub struct SliceSink<'a> {
+    /// Destination.
+    buffer: &'a mut [u8],
+
+    /// Number of bytes written.
+    len: usize,
+}
+
+impl<'a> SliceSink<'a> {
+    /// Creates an empty sink over the slice.
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    /// Number of bytes written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if nothing was written.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes written.
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+impl<'a> Sink for SliceSink<'a> {
+    fn put(&mut self, byte: u8) -> Result<(), CodecError> {
+        let slot = self.buffer.get_mut(self.len).ok_or(CodecError::Oversize)?;
+
+        *slot = byte;
+        self.len += 1;
+
+        Ok(())
+    }
+}
+
+
+
+/// Streaming frame encoder.
+/// A frame may be written in several `push` calls and is closed by `finish`.
+pub trait Encoder {
+    /// Encodes a chunk of the current frame.
+    fn push<S: Sink>(&mut self, data: &[u8], sink: &mut S) -> Result<(), CodecError>;
+
+    /// Closes the current frame. The encoder is then ready for the next one.
+    fn finish<S: Sink>(&mut self, sink: &mut S) -> Result<(), CodecError>;
+
+    /// Encodes a whole frame.
+    fn encode<S: Sink>(&mut self, data: &[u8], sink: &mut S) -> Result<(), CodecError> {
+        self.push(data, sink)?;
+        self.finish(sink)
+    }
+}
+
+
+
+/// Streaming frame decoder.
+/// Bytes may arrive in any chunking; a frame split across reads is
+/// reassembled in the decoder. After an error the decoder resynchronizes on
+/// the next frame delimiter.
+pub trait Decoder {
+    /// Feeds a byte. Returns the length of the frame when the byte completes
+    /// one, or the error of a corrupted frame.
+    fn feed(&mut self, byte: u8) -> Option<Result<usize, CodecError>>;
+
+    /// Last completed frame. Valid until the next byte is fed.
+    fn frame(&self) -> &[u8];
+
+    /// Discards the partial frame.
+    fn reset(&mut self);
+
+    /// Feeds bytes until a frame is completed, advancing `input` past the
+    /// consumed bytes. Returns `None` if the input ran out first.
+    fn decode<'a>(&'a mut self, input: &mut &[u8]) -> Option<Result<&'a [u8], CodecError>> {
+        while let Some((byte, rest)) = input.split_first() {
+            *input = rest;
+
+            if let Some(result) = self.feed(*byte) {
+                return Some(result.map(|_| self.frame()));
+            }
+        }
+
+        None
+    }
+
+    /// Pops bytes from the ring until a frame is completed.
+    /// Returns `None` if the ring ran out first.
+    fn decode_ring<const N: usize>(&mut self, ring: &RingBuffer<u8, N>) -> Option<Result<&[u8], CodecError>> {
+        while let Some(byte) = ring.pop() {
+            if let Some(result) = self.feed(byte) {
+                return Some(result.map(|_| self.frame()));
+            }
+        }
+
+        None
+    }
+}
+
+
+
+/// Frame being reassembled by a decoder.
+struct Frame<const N: usize> {
+    /// Decoded bytes.
+    data: [u8; N],
+
+    /// Number of decoded bytes.
+    len: usize,
+
+    /// First error detected in the frame.
+    error: Option<CodecError>,
+
+    /// Set when the frame has been returned.
+    complete: bool,
+}
+
+impl<const N: usize> Frame<N> {
+    /// Static initializer.
+    const fn new() -> Self {
+        Self { data: [0; N], len: 0, error: None, complete: false }
+    }
+
+    /// Discards the frame.
+    fn clear(&mut self) {
+        self.len = 0;
+        self.error = None;
+        self.complete = false;
+    }
+
+    /// Discards the frame if it was already returned.
+    fn next(&mut self) {
+        if self.complete { self.clear() }
+    }
+
+    /// Appends a decoded byte.
+    fn append(&mut self, byte: u8) {
+        if self.error.is_some() { return }
+
+        match self.data.get_mut(self.len) {
+            Some(slot) => {
+                *slot = byte;
+                self.len += 1;
+            },
+            _ => self.error = Some(CodecError::Oversize),
+        }
+    }
+
+    /// Marks the frame as corrupted. Only the first error is kept.
+    fn fail(&mut self, error: CodecError) {
+        self.error.get_or_insert(error);
+    }
+
+    /// Closes the frame.
+    fn end(&mut self) -> Result<usize, CodecError> {
+        self.complete = true;
+
+        match self.error {
+            Some(error) => {
+                self.len = 0;
+                Err(error)
+            },
+            _ => Ok(self.len),
+        }
+    }
+
+    /// Completed frame.
+    fn data(&self) -> &[u8] {
+        match self.complete {
+            true => &self.data[..self.len],
+            _ => &[],
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_sink_fills_then_fails() {
+        let mut buffer = [0; 2];
+        let mut sink = SliceSink::new(&mut buffer);
+
+        assert!(sink.is_empty());
+        assert_eq!(sink.put(1), Ok(()));
+        assert_eq!(sink.put(2), Ok(()));
+        assert_eq!(sink.put(3), Err(CodecError::Oversize));
+        assert_eq!(sink.data(), [1, 2]);
+    }
+
+    #[test]
+    fn decode_ring_pops_until_frame() {
+        let mut ring = RingBuffer::<u8, 16>::new();
+        let mut encoder = CobsEncoder::new();
+        let mut decoder = CobsDecoder::<8>::new();
+
+        encoder.encode(&[1, 0, 2], &mut ring).unwrap();
+        encoder.encode(&[3], &mut ring).unwrap();
+
+        assert_eq!(decoder.decode_ring(&ring), Some(Ok(&[1, 0, 2][..])));
+        assert_eq!(decoder.decode_ring(&ring), Some(Ok(&[3][..])));
+        assert_eq!(decoder.decode_ring(&ring), None);
+    }
+
+    #[test]
+    fn frame_is_cleared_by_the_next_byte() {
+        let mut decoder = SlipDecoder::<8>::new();
+        let mut input = &[slip::END, 0x01, slip::END, 0x02][..];
+
+        assert_eq!(decoder.decode(&mut input), Some(Ok(&[0x01][..])));
+        assert_eq!(decoder.frame(), [0x01]);
+
+        assert_eq!(decoder.feed(0x03), None);
+        assert_eq!(decoder.frame(), []);
+    }
+}
@@ -0,0 +1,275 @@
+//! Serial Line Internet Protocol (SLIP, RFC 1055) framing.
+//! Frames are delimited by `END` bytes, and `END` / `ESC` bytes in the
+//! payload are replaced by two byte escape sequences.
+
+
+
+use super::{ CodecError, Decoder, Encoder, Frame, Sink };
+
+
+
+/// Frame delimiter.
+pub const END: u8 = 0xC0;
+
+/// Escape byte.
+pub const ESC: u8 = 0xDB;
+
+/// Escaped `END`.
+pub const ESC_END: u8 = 0xDC;
+
+/// Escaped `ESC`.
+pub const ESC_ESC: u8 = 0xDD;
+
+
+
+/// Maximum length of an encoded frame, including both delimiters.
+pub const fn max_encoded_len(len: usize) -> usize {
+    (2 * len) + 2
+}
+
+
+
+/// Streaming SLIP encoder.
+/// Each frame starts with an `END` to flush any line noise at the receiver.
+/// An empty payload encodes to `END END`, which decoders skip as noise:
+/// empty frames cannot be sent.
+#[derive(Default)]
+pub struct SlipEncoder {
+    /// Set once the leading `END` of the current frame was written.
+    started: bool,
+}
+
+impl SlipEncoder {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self { started: false }
+    }
+
+    /// Writes the leading `END` of the frame if needed.
+    fn start<S: Sink>(&mut self, sink: &mut S) -> Result<(), CodecError> {
+        if !self.started {
+            sink.put(END)?;
+            self.started = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl Encoder for SlipEncoder {
+    fn push<S: Sink>(&mut self, data: &[u8], sink: &mut S) -> Result<(), CodecError> {
+        self.start(sink)?;
+
+        for byte in data {
+            match *byte {
+                END => { sink.put(ESC)?; sink.put(ESC_END)?; },
+                ESC => { sink.put(ESC)?; sink.put(ESC_ESC)?; },
+                byte => sink.put(byte)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish<S: Sink>(&mut self, sink: &mut S) -> Result<(), CodecError> {
+        self.start(sink)?;
+        self.started = false;
+
+        sink.put(END)
+    }
+}
+
+
+
+/// Streaming SLIP decoder for frames of up to `N` bytes.
+/// Empty frames (back to back `END` bytes) are skipped.
+pub struct SlipDecoder<const N: usize> {
+    /// Frame being decoded.
+    frame: Frame<N>,
+
+    /// Set after an `ESC` byte.
+    escaped: bool,
+
+    /// Set once a byte of the current frame was received.
+    active: bool,
+}
+
+impl<const N: usize> SlipDecoder<N> {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self { frame: Frame::new(), escaped: false, active: false }
+    }
+}
+
+impl<const N: usize> Decoder for SlipDecoder<N> {
+    fn feed(&mut self, byte: u8) -> Option<Result<usize, CodecError>> {
+        self.frame.next();
+
+        if byte == END {
+            if !self.active { return None }
+
+            if self.escaped { self.frame.fail(CodecError::Truncated) }
+
+            self.escaped = false;
+            self.active = false;
+
+            return Some(self.frame.end());
+        }
+
+        self.active = true;
+
+        match (self.escaped, byte) {
+            (false, ESC) => {
+                self.escaped = true;
+                return None;
+            },
+            (false, byte) => self.frame.append(byte),
+
+            (true, ESC_END) => self.frame.append(END),
+            (true, ESC_ESC) => self.frame.append(ESC),
+            (true, _) => self.frame.fail(CodecError::Malformed),
+        }
+
+        self.escaped = false;
+
+        None
+    }
+
+    fn frame(&self) -> &[u8] {
+        self.frame.data()
+    }
+
+    fn reset(&mut self) {
+        self.frame.clear();
+        self.escaped = false;
+        self.active = false;
+    }
+}
+
+impl<const N: usize> Default for SlipDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::SliceSink;
+
+    /// Encodes a whole frame.
+    fn encode(data: &[u8]) -> Vec<u8> {
+        let mut buffer = [0; 2048];
+        let mut sink = SliceSink::new(&mut buffer);
+
+        SlipEncoder::new().encode(data, &mut sink).unwrap();
+
+        sink.data().to_vec()
+    }
+
+    /// Decodes the frames of a stream.
+    fn decode<const N: usize>(stream: &[u8]) -> Vec<Result<Vec<u8>, CodecError>> {
+        let mut decoder = SlipDecoder::<N>::new();
+        let mut input = stream;
+        let mut frames = Vec::new();
+
+        while let Some(frame) = decoder.decode(&mut input) {
+            frames.push(frame.map(|f| f.to_vec()));
+        }
+
+        frames
+    }
+
+    #[test]
+    fn reference_vectors() {
+        assert_eq!(encode(&[0x01, 0x02]), [END, 0x01, 0x02, END]);
+        assert_eq!(encode(&[END]), [END, ESC, ESC_END, END]);
+        assert_eq!(encode(&[ESC]), [END, ESC, ESC_ESC, END]);
+        assert_eq!(encode(&[ESC_END, ESC, END, ESC_ESC]), [END, ESC_END, ESC, ESC_ESC, ESC, ESC_END, ESC_ESC, END]);
+    }
+
+    #[test]
+    fn round_trip_all_lengths() {
+        for len in 1..=600 {
+            // Dense in special bytes.
+            let data: Vec<u8> = (0..len).map(|i| [END, ESC, ESC_END, ESC_ESC, i as u8][i % 5]).collect();
+            let encoded = encode(&data);
+
+            assert!(encoded.len() <= max_encoded_len(len), "len {}", len);
+            assert!(!encoded[1..encoded.len() - 1].contains(&END), "len {}", len);
+            assert_eq!(decode::<600>(&encoded), [Ok(data)], "len {}", len);
+        }
+    }
+
+    #[test]
+    fn empty_payload_is_not_a_frame() {
+        assert_eq!(encode(&[]), [END, END]);
+        assert_eq!(decode::<8>(&encode(&[])), []);
+    }
+
+    #[test]
+    fn chunked_encoding_and_decoding() {
+        let data: Vec<u8> = (0..600).map(|i| (i * 7) as u8).collect();
+        let expected = encode(&data);
+
+        for chunk in [1, 2, 3, 64, 255] {
+            let mut buffer = [0; 2048];
+            let mut sink = SliceSink::new(&mut buffer);
+            let mut encoder = SlipEncoder::new();
+
+            for part in data.chunks(chunk) {
+                encoder.push(part, &mut sink).unwrap();
+            }
+            encoder.finish(&mut sink).unwrap();
+
+            assert_eq!(sink.data(), &expected[..], "chunk {}", chunk);
+
+            // An escape sequence may be split between reads.
+            let mut decoder = SlipDecoder::<600>::new();
+            let mut frames = Vec::new();
+
+            for part in expected.chunks(chunk) {
+                let mut input = part;
+
+                while let Some(frame) = decoder.decode(&mut input) {
+                    frames.push(frame.unwrap().to_vec());
+                }
+            }
+
+            assert_eq!(frames, core::slice::from_ref(&data), "chunk {}", chunk);
+        }
+    }
+
+    #[test]
+    fn truncated_escape() {
+        assert_eq!(decode::<8>(&[END, 0x01, ESC, END]), [Err(CodecError::Truncated)]);
+    }
+
+    #[test]
+    fn malformed_escape_and_resync() {
+        let mut stream = std::vec![END, 0x01, ESC, 0x02, 0x03, END];
+        stream.extend(encode(&[0x04]));
+
+        assert_eq!(decode::<8>(&stream), [Err(CodecError::Malformed), Ok(std::vec![0x04])]);
+    }
+
+    #[test]
+    fn oversize_frame_and_resync() {
+        let mut stream = encode(&[1, 2, 3, 4, 5]);
+        stream.extend(encode(&[6]));
+
+        assert_eq!(decode::<4>(&stream), [Err(CodecError::Oversize), Ok(std::vec![6])]);
+    }
+
+    #[test]
+    fn encoder_reports_full_sink() {
+        let mut buffer = [0; 3];
+        let mut sink = SliceSink::new(&mut buffer);
+
+        assert_eq!(SlipEncoder::new().encode(&[END], &mut sink), Err(CodecError::Oversize));
+    }
+}
@@ -0,0 +1,80 @@
+//! Catalogue of standard CRC algorithms.
+//! Names and parameters follow the CRC RevEng catalogue.
+
+
+
+use super::Algorithm;
+
+
+
+/// CRC-8/SMBUS. SMBus Packet Error Checking.
+pub const CRC_8_SMBUS: Algorithm<u8> = Algorithm { poly: 0x07, init: 0x00, reflect: false, xorout: 0x00, check: 0xF4 };
+
+/// CRC-8/MAXIM-DOW. 1-Wire ROM codes and scratchpads.
+pub const CRC_8_MAXIM_DOW: Algorithm<u8> = Algorithm { poly: 0x31, init: 0x00, reflect: true, xorout: 0x00, check: 0xA1 };
+
+/// CRC-8/AUTOSAR.
+pub const CRC_8_AUTOSAR: Algorithm<u8> = Algorithm { poly: 0x2F, init: 0xFF, reflect: false, xorout: 0xFF, check: 0xDF };
+
+/// CRC-8/SAE-J1850.
+pub const CRC_8_SAE_J1850: Algorithm<u8> = Algorithm { poly: 0x1D, init: 0xFF, reflect: false, xorout: 0xFF, check: 0x4B };
+
+/// CRC-16/ARC.
+pub const CRC_16_ARC: Algorithm<u16> = Algorithm { poly: 0x8005, init: 0x0000, reflect: true, xorout: 0x0000, check: 0xBB3D };
+
+/// CRC-16/MODBUS. Modbus RTU frames.
+pub const CRC_16_MODBUS: Algorithm<u16> = Algorithm { poly: 0x8005, init: 0xFFFF, reflect: true, xorout: 0x0000, check: 0x4B37 };
+
+/// CRC-16/USB. USB data packets.
+pub const CRC_16_USB: Algorithm<u16> = Algorithm { poly: 0x8005, init: 0xFFFF, reflect: true, xorout: 0xFFFF, check: 0xB4C8 };
+
+/// CRC-16/IBM-3740, also known as CRC-16/CCITT-FALSE.
+pub const CRC_16_IBM_3740: Algorithm<u16> = Algorithm { poly: 0x1021, init: 0xFFFF, reflect: false, xorout: 0x0000, check: 0x29B1 };
+
+/// CRC-16/IBM-SDLC, also known as CRC-16/X-25.
+pub const CRC_16_IBM_SDLC: Algorithm<u16> = Algorithm { poly: 0x1021, init: 0xFFFF, reflect: true, xorout: 0xFFFF, check: 0x906E };
+
+/// CRC-16/KERMIT.
+pub const CRC_16_KERMIT: Algorithm<u16> = Algorithm { poly: 0x1021, init: 0x0000, reflect: true, xorout: 0x0000, check: 0x2189 };
+
+/// CRC-16/XMODEM.
+pub const CRC_16_XMODEM: Algorithm<u16> = Algorithm { poly: 0x1021, init: 0x0000, reflect: false, xorout: 0x0000, check: 0x31C3 };
+
+/// CRC-32/ISO-HDLC. Ethernet, zlib, PNG.
+pub const CRC_32_ISO_HDLC: Algorithm<u32> = Algorithm { poly: 0x04C11DB7, init: 0xFFFFFFFF, reflect: true, xorout: 0xFFFFFFFF, check: 0xCBF43926 };
+
+/// CRC-32/ISCSI, also known as CRC-32C (Castagnoli).
+pub const CRC_32_ISCSI: Algorithm<u32> = Algorithm { poly: 0x1EDC6F41, init: 0xFFFFFFFF, reflect: true, xorout: 0xFFFFFFFF, check: 0xE3069283 };
+
+/// CRC-32/BZIP2.
+pub const CRC_32_BZIP2: Algorithm<u32> = Algorithm { poly: 0x04C11DB7, init: 0xFFFFFFFF, reflect: false, xorout: 0xFFFFFFFF, check: 0xFC891918 };
+
+/// CRC-32/MPEG-2. Default configuration of the STM32 CRC unit.
+pub const CRC_32_MPEG_2: Algorithm<u32> = Algorithm { poly: 0x04C11DB7, init: 0xFFFFFFFF, reflect: false, xorout: 0x00000000, check: 0x0376E6E7 };
+
+
+
+/// Checks at compile time that an algorithm produces its check value.
+macro_rules! check {
+    ($($w:ty => $alg:ident),* $(,)?) => {
+        $( const _: () = assert!(super::Crc::<$w>::new(&$alg).checksum(b"123456789") == $alg.check); )*
+    };
+}
+
+check! {
+    u8 => CRC_8_SMBUS,
+    u8 => CRC_8_MAXIM_DOW,
+    u8 => CRC_8_AUTOSAR,
+    u8 => CRC_8_SAE_J1850,
+    u16 => CRC_16_ARC,
+    u16 => CRC_16_MODBUS,
+    u16 => CRC_16_USB,
+    u16 => CRC_16_IBM_3740,
+    u16 => CRC_16_IBM_SDLC,
+    u16 => CRC_16_KERMIT,
+    u16 => CRC_16_XMODEM,
+    u32 => CRC_32_ISO_HDLC,
+    u32 => CRC_32_ISCSI,
+    u32 => CRC_32_BZIP2,
+    u32 => CRC_32_MPEG_2,
+}
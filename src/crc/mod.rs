@@ -0,0 +1,60 @@
+//! Cyclic Redundancy Checks.
+//! Parametric CRC-8/16/32 computed with lookup tables generated at compile
+//! time, a catalogue of standard algorithms and an abstraction to route the
+//! computation to a hardware CRC unit.
+
+
+
+mod catalog;
+mod table;
+
+
+
+pub use self::catalog::*;
+pub use self::table::{ Crc, Digest };
+
+
+
+/// Parameters of a CRC algorithm (Rocksoft model).
+/// The width is given by the register type `W`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Algorithm<W> {
+    /// Generator polynomial, without the leading term, MSB first.
+    pub poly: W,
+
+    /// Initial value of the register.
+    pub init: W,
+
+    /// Input bytes and output value are bit reversed (LSB first).
+    pub reflect: bool,
+
+    /// Value XORed with the register to produce the CRC.
+    pub xorout: W,
+
+    /// CRC of the ASCII string `"123456789"`.
+    pub check: W,
+}
+
+
+
+/// CRC computation unit.
+/// Implemented in software by `Digest`, and by HALs to route the computation
+/// to a hardware CRC unit configured for the same algorithm. Drivers that are
+/// generic over the engine then run on either.
+pub trait CrcEngine<W> {
+    /// Restarts the computation.
+    fn reset(&mut self);
+
+    /// Feeds data to the computation.
+    fn update(&mut self, data: &[u8]);
+
+    /// CRC of the data fed since the last reset.
+    fn finalize(&mut self) -> W;
+
+    /// Computes the CRC of the data.
+    fn checksum(&mut self, data: &[u8]) -> W {
+        self.reset();
+        self.update(data);
+        self.finalize()
+    }
+}
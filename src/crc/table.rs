@@ -0,0 +1,202 @@
+//! Table driven CRC computation.
+
+
+
+use super::{ Algorithm, CrcEngine };
+
+
+
+/// CRC algorithm with its lookup table.
+/// Meant to be built in a `const` or `static`, so that the table is generated
+/// at compile time and placed in flash.
+pub struct Crc<W: 'static> {
+    /// Parameters of the algorithm.
+    algorithm: Algorithm<W>,
+
+    /// Register update for each input byte.
+    table: [W; 256],
+}
+
+/// Streaming computation of a CRC in software.
+pub struct Digest<'a, W: 'static> {
+    /// Algorithm and table.
+    crc: &'a Crc<W>,
+
+    /// Current value of the register.
+    register: W,
+}
+
+
+
+macro_rules! crc {
+    ($w:ty) => {
+        impl Crc<$w> {
+            /// Builds the lookup table of the algorithm.
+            pub const fn new(algorithm: &Algorithm<$w>) -> Self {
+                let bits = <$w>::BITS;
+                let poly = algorithm.poly.reverse_bits();
+
+                let mut table = [0; 256];
+                let mut i = 0;
+
+                while i < 256 {
+                    let mut entry: $w;
+                    let mut bit = 0;
+
+                    if algorithm.reflect {
+                        entry = i as $w;
+
+                        while bit < 8 {
+                            entry = if (entry & 1) != 0 { (entry >> 1) ^ poly } else { entry >> 1 };
+                            bit += 1;
+                        }
+                    } else {
+                        entry = (i as $w) << (bits - 8);
+
+                        while bit < 8 {
+                            entry = if (entry >> (bits - 1)) != 0 { (entry << 1) ^ algorithm.poly } else { entry << 1 };
+                            bit += 1;
+                        }
+                    }
+
+                    table[i] = entry;
+                    i += 1;
+                }
+
+                Self { algorithm: *algorithm, table }
+            }
+
+            /// Parameters of the algorithm.
+            pub const fn algorithm(&self) -> &Algorithm<$w> {
+                &self.algorithm
+            }
+
+            /// Initial value of the register.
+            pub const fn init(&self) -> $w {
+                match self.algorithm.reflect {
+                    true => self.algorithm.init.reverse_bits(),
+                    _ => self.algorithm.init,
+                }
+            }
+
+            /// Feeds data to the register and returns its new value.
+            pub const fn update(&self, mut register: $w, data: &[u8]) -> $w {
+                let bits = <$w>::BITS;
+                let mut i = 0;
+
+                while i < data.len() {
+                    let byte = data[i] as $w;
+
+                    register = match self.algorithm.reflect {
+                        true => self.table[((register ^ byte) & 0xFF) as usize] ^ match register.checked_shr(8) { Some(r) => r, _ => 0 },
+                        _ => self.table[(((register >> (bits - 8)) ^ byte) & 0xFF) as usize] ^ match register.checked_shl(8) { Some(r) => r, _ => 0 },
+                    };
+
+                    i += 1;
+                }
+
+                register
+            }
+
+            /// CRC of the given register value.
+            pub const fn finalize(&self, register: $w) -> $w {
+                register ^ self.algorithm.xorout
+            }
+
+            /// Computes the CRC of the data.
+            pub const fn checksum(&self, data: &[u8]) -> $w {
+                self.finalize(self.update(self.init(), data))
+            }
+
+            /// Starts a streaming computation.
+            pub const fn digest(&self) -> Digest<'_, $w> {
+                Digest { crc: self, register: self.init() }
+            }
+        }
+
+        impl<'a> Digest<'a, $w> {
+            /// Feeds data to the computation.
+            pub fn update(&mut self, data: &[u8]) {
+                self.register = self.crc.update(self.register, data);
+            }
+
+            /// CRC of the data fed so far.
+            pub const fn finalize(&self) -> $w {
+                self.crc.finalize(self.register)
+            }
+        }
+
+        impl<'a> CrcEngine<$w> for Digest<'a, $w> {
+            fn reset(&mut self) {
+                self.register = self.crc.init();
+            }
+
+            fn update(&mut self, data: &[u8]) {
+                Digest::<$w>::update(self, data)
+            }
+
+            fn finalize(&mut self) -> $w {
+                Digest::<$w>::finalize(self)
+            }
+        }
+    };
+}
+
+crc!(u8);
+crc!(u16);
+crc!(u32);
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::*;
+
+    const CHECK: &[u8] = b"123456789";
+
+    /// Feeds the check string in every split to a digest and to the engine.
+    macro_rules! streaming {
+        ($($w:ty => $alg:ident),*) => {$({
+            let crc = Crc::<$w>::new(&$alg);
+
+            for split in 0..=CHECK.len() {
+                let (a, b) = CHECK.split_at(split);
+
+                let mut digest = crc.digest();
+                digest.update(a);
+                digest.update(b);
+                assert_eq!(digest.finalize(), $alg.check, "{} split at {}", stringify!($alg), split);
+
+                assert_eq!(crc.finalize(crc.update(crc.update(crc.init(), a), b)), $alg.check);
+
+                let mut engine = crc.digest();
+                engine.update(b"garbage");
+                assert_eq!(CrcEngine::checksum(&mut engine, CHECK), $alg.check);
+            }
+
+            let mut digest = crc.digest();
+            for byte in CHECK { digest.update(core::slice::from_ref(byte)); }
+            assert_eq!(digest.finalize(), $alg.check);
+        })*};
+    }
+
+    #[test]
+    fn split_updates() {
+        streaming!(
+            u8 => CRC_8_SMBUS, u8 => CRC_8_MAXIM_DOW, u8 => CRC_8_AUTOSAR, u8 => CRC_8_SAE_J1850,
+            u16 => CRC_16_ARC, u16 => CRC_16_MODBUS, u16 => CRC_16_USB, u16 => CRC_16_IBM_3740,
+            u16 => CRC_16_IBM_SDLC, u16 => CRC_16_KERMIT, u16 => CRC_16_XMODEM,
+            u32 => CRC_32_ISO_HDLC, u32 => CRC_32_ISCSI, u32 => CRC_32_BZIP2, u32 => CRC_32_MPEG_2
+        );
+    }
+
+    #[test]
+    fn empty_data() {
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+        assert_eq!(crc.checksum(&[]), 0);
+        assert_eq!(crc.digest().finalize(), 0);
+    }
+}
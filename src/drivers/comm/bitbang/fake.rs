@@ -0,0 +1,152 @@
+//! Simulated lines for the tests of the bit-banged drivers.
+//! Pins and delay share a bus: every level driven by the controller is
+//! logged with the current time and forwarded to a simulated device, which
+//! answers on the input lines or holds lines low (wired AND). Time only
+//! advances through the delay.
+
+
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::vec::Vec;
+
+use crate::drivers::gpio::{ InputPin, OutputPin };
+use crate::time::Delay;
+
+
+
+/// Number of simulated lines.
+pub const LINES: usize = 4;
+
+
+
+/// Simulated device on the bus.
+pub trait Device {
+    /// The controller drove a line to the given level. `lines` holds the
+    /// levels driven by the controller after the change.
+    fn output(&mut self, _line: usize, _high: bool, _lines: &[bool; LINES], _time: u64) {}
+
+    /// Level of a line as seen by the controller, if it releases it.
+    fn input(&mut self, _line: usize, _time: u64) -> bool {
+        true
+    }
+}
+
+/// Device that never drives the bus.
+pub struct Idle;
+
+impl Device for Idle {}
+
+
+
+/// State of the simulated bus.
+struct Sim<D> {
+    /// Levels driven by the controller.
+    lines: [bool; LINES],
+
+    /// Time in microseconds.
+    time: u64,
+
+    /// Changes driven by the controller: time, line and level.
+    log: Vec<(u64, usize, bool)>,
+
+    /// Simulated device.
+    device: D,
+}
+
+/// Simulated bus.
+pub struct Bus<D>(Rc<RefCell<Sim<D>>>);
+
+impl<D: Device> Bus<D> {
+    /// Creates a bus with all lines released.
+    pub fn new(device: D) -> Self {
+        Self(Rc::new(RefCell::new(Sim { lines: [true; LINES], time: 0, log: Vec::new(), device })))
+    }
+
+    /// Pin on the given line.
+    pub fn pin(&self, line: usize) -> Pin<D> {
+        Pin { sim: self.0.clone(), line }
+    }
+
+    /// Delay advancing the time of the bus.
+    pub fn delay(&self) -> FakeDelay<D> {
+        FakeDelay { sim: self.0.clone() }
+    }
+
+    /// Current time in microseconds.
+    pub fn time(&self) -> u64 {
+        self.0.borrow().time
+    }
+
+    /// Changes driven by the controller on the given line: time and level.
+    pub fn log(&self, line: usize) -> Vec<(u64, bool)> {
+        self.0.borrow().log.iter().filter(|e| e.1 == line).map(|e| (e.0, e.2)).collect()
+    }
+
+    /// Level driven by the controller on the given line at the given time.
+    pub fn level(&self, line: usize, time: u64) -> bool {
+        self.log(line).iter().take_while(|e| e.0 <= time).last().map(|e| e.1).unwrap_or(true)
+    }
+
+    /// Accesses the simulated device.
+    pub fn device<T>(&self, f: impl FnOnce(&mut D) -> T) -> T {
+        f(&mut self.0.borrow_mut().device)
+    }
+}
+
+
+
+/// Pin on a simulated line.
+pub struct Pin<D> {
+    /// Simulated bus.
+    sim: Rc<RefCell<Sim<D>>>,
+
+    /// Line of the pin.
+    line: usize,
+}
+
+impl<D: Device> Pin<D> {
+    /// Drives the line.
+    fn drive(&mut self, high: bool) {
+        let sim = &mut *self.sim.borrow_mut();
+
+        if sim.lines[self.line] == high { return }
+
+        sim.lines[self.line] = high;
+        sim.log.push((sim.time, self.line, high));
+        sim.device.output(self.line, high, &sim.lines, sim.time);
+    }
+}
+
+impl<D: Device> OutputPin for Pin<D> {
+    fn set_high(&mut self) {
+        self.drive(true)
+    }
+
+    fn set_low(&mut self) {
+        self.drive(false)
+    }
+}
+
+impl<D: Device> InputPin for Pin<D> {
+    fn is_high(&self) -> bool {
+        let sim = &mut *self.sim.borrow_mut();
+        let time = sim.time;
+
+        sim.lines[self.line] && sim.device.input(self.line, time)
+    }
+}
+
+
+
+/// Delay advancing the time of a simulated bus.
+pub struct FakeDelay<D> {
+    /// Simulated bus.
+    sim: Rc<RefCell<Sim<D>>>,
+}
+
+impl<D> Delay for FakeDelay<D> {
+    fn delay_us(&mut self, us: u32) {
+        self.sim.borrow_mut().time += us as u64;
+    }
+}
@@ -0,0 +1,529 @@
+//! Bit-banged I2C controller.
+
+
+
+use crate::drivers::gpio::{ InputPin, OutputPin };
+use crate::time::Delay;
+
+use super::super::{ CommunicationError, DriverError, ErrorKind, I2CAddress, I2CDriver, I2COperation };
+
+
+
+/// Errors of the bit-banged I2C controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitBangI2CError {
+    /// The target did not acknowledge its address.
+    AddressNack,
+
+    /// The target did not acknowledge a written byte.
+    DataNack,
+
+    /// Another controller drove SDA low while this one released it.
+    ArbitrationLost,
+
+    /// A target stretched the clock longer than the allowed time.
+    Timeout,
+}
+
+impl DriverError for BitBangI2CError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            BitBangI2CError::AddressNack => ErrorKind::AddressNack,
+            BitBangI2CError::DataNack => ErrorKind::DataNack,
+            BitBangI2CError::ArbitrationLost => ErrorKind::ArbitrationLost,
+            BitBangI2CError::Timeout => ErrorKind::Timeout,
+        }
+    }
+}
+
+
+
+/// Bit-banged I2C controller.
+/// Both pins must be configured as open drain outputs that can be read back.
+pub struct BitBangI2C<SCL, SDA, D> {
+    /// Clock line.
+    scl: SCL,
+
+    /// Data line.
+    sda: SDA,
+
+    /// Delay source.
+    delay: D,
+
+    /// Half of the clock period in microseconds.
+    half: u32,
+
+    /// Maximum clock stretching in microseconds.
+    stretch: u32,
+}
+
+impl<SCL, SDA, D> BitBangI2C<SCL, SDA, D>
+    where SCL: OutputPin + InputPin, SDA: OutputPin + InputPin, D: Delay
+{
+    /// Creates a controller running at (at most) the given frequency in Hz.
+    /// Clock stretching is allowed for up to 10 ms.
+    pub fn new(mut scl: SCL, mut sda: SDA, delay: D, frequency: u32) -> Self {
+        scl.set_high();
+        sda.set_high();
+
+        let half = (500_000 / frequency.max(1)).max(1);
+
+        Self { scl, sda, delay, half, stretch: 10_000 }
+    }
+
+    /// Configures the maximum clock stretching in microseconds.
+    pub fn stretching(&mut self, us: u32) {
+        self.stretch = us;
+    }
+
+    /// Consumes the driver and returns the pins and the delay source.
+    pub fn release(self) -> (SCL, SDA, D) {
+        (self.scl, self.sda, self.delay)
+    }
+
+    /// Releases SCL and waits while a target stretches the clock.
+    fn scl_high(&mut self) -> Result<(), BitBangI2CError> {
+        self.scl.set_high();
+
+        let mut waited = 0;
+        while self.scl.is_low() {
+            if waited >= self.stretch { return Err(BitBangI2CError::Timeout) }
+
+            self.delay.delay_us(1);
+            waited += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Generates a (repeated) START condition.
+    fn start(&mut self) -> Result<(), BitBangI2CError> {
+        self.sda.set_high();
+        self.delay.delay_us(self.half);
+        self.scl_high()?;
+        self.delay.delay_us(self.half);
+
+        if self.sda.is_low() { return Err(BitBangI2CError::ArbitrationLost) }
+
+        self.sda.set_low();
+        self.delay.delay_us(self.half);
+        self.scl.set_low();
+
+        Ok(())
+    }
+
+    /// Generates a STOP condition.
+    fn stop(&mut self) -> Result<(), BitBangI2CError> {
+        self.sda.set_low();
+        self.delay.delay_us(self.half);
+        self.scl_high()?;
+        self.delay.delay_us(self.half);
+        self.sda.set_high();
+        self.delay.delay_us(self.half);
+
+        Ok(())
+    }
+
+    /// Writes a single bit.
+    fn write_bit(&mut self, bit: bool) -> Result<(), BitBangI2CError> {
+        self.sda.set(bit);
+        self.delay.delay_us(self.half);
+        self.scl_high()?;
+
+        if bit && self.sda.is_low() { return Err(BitBangI2CError::ArbitrationLost) }
+
+        self.delay.delay_us(self.half);
+        self.scl.set_low();
+
+        Ok(())
+    }
+
+    /// Reads a single bit.
+    fn read_bit(&mut self) -> Result<bool, BitBangI2CError> {
+        self.sda.set_high();
+        self.delay.delay_us(self.half);
+        self.scl_high()?;
+
+        let bit = self.sda.is_high();
+
+        self.delay.delay_us(self.half);
+        self.scl.set_low();
+
+        Ok(bit)
+    }
+
+    /// Writes a byte and returns `true` if it was acknowledged.
+    fn write_byte(&mut self, byte: u8) -> Result<bool, BitBangI2CError> {
+        for i in (0..8).rev() {
+            self.write_bit((byte >> i) & 1 != 0)?;
+        }
+
+        Ok(!self.read_bit()?)
+    }
+
+    /// Reads a byte and acknowledges it if requested.
+    fn read_byte(&mut self, ack: bool) -> Result<u8, BitBangI2CError> {
+        let mut byte = 0;
+
+        for _ in 0..8 {
+            byte = (byte << 1) | (self.read_bit()? as u8);
+        }
+
+        self.write_bit(!ack)?;
+
+        Ok(byte)
+    }
+
+    /// Issues a (repeated) START and the address in the given direction.
+    fn address<A: I2CAddress>(&mut self, addr: A, read: bool, restart: bool) -> Result<(), BitBangI2CError> {
+        self.start()?;
+
+        let addr: u16 = addr.into();
+
+        let ack = match (A::TENBIT, read, restart) {
+            // 7-bit address and direction.
+            (false, _, _) => self.write_byte(((addr as u8) << 1) | read as u8)?,
+
+            // A repeated START in read mode only needs the 10-bit header.
+            (true, true, true) => self.write_byte(header(addr) | 1)?,
+
+            // Full 10-bit address, followed by a repeated START for reads.
+            (true, _, _) => {
+                let ack = self.write_byte(header(addr))? && self.write_byte(addr as u8)?;
+
+                if ack && read {
+                    self.start()?;
+                    self.write_byte(header(addr) | 1)?
+                } else {
+                    ack
+                }
+            },
+        };
+
+        if !ack { return Err(BitBangI2CError::AddressNack) }
+
+        Ok(())
+    }
+
+    /// Executes the operations of a transaction, without the final STOP.
+    fn execute<A: I2CAddress>(&mut self, addr: A, operations: &mut [I2COperation<'_>]) -> Result<(), BitBangI2CError> {
+        // Current direction of the transfer (`true` for reads).
+        let mut direction: Option<bool> = None;
+
+        for i in 0..operations.len() {
+            // The last byte of a read is not acknowledged unless the next transfer is also a read.
+            let nack = !next_is_read(&operations[i+1..]);
+
+            let reading = matches!(operations[i], I2COperation::Read(_));
+
+            let (write, read): (&[u8], &mut [u8]) = match &mut operations[i] {
+                I2COperation::Write(w) => (w, &mut []),
+                I2COperation::Read(r) => (&[], r),
+                I2COperation::WriteRead(w, r) => (w, r),
+            };
+
+            // Empty operations only address the target if nothing else did (quick commands).
+            let empty = direction.is_none() && write.is_empty() && read.is_empty();
+
+            if !write.is_empty() || (empty && !reading) {
+                if direction != Some(false) {
+                    self.address(addr, false, direction.is_some())?;
+                    direction = Some(false);
+                }
+
+                for byte in write {
+                    if !self.write_byte(*byte)? { return Err(BitBangI2CError::DataNack) }
+                }
+            }
+
+            if !read.is_empty() || (empty && reading) {
+                if direction != Some(true) {
+                    self.address(addr, true, direction.is_some())?;
+                    direction = Some(true);
+                }
+
+                let n = read.len();
+                for (j, byte) in read.iter_mut().enumerate() {
+                    *byte = self.read_byte(!(nack && (j == n - 1)))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<SCL, SDA, D> CommunicationError for BitBangI2C<SCL, SDA, D> {
+    type Error = BitBangI2CError;
+}
+
+impl<A, SCL, SDA, D> I2CDriver<A> for BitBangI2C<SCL, SDA, D>
+    where A: I2CAddress, SCL: OutputPin + InputPin, SDA: OutputPin + InputPin, D: Delay
+{
+    fn transaction(&mut self, addr: A, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error> {
+        let result = self.execute(addr, operations);
+
+        // Release the bus unless it was taken by another controller.
+        match result {
+            Err(BitBangI2CError::ArbitrationLost) => {
+                self.scl.set_high();
+                self.sda.set_high();
+            },
+            _ => self.stop()?,
+        }
+
+        result
+    }
+}
+
+
+
+/// First byte of a 10-bit address.
+fn header(addr: u16) -> u8 {
+    0xF0 | (((addr >> 7) as u8) & 0x06)
+}
+
+/// Returns `true` if the next non empty operation starts with a read.
+fn next_is_read(operations: &[I2COperation<'_>]) -> bool {
+    for operation in operations {
+        match operation {
+            I2COperation::Read([]) | I2COperation::Write([]) | I2COperation::WriteRead([], []) => continue,
+            I2COperation::WriteRead([], _) => return true,
+            I2COperation::Read(_) => return true,
+            _ => return false,
+        }
+    }
+
+    false
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::fake::{ Bus, Device };
+    use super::super::super::I2CAddress7bit;
+
+    const SCL: usize = 0;
+    const SDA: usize = 1;
+
+    /// Bus conditions seen by the target.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Event {
+        Start,
+        Stop,
+        Address(u8),
+        Byte(u8),
+        Ack(bool),
+    }
+
+    /// Progress of the target through a transaction.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum State {
+        Idle,
+        Receive(bool),
+        AckSlot(bool),
+        Transmit,
+        ControllerAck(bool),
+    }
+
+    /// Simulated I2C target that acknowledges writes and sends its response on reads.
+    struct Target {
+        address: u8,
+        response: Vec<u8>,
+        sent: usize,
+        events: Vec<Event>,
+        state: State,
+        shift: u8,
+        bits: u8,
+        sda_low: bool,
+        stretch: u64,
+        stretched: u64,
+    }
+
+    impl Target {
+        fn new(address: u8, response: &[u8]) -> Self {
+            Self {
+                address, response: response.to_vec(), sent: 0, events: Vec::new(),
+                state: State::Idle, shift: 0, bits: 0, sda_low: false,
+                stretch: 0, stretched: 0,
+            }
+        }
+
+        /// Drives the next bit of the response.
+        fn present(&mut self) {
+            let byte = self.response.get(self.sent).copied().unwrap_or(0xFF);
+            self.sda_low = (byte >> (7 - self.bits)) & 1 == 0;
+        }
+    }
+
+    impl Device for Target {
+        fn output(&mut self, line: usize, high: bool, lines: &[bool; 4], time: u64) {
+            match (line, high) {
+                // SDA changing while SCL is high: START or STOP condition.
+                (SDA, false) if lines[SCL] => {
+                    self.events.push(Event::Start);
+                    self.state = State::Receive(true);
+                    self.shift = 0;
+                    self.bits = 0;
+                },
+
+                (SDA, true) if lines[SCL] => {
+                    self.events.push(Event::Stop);
+                    self.state = State::Idle;
+                    self.sda_low = false;
+                },
+
+                (SCL, true) => match self.state {
+                    State::Receive(_) => {
+                        self.shift = (self.shift << 1) | (lines[SDA] as u8);
+                        self.bits += 1;
+                    },
+
+                    State::ControllerAck(_) => self.state = State::ControllerAck(!lines[SDA]),
+
+                    _ => (),
+                },
+
+                (SCL, false) => match self.state {
+                    State::Receive(address) if self.bits == 8 => {
+                        let byte = self.shift;
+
+                        match address {
+                            true => {
+                                self.events.push(Event::Address(byte));
+
+                                if (byte >> 1) != self.address {
+                                    self.state = State::Idle;
+                                    return;
+                                }
+
+                                // Hold the clock low after acknowledging the address.
+                                self.stretched = time + self.stretch;
+                                self.state = State::AckSlot(byte & 1 != 0);
+                            },
+
+                            _ => {
+                                self.events.push(Event::Byte(byte));
+                                self.state = State::AckSlot(false);
+                            },
+                        }
+
+                        self.sda_low = true;
+                    },
+
+                    State::AckSlot(read) => {
+                        self.bits = 0;
+                        self.shift = 0;
+
+                        match read {
+                            true => { self.state = State::Transmit; self.present() },
+                            _ => { self.state = State::Receive(false); self.sda_low = false },
+                        }
+                    },
+
+                    State::Transmit => {
+                        self.bits += 1;
+
+                        match self.bits {
+                            8 => { self.sda_low = false; self.state = State::ControllerAck(false) },
+                            _ => self.present(),
+                        }
+                    },
+
+                    State::ControllerAck(ack) => {
+                        self.events.push(Event::Ack(ack));
+                        self.sent += 1;
+                        self.bits = 0;
+
+                        match ack {
+                            true => { self.state = State::Transmit; self.present() },
+                            _ => self.state = State::Idle,
+                        }
+                    },
+
+                    _ => (),
+                },
+
+                _ => (),
+            }
+        }
+
+        fn input(&mut self, line: usize, time: u64) -> bool {
+            match line {
+                SDA => !self.sda_low,
+                _ => time >= self.stretched,
+            }
+        }
+    }
+
+    fn controller(bus: &Bus<Target>) -> BitBangI2C<impl OutputPin + InputPin, impl OutputPin + InputPin, impl Delay> {
+        BitBangI2C::new(bus.pin(SCL), bus.pin(SDA), bus.delay(), 100_000)
+    }
+
+    #[test]
+    fn write_is_framed_and_acknowledged() {
+        let bus = Bus::new(Target::new(0x50, &[]));
+        let mut i2c = controller(&bus);
+
+        assert_eq!(I2CDriver::send(&mut i2c, I2CAddress7bit::new(0x50), &[0x12, 0x34]), Ok(()));
+
+        let events = bus.device(|t| t.events.clone());
+        assert_eq!(events, [Event::Start, Event::Address(0xA0), Event::Byte(0x12), Event::Byte(0x34), Event::Stop]);
+    }
+
+    #[test]
+    fn write_read_uses_repeated_start_and_nacks_last_byte() {
+        let bus = Bus::new(Target::new(0x50, &[0xAB, 0xCD]));
+        let mut i2c = controller(&bus);
+
+        let mut data = [0; 2];
+        assert_eq!(I2CDriver::transfer(&mut i2c, I2CAddress7bit::new(0x50), &[0x01], &mut data), Ok(()));
+        assert_eq!(data, [0xAB, 0xCD]);
+
+        let events = bus.device(|t| t.events.clone());
+        assert_eq!(events, [
+            Event::Start, Event::Address(0xA0), Event::Byte(0x01),
+            Event::Start, Event::Address(0xA1), Event::Ack(true), Event::Ack(false),
+            Event::Stop,
+        ]);
+    }
+
+    #[test]
+    fn address_nack_releases_the_bus() {
+        let bus = Bus::new(Target::new(0x50, &[]));
+        let mut i2c = controller(&bus);
+
+        assert_eq!(I2CDriver::send(&mut i2c, I2CAddress7bit::new(0x51), &[0x00]), Err(BitBangI2CError::AddressNack));
+
+        let events = bus.device(|t| t.events.clone());
+        assert_eq!(events, [Event::Start, Event::Address(0xA2), Event::Stop]);
+        assert!(bus.level(SCL, bus.time()) && bus.level(SDA, bus.time()));
+    }
+
+    #[test]
+    fn clock_stretching_is_waited_for() {
+        let bus = Bus::new(Target::new(0x50, &[]));
+        bus.device(|t| t.stretch = 200);
+        let mut i2c = controller(&bus);
+
+        assert_eq!(I2CDriver::send(&mut i2c, I2CAddress7bit::new(0x50), &[0x12]), Ok(()));
+
+        let events = bus.device(|t| t.events.clone());
+        assert_eq!(events, [Event::Start, Event::Address(0xA0), Event::Byte(0x12), Event::Stop]);
+        assert!(bus.time() >= 200);
+    }
+
+    #[test]
+    fn clock_stretching_times_out() {
+        let bus = Bus::new(Target::new(0x50, &[]));
+        bus.device(|t| t.stretch = 1000);
+        let mut i2c = controller(&bus);
+        i2c.stretching(100);
+
+        assert_eq!(I2CDriver::send(&mut i2c, I2CAddress7bit::new(0x50), &[0x12]), Err(BitBangI2CError::Timeout));
+    }
+}
@@ -0,0 +1,30 @@
+//! Software (bit-banged) communication drivers.
+//! Implement the communication driver traits using only GPIO pins and a
+//! delay source. Timing is derived from microsecond delays, so the achievable
+//! bit rates depend on the speed of the core and of the pin accesses.
+
+
+
+mod i2c;
+mod onewire;
+mod spi;
+mod uart;
+
+#[cfg(test)]
+mod fake;
+
+
+
+pub use self::i2c::{ BitBangI2C, BitBangI2CError };
+pub use self::onewire::BitBangOneWire;
+pub use self::spi::BitBangSPI;
+pub use self::uart::BitBangUart;
+
+
+
+/// Word types that can be shifted by the bit-banged drivers.
+pub trait Word: crate::drivers::Data + Default + Into<u32> + TryFrom<u32> {}
+
+impl Word for u8  {}
+impl Word for u16 {}
+impl Word for u32 {}
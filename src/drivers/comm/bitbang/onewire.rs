@@ -0,0 +1,212 @@
+//! Bit-banged 1-Wire master.
+
+
+
+use crate::drivers::gpio::{ InputPin, OutputPin };
+use crate::time::Delay;
+
+use super::super::CommunicationError;
+use super::super::onewire::{ OneWireDriver, OneWireError };
+
+
+
+/// Bit-banged 1-Wire master at standard speed.
+/// The pin must be configured as an open drain output that can be read back,
+/// with a pull-up on the bus. The timing critical part of each slot, and the
+/// presence sample of the reset, run in a critical section, so that
+/// interrupts cannot stretch them.
+pub struct BitBangOneWire<P, D> {
+    /// Bus line.
+    pin: P,
+
+    /// Delay source.
+    delay: D,
+}
+
+impl<P: OutputPin + InputPin, D: Delay> BitBangOneWire<P, D> {
+    /// Creates the driver and releases the bus.
+    pub fn new(mut pin: P, delay: D) -> Self {
+        pin.set_high();
+
+        Self { pin, delay }
+    }
+
+    /// Consumes the driver and returns the pin and the delay source.
+    pub fn release(self) -> (P, D) {
+        (self.pin, self.delay)
+    }
+
+    /// Pulls the bus low for `low` µs, releases it and samples it after `sample` µs.
+    fn slot(&mut self, low: u32, sample: u32) -> bool {
+        critical_section::with(|_| {
+            self.pin.set_low();
+            self.delay.delay_us(low);
+            self.pin.set_high();
+            self.delay.delay_us(sample);
+
+            self.pin.is_high()
+        })
+    }
+}
+
+impl<P, D> CommunicationError for BitBangOneWire<P, D> {
+    type Error = OneWireError;
+}
+
+impl<P: OutputPin + InputPin, D: Delay> OneWireDriver for BitBangOneWire<P, D> {
+    fn reset(&mut self) -> Result<bool, Self::Error> {
+        if self.pin.is_low() { return Err(OneWireError::Short) }
+
+        // A longer reset pulse is harmless, only the presence sample is timing critical.
+        self.pin.set_low();
+        self.delay.delay_us(480);
+
+        let presence = critical_section::with(|_| {
+            self.pin.set_high();
+            self.delay.delay_us(70);
+
+            self.pin.is_low()
+        });
+
+        self.delay.delay_us(410);
+
+        match self.pin.is_low() {
+            true => Err(OneWireError::Short),
+            _ => Ok(presence),
+        }
+    }
+
+    fn write_bit(&mut self, bit: bool) -> Result<(), Self::Error> {
+        match bit {
+            true => { self.slot(6, 0); self.delay.delay_us(64); },
+            _ => { self.slot(60, 0); self.delay.delay_us(10); },
+        }
+
+        Ok(())
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Self::Error> {
+        let bit = self.slot(6, 9);
+        self.delay.delay_us(55);
+
+        Ok(bit)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::fake::{ Bus, Device, LINES };
+
+    const LINE: usize = 0;
+
+    /// Simulated 1-Wire slave.
+    #[derive(Default)]
+    struct Slave {
+        /// `true` if the slave answers resets.
+        present: bool,
+
+        /// `true` if the bus is shorted to ground.
+        short: bool,
+
+        /// `true` if the presence pulse never ends.
+        stuck: bool,
+
+        /// Bits answered to the next read slots.
+        tx: Vec<bool>,
+
+        /// Bits written by the master.
+        rx: Vec<bool>,
+
+        /// Start of the current low pulse.
+        fell: u64,
+
+        /// The slave holds the bus low until then.
+        hold: u64,
+
+        /// Presence pulse.
+        presence: (u64, u64),
+    }
+
+    impl Device for Slave {
+        fn output(&mut self, _: usize, high: bool, _: &[bool; LINES], time: u64) {
+            if !high {
+                self.fell = time;
+
+                // A 0 is answered by holding the bus past the sample point.
+                if !self.tx.is_empty() && !self.tx.remove(0) { self.hold = time + 30 }
+
+                return;
+            }
+
+            match time - self.fell {
+                480.. if self.present => self.presence = (time + 20, if self.stuck { u64::MAX } else { time + 140 }),
+                480.. => (),
+                // Write slots are sampled 30 µs after the falling edge.
+                low if self.hold <= self.fell => self.rx.push(low < 30),
+                _ => (),
+            }
+        }
+
+        fn input(&mut self, _: usize, time: u64) -> bool {
+            let (start, end) = self.presence;
+
+            !self.short && (time >= self.hold) && !((start..end).contains(&time))
+        }
+    }
+
+    #[test]
+    fn reset_samples_presence() {
+        let bus = Bus::new(Slave { present: true, ..Slave::default() });
+        let mut wire = BitBangOneWire::new(bus.pin(LINE), bus.delay());
+
+        assert_eq!(wire.reset(), Ok(true));
+        assert_eq!(bus.log(LINE), [(0, false), (480, true)]);
+        assert_eq!(bus.time(), 960);
+
+        bus.device(|slave| slave.present = false);
+        assert_eq!(wire.reset(), Ok(false));
+    }
+
+    #[test]
+    fn reset_detects_short() {
+        let bus = Bus::new(Slave { short: true, ..Slave::default() });
+        let mut wire = BitBangOneWire::new(bus.pin(LINE), bus.delay());
+
+        assert_eq!(wire.reset(), Err(OneWireError::Short));
+
+        bus.device(|slave| { slave.short = false; slave.present = true });
+        assert_eq!(wire.reset(), Ok(true));
+
+        // Still held low at the end of the reset.
+        bus.device(|slave| slave.stuck = true);
+        assert_eq!(wire.reset(), Err(OneWireError::Short));
+    }
+
+    #[test]
+    fn bytes_are_sent_lsb_first() {
+        let bus = Bus::new(Slave::default());
+        let mut wire = BitBangOneWire::new(bus.pin(LINE), bus.delay());
+
+        assert_eq!(wire.write_byte(0xA5), Ok(()));
+
+        let bits: Vec<bool> = (0..8).map(|i| (0xA5 >> i) & 1 != 0).collect();
+        assert_eq!(bus.device(|slave| slave.rx.clone()), bits);
+
+        // Every slot lasts at least 70 µs.
+        assert_eq!(bus.time(), 8 * 70);
+    }
+
+    #[test]
+    fn bytes_are_read_lsb_first() {
+        let bus = Bus::new(Slave { tx: (0..8).map(|i| (0x3C >> i) & 1 != 0).collect(), ..Slave::default() });
+        let mut wire = BitBangOneWire::new(bus.pin(LINE), bus.delay());
+
+        assert_eq!(wire.read_byte(), Ok(0x3C));
+        assert!(bus.device(|slave| slave.tx.is_empty()));
+    }
+}
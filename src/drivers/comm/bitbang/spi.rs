@@ -0,0 +1,246 @@
+//! Bit-banged SPI controller.
+
+
+
+use core::convert::Infallible;
+
+use crate::drivers::gpio::{ InputPin, OutputPin };
+use crate::time::Delay;
+
+use super::Word;
+use super::super::{ BitOrder, CommunicationError, SPIConfig, SPIDriver };
+
+
+
+/// Bit-banged SPI controller.
+pub struct BitBangSPI<SCK, MOSI, MISO, D> {
+    /// Clock line.
+    sck: SCK,
+
+    /// Controller output line.
+    mosi: MOSI,
+
+    /// Controller input line.
+    miso: MISO,
+
+    /// Delay source.
+    delay: D,
+
+    /// Bus configuration.
+    config: SPIConfig,
+
+    /// Half of the clock period in microseconds.
+    half: u32,
+}
+
+impl<SCK, MOSI, MISO, D> BitBangSPI<SCK, MOSI, MISO, D>
+    where SCK: OutputPin, MOSI: OutputPin, MISO: InputPin, D: Delay
+{
+    /// Creates a controller with the given configuration.
+    pub fn new(sck: SCK, mosi: MOSI, miso: MISO, delay: D, config: SPIConfig) -> Self {
+        let mut spi = Self { sck, mosi, miso, delay, config, half: 1 };
+        spi.apply(config);
+        spi
+    }
+
+    /// Consumes the driver and returns the pins and the delay source.
+    pub fn release(self) -> (SCK, MOSI, MISO, D) {
+        (self.sck, self.mosi, self.miso, self.delay)
+    }
+
+    /// Applies a configuration and sets the clock to its idle level.
+    fn apply(&mut self, config: SPIConfig) {
+        self.config = config;
+        self.half = (500_000 / config.frequency.max(1)).max(1);
+        self.sck.set(config.mode.cpol());
+    }
+
+    /// Shifts a word out and returns the word shifted in.
+    fn shift(&mut self, word: u32) -> u32 {
+        let bits = self.config.bits.clamp(1, 32) as u32;
+        let cpol = self.config.mode.cpol();
+        let cpha = self.config.mode.cpha();
+
+        let mut read = 0;
+
+        for i in 0..bits {
+            let n = match self.config.order {
+                BitOrder::MSBFirst => bits - 1 - i,
+                BitOrder::LSBFirst => i,
+            };
+
+            let bit = (word >> n) & 1 != 0;
+
+            // CPHA = 0 : data is set before the leading edge and sampled on it.
+            // CPHA = 1 : data is set on the leading edge and sampled on the trailing one.
+            if !cpha { self.mosi.set(bit) }
+            self.delay.delay_us(self.half);
+            self.sck.set(!cpol);
+            if cpha { self.mosi.set(bit) }
+            else { read |= (self.miso.is_high() as u32) << n }
+
+            self.delay.delay_us(self.half);
+            self.sck.set(cpol);
+            if cpha { read |= (self.miso.is_high() as u32) << n }
+        }
+
+        read
+    }
+}
+
+impl<SCK, MOSI, MISO, D> CommunicationError for BitBangSPI<SCK, MOSI, MISO, D> {
+    type Error = Infallible;
+}
+
+impl<W, SCK, MOSI, MISO, D> SPIDriver<W> for BitBangSPI<SCK, MOSI, MISO, D>
+    where W: Word, SCK: OutputPin, MOSI: OutputPin, MISO: InputPin, D: Delay
+{
+    fn configure(&mut self, config: &SPIConfig) -> Result<(), Self::Error> {
+        self.apply(*config);
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        for i in 0..read.len().max(write.len()) {
+            let out = write.get(i).map(|w| (*w).into()).unwrap_or(0);
+            let word = self.shift(out);
+
+            if let Some(r) = read.get_mut(i) {
+                *r = W::try_from(word).unwrap_or_default();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        for w in words.iter_mut() {
+            let word = self.shift((*w).into());
+            *w = W::try_from(word).unwrap_or_default();
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::fake::{ Bus, Device };
+    use super::super::super::SPIMode;
+
+    const SCK: usize = 0;
+    const MOSI: usize = 1;
+    const MISO: usize = 2;
+
+    /// Simulated SPI target, shifting MSB first.
+    struct Target {
+        cpol: bool,
+        cpha: bool,
+        response: Vec<bool>,
+        next: usize,
+        miso: bool,
+        received: Vec<bool>,
+        edges: usize,
+    }
+
+    impl Target {
+        fn new(mode: SPIMode, response: &[u8]) -> Self {
+            let response = response.iter().flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 != 0)).collect();
+            let mut target = Self { cpol: mode.cpol(), cpha: mode.cpha(), response, next: 0, miso: true, received: Vec::new(), edges: 0 };
+
+            // With CPHA = 0 the first bit is output before the first edge.
+            if !target.cpha { target.present() }
+
+            target
+        }
+
+        fn present(&mut self) {
+            self.miso = self.response.get(self.next).copied().unwrap_or(true);
+            self.next += 1;
+        }
+
+        fn received(&self) -> Vec<u8> {
+            self.received.chunks(8).map(|c| c.iter().fold(0, |b, bit| (b << 1) | (*bit as u8))).collect()
+        }
+    }
+
+    impl Device for Target {
+        fn output(&mut self, line: usize, high: bool, lines: &[bool; 4], _: u64) {
+            if line != SCK { return }
+
+            let leading = high != self.cpol;
+
+            // Ignore the clock being set to its idle level.
+            if leading { self.edges += 1 }
+            else if self.edges == 0 { return }
+
+            // Sample on the leading edge with CPHA = 0, on the trailing one otherwise.
+            match leading != self.cpha {
+                true => self.received.push(lines[MOSI]),
+                _ => self.present(),
+            }
+        }
+
+        fn input(&mut self, line: usize, _: u64) -> bool {
+            match line {
+                MISO => self.miso,
+                _ => true,
+            }
+        }
+    }
+
+    fn exchange(mode: SPIMode) {
+        let bus = Bus::new(Target::new(mode, &[0x5A, 0xC3]));
+        let config = SPIConfig::new(1_000_000).mode(mode);
+        let mut spi = BitBangSPI::new(bus.pin(SCK), bus.pin(MOSI), bus.pin(MISO), bus.delay(), config);
+
+        assert_eq!(bus.level(SCK, 0), mode.cpol());
+
+        let mut words = [0xA5u8, 0x3C];
+        assert_eq!(SPIDriver::transfer_in_place(&mut spi, &mut words), Ok(()));
+
+        assert_eq!(words, [0x5A, 0xC3], "{:?}", mode);
+        assert_eq!(bus.device(|t| t.received()), [0xA5, 0x3C], "{:?}", mode);
+        assert_eq!(bus.device(|t| t.edges), 16, "{:?}", mode);
+        assert_eq!(bus.level(SCK, bus.time()), mode.cpol(), "{:?}", mode);
+    }
+
+    #[test]
+    fn mode0() {
+        exchange(SPIMode::Mode0);
+    }
+
+    #[test]
+    fn mode1() {
+        exchange(SPIMode::Mode1);
+    }
+
+    #[test]
+    fn mode2() {
+        exchange(SPIMode::Mode2);
+    }
+
+    #[test]
+    fn mode3() {
+        exchange(SPIMode::Mode3);
+    }
+
+    #[test]
+    fn lsb_first() {
+        let bus = Bus::new(Target::new(SPIMode::Mode0, &[]));
+        let config = SPIConfig::new(1_000_000).order(BitOrder::LSBFirst);
+        let mut spi = BitBangSPI::new(bus.pin(SCK), bus.pin(MOSI), bus.pin(MISO), bus.delay(), config);
+
+        assert_eq!(SPIDriver::<u8>::transfer(&mut spi, &mut [], &[0x01]), Ok(()));
+        assert_eq!(bus.device(|t| t.received()), [0x80]);
+    }
+}
@@ -0,0 +1,257 @@
+//! Bit-banged UART.
+
+
+
+use crate::drivers::gpio::{ InputPin, OutputPin };
+use crate::time::Delay;
+
+use super::Word;
+use super::super::{
+    CommunicationError, Parity, StopBits,
+    UartConfig, UartDriver, UartError, UartRxDriver, UartTxDriver,
+};
+
+
+
+/// Bit-banged UART.
+/// Reception blocks polling the RX pin for a start bit, so it is only
+/// suitable for low baud rates and when the core can be dedicated to it.
+pub struct BitBangUart<TX, RX, D> {
+    /// Transmission line.
+    tx: TX,
+
+    /// Reception line.
+    rx: RX,
+
+    /// Delay source.
+    delay: D,
+
+    /// Line configuration.
+    config: UartConfig,
+
+    /// Bit time in microseconds.
+    bit: u32,
+}
+
+impl<TX: OutputPin, RX: InputPin, D: Delay> BitBangUart<TX, RX, D> {
+    /// Creates a UART with the given line configuration.
+    pub fn new(mut tx: TX, rx: RX, delay: D, config: UartConfig) -> Self {
+        tx.set_high();
+
+        Self { tx, rx, delay, config, bit: (1_000_000 / config.baud.max(1)).max(1) }
+    }
+
+    /// Consumes the driver and returns the pins and the delay source.
+    pub fn release(self) -> (TX, RX, D) {
+        (self.tx, self.rx, self.delay)
+    }
+
+    /// Sends a single character.
+    fn send_word(&mut self, word: u32) {
+        let data = self.config.data as u32;
+
+        // Start bit.
+        self.tx.set_low();
+        self.delay.delay_us(self.bit);
+
+        for i in 0..data {
+            self.tx.set((word >> i) & 1 != 0);
+            self.delay.delay_us(self.bit);
+        }
+
+        if let Some(p) = parity(self.config.parity, word, data) {
+            self.tx.set(p);
+            self.delay.delay_us(self.bit);
+        }
+
+        // Stop bits.
+        self.tx.set_high();
+        match self.config.stop {
+            StopBits::One => self.delay.delay_us(self.bit),
+            StopBits::OneAndHalf => self.delay.delay_us(self.bit + (self.bit / 2)),
+            StopBits::Two => self.delay.delay_us(2 * self.bit),
+        }
+    }
+
+    /// Receives a single character.
+    fn recv_word(&mut self) -> Result<u32, UartError> {
+        let data = self.config.data as u32;
+
+        // Wait for the start bit and sample it in its middle.
+        while self.rx.is_high() {}
+        self.delay.delay_us(self.bit / 2);
+        if self.rx.is_high() { return Err(UartError::Noise) }
+
+        let mut word = 0;
+        for i in 0..data {
+            self.delay.delay_us(self.bit);
+            word |= (self.rx.is_high() as u32) << i;
+        }
+
+        let parityok = match parity(self.config.parity, word, data) {
+            Some(p) => {
+                self.delay.delay_us(self.bit);
+                self.rx.is_high() == p
+            },
+            _ => true,
+        };
+
+        // Stop bit.
+        self.delay.delay_us(self.bit);
+        if self.rx.is_low() {
+            // Wait for the line to be released before reporting.
+            while self.rx.is_low() {}
+            return Err(if word == 0 { UartError::Break } else { UartError::Framing });
+        }
+
+        if !parityok { return Err(UartError::Parity) }
+
+        Ok(word)
+    }
+}
+
+impl<TX, RX, D> CommunicationError for BitBangUart<TX, RX, D> {
+    type Error = UartError;
+}
+
+impl<TX: OutputPin, RX: InputPin, D: Delay> UartDriver for BitBangUart<TX, RX, D> {
+    fn configure(&mut self, config: &UartConfig) -> Result<(), Self::Error> {
+        if config.baud == 0 { return Err(UartError::Unsupported) }
+
+        self.config = *config;
+        self.bit = (1_000_000 / config.baud).max(1);
+
+        Ok(())
+    }
+}
+
+impl<W: Word, TX: OutputPin, RX: InputPin, D: Delay> UartTxDriver<W> for BitBangUart<TX, RX, D> {
+    fn send(&mut self, data: &[W]) -> Result<(), Self::Error> {
+        for word in data {
+            self.send_word((*word).into());
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<W: Word, TX: OutputPin, RX: InputPin, D: Delay> UartRxDriver<W> for BitBangUart<TX, RX, D> {
+    fn recv(&mut self, data: &mut [W]) -> Result<(), Self::Error> {
+        for word in data.iter_mut() {
+            *word = W::try_from(self.recv_word()?).unwrap_or_default();
+        }
+
+        Ok(())
+    }
+}
+
+
+
+/// Computes the parity bit of a character, if any.
+fn parity(parity: Parity, word: u32, bits: u32) -> Option<bool> {
+    let ones = (word & ((1 << bits) - 1)).count_ones();
+
+    match parity {
+        Parity::None => None,
+        Parity::Even => Some((ones & 1) == 1),
+        Parity::Odd => Some((ones & 1) == 0),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::fake::{ Bus, Device, Idle };
+
+    const TX: usize = 0;
+    const RX: usize = 1;
+
+    /// Bit time at 10000 baud.
+    const BIT: u64 = 100;
+
+    /// Line driven with a waveform of one level per bit time.
+    struct Wave(Vec<bool>);
+
+    impl Device for Wave {
+        fn input(&mut self, line: usize, time: u64) -> bool {
+            match line {
+                RX => self.0.get((time / BIT) as usize).copied().unwrap_or(true),
+                _ => true,
+            }
+        }
+    }
+
+    /// Levels of a character: start bit, data LSB first, parity bit and stop bit.
+    fn character(byte: u8, parity: Option<bool>) -> Vec<bool> {
+        let mut bits = std::vec![false];
+        bits.extend((0..8).map(|i| (byte >> i) & 1 != 0));
+        bits.extend(parity);
+        bits.push(true);
+        bits
+    }
+
+    #[test]
+    fn send_frames_character_with_parity() {
+        let bus = Bus::new(Idle);
+        let config = UartConfig::new(10_000).parity(Parity::Even);
+        let mut uart = BitBangUart::new(bus.pin(TX), bus.pin(RX), bus.delay(), config);
+
+        assert_eq!(UartTxDriver::send(&mut uart, &[0xA3u8]), Ok(()));
+
+        // 0xA3 has four bits set: the even parity bit is 0.
+        let start = bus.log(TX)[0].0;
+        let levels: Vec<bool> = (0..11).map(|n| bus.level(TX, start + n * BIT + BIT / 2)).collect();
+
+        assert_eq!(levels, character(0xA3, Some(false)));
+        assert_eq!(bus.time(), start + 11 * BIT);
+    }
+
+    #[test]
+    fn send_odd_parity_and_two_stop_bits() {
+        let bus = Bus::new(Idle);
+        let config = UartConfig::new(10_000).parity(Parity::Odd).stopbits(StopBits::Two);
+        let mut uart = BitBangUart::new(bus.pin(TX), bus.pin(RX), bus.delay(), config);
+
+        assert_eq!(UartTxDriver::send(&mut uart, &[0x01u8]), Ok(()));
+
+        let start = bus.log(TX)[0].0;
+        let levels: Vec<bool> = (0..11).map(|n| bus.level(TX, start + n * BIT + BIT / 2)).collect();
+
+        assert_eq!(levels, character(0x01, Some(false)));
+        assert_eq!(bus.time(), start + 12 * BIT);
+    }
+
+    #[test]
+    fn recv_checks_parity() {
+        let bus = Bus::new(Wave(character(0xA3, Some(false))));
+        let config = UartConfig::new(10_000).parity(Parity::Even);
+        let mut uart = BitBangUart::new(bus.pin(TX), bus.pin(RX), bus.delay(), config);
+
+        let mut data = [0u8];
+        assert_eq!(UartRxDriver::recv(&mut uart, &mut data), Ok(()));
+        assert_eq!(data, [0xA3]);
+
+        let bus = Bus::new(Wave(character(0xA3, Some(true))));
+        let mut uart = BitBangUart::new(bus.pin(TX), bus.pin(RX), bus.delay(), config);
+
+        assert_eq!(UartRxDriver::recv(&mut uart, &mut data), Err(UartError::Parity));
+    }
+
+    #[test]
+    fn recv_without_parity() {
+        let bus = Bus::new(Wave(character(0x5C, None)));
+        let mut uart = BitBangUart::new(bus.pin(TX), bus.pin(RX), bus.delay(), UartConfig::new(10_000));
+
+        let mut data = [0u8];
+        assert_eq!(UartRxDriver::recv(&mut uart, &mut data), Ok(()));
+        assert_eq!(data, [0x5C]);
+    }
+}
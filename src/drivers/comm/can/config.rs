@@ -0,0 +1,74 @@
+//! CAN controller configuration.
+
+
+
+/// Bit timing and operating mode of a CAN controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanConfig {
+    /// Nominal (arbitration phase) bit rate in bits per second.
+    pub bitrate: u32,
+
+    /// Data phase bit rate of CAN FD frames. `None` for a classic CAN controller.
+    pub data_bitrate: Option<u32>,
+
+    /// Operating mode.
+    pub mode: CanMode,
+
+    /// Recovers automatically from the bus-off state after 128 occurrences of
+    /// 11 recessive bits.
+    pub auto_recovery: bool,
+}
+
+impl CanConfig {
+    /// Creates a new classic CAN configuration at the given bit rate.
+    pub const fn new(bitrate: u32) -> Self {
+        Self { bitrate, data_bitrate: None, mode: CanMode::Normal, auto_recovery: true }
+    }
+
+    /// Enables CAN FD with the given data phase bit rate.
+    #[inline]
+    pub const fn fd(mut self, data_bitrate: u32) -> Self {
+        self.data_bitrate = Some(data_bitrate);
+        self
+    }
+
+    /// Configures the operating mode.
+    #[inline]
+    pub const fn mode(mut self, mode: CanMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Configures the automatic bus-off recovery.
+    #[inline]
+    pub const fn auto_recovery(mut self, enable: bool) -> Self {
+        self.auto_recovery = enable;
+        self
+    }
+
+    /// Returns `true` if CAN FD frames are enabled.
+    pub const fn is_fd(&self) -> bool {
+        self.data_bitrate.is_some()
+    }
+}
+
+impl Default for CanConfig {
+    fn default() -> Self {
+        Self::new(500_000)
+    }
+}
+
+
+
+/// Operating mode of a CAN controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanMode {
+    /// Takes part in the bus.
+    Normal,
+
+    /// Receives frames without acknowledging them or transmitting.
+    Silent,
+
+    /// Receives its own frames internally, without driving the bus.
+    Loopback,
+}
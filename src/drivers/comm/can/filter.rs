@@ -0,0 +1,100 @@
+//! CAN acceptance filters.
+
+
+
+use super::CanId;
+
+
+
+/// Acceptance filter.
+/// A received frame passes the filter if the bits of its identifier selected
+/// by the mask match the filter identifier. Standard filters only match
+/// standard identifiers, and extended filters only match extended identifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanFilter {
+    /// Accepts all frames.
+    All,
+
+    /// Filter on 11-bit identifiers.
+    Standard { id: u16, mask: u16 },
+
+    /// Filter on 29-bit identifiers.
+    Extended { id: u32, mask: u32 },
+}
+
+impl CanFilter {
+    /// Filter accepting only the given identifier.
+    pub const fn exact(id: CanId) -> Self {
+        match id {
+            CanId::Standard(id) => CanFilter::Standard { id, mask: CanId::STANDARD_MAX },
+            CanId::Extended(id) => CanFilter::Extended { id, mask: CanId::EXTENDED_MAX },
+        }
+    }
+
+    /// Filter accepting the identifiers that match `id` on the bits set in `mask`.
+    pub const fn masked(id: CanId, mask: u32) -> Self {
+        match id {
+            CanId::Standard(id) => CanFilter::Standard { id, mask: (mask as u16) & CanId::STANDARD_MAX },
+            CanId::Extended(id) => CanFilter::Extended { id, mask: mask & CanId::EXTENDED_MAX },
+        }
+    }
+
+    /// Returns `true` if a frame with the given identifier passes the filter.
+    pub const fn matches(&self, id: CanId) -> bool {
+        match (self, id) {
+            (CanFilter::All, _) => true,
+            (CanFilter::Standard { id: filter, mask }, CanId::Standard(id)) => ((id ^ *filter) & *mask) == 0,
+            (CanFilter::Extended { id: filter, mask }, CanId::Extended(id)) => ((id ^ *filter) & *mask) == 0,
+            _ => false,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_masks() {
+        let filter = CanFilter::masked(CanId::Standard(0x120), 0x7F0);
+
+        assert!(filter.matches(CanId::Standard(0x120)));
+        assert!(filter.matches(CanId::Standard(0x12F)));
+        assert!(!filter.matches(CanId::Standard(0x130)));
+        assert!(!filter.matches(CanId::Standard(0x520)));
+
+        // Standard filters never match extended identifiers.
+        assert!(!filter.matches(CanId::Extended(0x120)));
+
+        // Mask bits above 11 bits are dropped.
+        assert_eq!(CanFilter::masked(CanId::Standard(0x120), 0xFFFF_FFF0), filter);
+    }
+
+    #[test]
+    fn extended_masks() {
+        let filter = CanFilter::masked(CanId::Extended(0x18DA_F100), 0x1FFF_FF00);
+
+        assert!(filter.matches(CanId::Extended(0x18DA_F1FF)));
+        assert!(!filter.matches(CanId::Extended(0x18DA_F200)));
+        assert!(!filter.matches(CanId::Extended(0x08DA_F100)));
+        assert!(!filter.matches(CanId::Standard(0x100)));
+    }
+
+    #[test]
+    fn exact_and_all() {
+        let standard = CanFilter::exact(CanId::Standard(0x7DF));
+        let extended = CanFilter::exact(CanId::Extended(0x7DF));
+
+        assert!(standard.matches(CanId::Standard(0x7DF)));
+        assert!(!standard.matches(CanId::Standard(0x7DE)));
+        assert!(!standard.matches(CanId::Extended(0x7DF)));
+
+        assert!(extended.matches(CanId::Extended(0x7DF)));
+        assert!(!extended.matches(CanId::Extended(0x1000_07DF)));
+
+        assert!(CanFilter::All.matches(CanId::Standard(0)));
+        assert!(CanFilter::All.matches(CanId::Extended(CanId::EXTENDED_MAX)));
+    }
+}
@@ -0,0 +1,312 @@
+//! CAN identifiers and frames.
+
+
+
+/// CAN identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CanId {
+    /// 11-bit identifier.
+    Standard(u16),
+
+    /// 29-bit identifier.
+    Extended(u32),
+}
+
+impl CanId {
+    /// Maximum standard identifier.
+    pub const STANDARD_MAX: u16 = 0x7FF;
+
+    /// Maximum extended identifier.
+    pub const EXTENDED_MAX: u32 = 0x1FFF_FFFF;
+
+    /// Standard identifier, if it fits in 11 bits.
+    pub const fn standard(id: u16) -> Option<Self> {
+        match id <= Self::STANDARD_MAX {
+            true => Some(CanId::Standard(id)),
+            _ => None,
+        }
+    }
+
+    /// Extended identifier, if it fits in 29 bits.
+    pub const fn extended(id: u32) -> Option<Self> {
+        match id <= Self::EXTENDED_MAX {
+            true => Some(CanId::Extended(id)),
+            _ => None,
+        }
+    }
+
+    /// Raw value of the identifier.
+    pub const fn raw(&self) -> u32 {
+        match self {
+            CanId::Standard(id) => *id as u32,
+            CanId::Extended(id) => *id,
+        }
+    }
+
+    /// Returns `true` for extended identifiers.
+    pub const fn is_extended(&self) -> bool {
+        matches!(self, CanId::Extended(_))
+    }
+}
+
+
+
+/// Payload lengths of each DLC value.
+const LENGTHS: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Maximum payload of a classic CAN frame.
+pub const CAN_MAX_LEN: usize = 8;
+
+/// Maximum payload of a CAN FD frame.
+pub const CANFD_MAX_LEN: usize = 64;
+
+/// Payload length of a CAN FD frame with the given DLC (12 to 64 bytes above 8).
+/// Classic frames with a DLC above 8 carry 8 bytes, which this function does
+/// not apply: use `CAN_MAX_LEN` for them.
+pub const fn dlc_to_len(dlc: u8) -> usize {
+    LENGTHS[(dlc & 0xF) as usize] as usize
+}
+
+/// DLC of a payload length, if it is a valid CAN FD length.
+pub const fn len_to_dlc(len: usize) -> Option<u8> {
+    let mut dlc = 0;
+
+    while dlc < 16 {
+        if LENGTHS[dlc] as usize == len { return Some(dlc as u8) }
+        dlc += 1;
+    }
+
+    None
+}
+
+/// Smallest valid CAN FD length that holds the given number of bytes.
+pub const fn padded_len(len: usize) -> Option<usize> {
+    let mut dlc = 0;
+
+    while dlc < 16 {
+        if LENGTHS[dlc] as usize >= len { return Some(LENGTHS[dlc] as usize) }
+        dlc += 1;
+    }
+
+    None
+}
+
+
+
+/// Classic CAN or CAN FD frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanFrame {
+    /// Identifier.
+    id: CanId,
+
+    /// Payload. Only the first `len` bytes are valid.
+    data: [u8; CANFD_MAX_LEN],
+
+    /// Payload length (requested length for remote frames).
+    len: u8,
+
+    /// Remote transmission request.
+    remote: bool,
+
+    /// CAN FD format.
+    fd: bool,
+
+    /// CAN FD bit rate switch.
+    brs: bool,
+
+    /// CAN FD error state indicator (transmitter is error passive).
+    esi: bool,
+}
+
+impl CanFrame {
+    /// Classic data frame. Fails if the payload exceeds 8 bytes.
+    pub const fn new(id: CanId, data: &[u8]) -> Option<Self> {
+        if data.len() > CAN_MAX_LEN { return None }
+
+        Some(Self::build(id, data, false))
+    }
+
+    /// Classic remote frame requesting `len` bytes. Fails if `len` exceeds 8.
+    pub const fn remote(id: CanId, len: usize) -> Option<Self> {
+        if len > CAN_MAX_LEN { return None }
+
+        let mut frame = Self::build(id, &[], false);
+        frame.len = len as u8;
+        frame.remote = true;
+
+        Some(frame)
+    }
+
+    /// CAN FD data frame. Fails if the length is not a valid CAN FD length
+    /// (see `padded_len`).
+    pub const fn fd(id: CanId, data: &[u8]) -> Option<Self> {
+        if len_to_dlc(data.len()).is_none() { return None }
+
+        Some(Self::build(id, data, true))
+    }
+
+    /// Configures the bit rate switch of a CAN FD frame.
+    #[inline]
+    pub const fn bitrate_switch(mut self, brs: bool) -> Self {
+        self.brs = brs && self.fd;
+        self
+    }
+
+    /// Configures the error state indicator of a CAN FD frame.
+    #[inline]
+    pub const fn error_passive(mut self, esi: bool) -> Self {
+        self.esi = esi && self.fd;
+        self
+    }
+
+    /// Identifier.
+    pub const fn id(&self) -> CanId {
+        self.id
+    }
+
+    /// Payload (empty for remote frames).
+    pub fn data(&self) -> &[u8] {
+        match self.remote {
+            true => &[],
+            _ => &self.data[..self.len as usize],
+        }
+    }
+
+    /// Payload length, or requested length of a remote frame.
+    pub const fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns `true` if the frame carries no payload.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Data Length Code.
+    pub const fn dlc(&self) -> u8 {
+        match len_to_dlc(self.len as usize) {
+            Some(dlc) => dlc,
+            _ => 0,
+        }
+    }
+
+    /// Returns `true` for remote frames.
+    pub const fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    /// Returns `true` for CAN FD frames.
+    pub const fn is_fd(&self) -> bool {
+        self.fd
+    }
+
+    /// Returns `true` if the data phase of a CAN FD frame uses the data bit rate.
+    pub const fn brs(&self) -> bool {
+        self.brs
+    }
+
+    /// Returns `true` if the transmitter of a CAN FD frame is error passive.
+    pub const fn esi(&self) -> bool {
+        self.esi
+    }
+
+    /// Builds a data frame.
+    const fn build(id: CanId, data: &[u8], fd: bool) -> Self {
+        let mut buffer = [0; CANFD_MAX_LEN];
+        let mut i = 0;
+
+        while i < data.len() {
+            buffer[i] = data[i];
+            i += 1;
+        }
+
+        Self { id, data: buffer, len: data.len() as u8, remote: false, fd, brs: false, esi: false }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dlc_lengths() {
+        for dlc in 0..16u8 {
+            let len = dlc_to_len(dlc);
+
+            assert_eq!(len, LENGTHS[dlc as usize] as usize);
+            assert_eq!(len_to_dlc(len), Some(dlc));
+            assert_eq!(padded_len(len), Some(len));
+        }
+
+        // Only the low nibble is a DLC.
+        assert_eq!(dlc_to_len(0x1F), 64);
+
+        for len in [9, 13, 21, 33, 49, 63, 65] {
+            assert_eq!(len_to_dlc(len), None);
+        }
+
+        assert_eq!(padded_len(9), Some(12));
+        assert_eq!(padded_len(25), Some(32));
+        assert_eq!(padded_len(49), Some(64));
+        assert_eq!(padded_len(65), None);
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(CanId::standard(0x7FF), Some(CanId::Standard(0x7FF)));
+        assert_eq!(CanId::standard(0x800), None);
+        assert_eq!(CanId::extended(0x1FFF_FFFF), Some(CanId::Extended(0x1FFF_FFFF)));
+        assert_eq!(CanId::extended(0x2000_0000), None);
+        assert!(!CanId::Standard(1).is_extended());
+        assert!(CanId::Extended(1).is_extended());
+    }
+
+    #[test]
+    fn frame_lengths() {
+        let id = CanId::Standard(0x123);
+
+        assert!(CanFrame::new(id, &[0; 8]).is_some());
+        assert!(CanFrame::new(id, &[0; 9]).is_none());
+        assert!(CanFrame::remote(id, 9).is_none());
+
+        for len in 0..=CANFD_MAX_LEN + 1 {
+            let frame = CanFrame::fd(id, &[0xAA; CANFD_MAX_LEN + 1][..len]);
+
+            match len_to_dlc(len) {
+                Some(dlc) => assert_eq!(frame.map(|f| f.dlc()), Some(dlc)),
+                _ => assert!(frame.is_none(), "length {}", len),
+            }
+        }
+
+        let frame = CanFrame::fd(id, &[1; 48]).unwrap();
+        assert!(frame.is_fd());
+        assert_eq!(frame.dlc(), 14);
+        assert_eq!(frame.data(), &[1; 48]);
+    }
+
+    #[test]
+    fn remote_frames() {
+        let frame = CanFrame::remote(CanId::Extended(0x1234), 4).unwrap();
+
+        assert!(frame.is_remote());
+        assert_eq!(frame.len(), 4);
+        assert_eq!(frame.dlc(), 4);
+        assert_eq!(frame.data(), &[]);
+    }
+
+    #[test]
+    fn fd_flags_ignored_on_classic_frames() {
+        let id = CanId::Standard(0x123);
+
+        let classic = CanFrame::new(id, &[1, 2]).unwrap().bitrate_switch(true).error_passive(true);
+        assert!(!classic.brs());
+        assert!(!classic.esi());
+        assert_eq!(classic, CanFrame::new(id, &[1, 2]).unwrap());
+
+        let fd = CanFrame::fd(id, &[1, 2]).unwrap().bitrate_switch(true).error_passive(true);
+        assert!(fd.brs());
+        assert!(fd.esi());
+    }
+}
@@ -0,0 +1,834 @@
+//! ISO-TP (ISO 15765-2) transport protocol.
+//! Segments messages of up to 4 GiB into single, first and consecutive CAN
+//! frames, and reassembles them on reception with flow control. Uses normal
+//! addressing: each endpoint transmits on one identifier and receives on
+//! another one. Classic CAN (8 byte frames) and CAN FD (up to 64 byte frames)
+//! are supported.
+//!
+//! An endpoint handles one message at a time in either direction. The
+//! reassembly buffer is borrowed, so that it can be placed in a static
+//! allocation, e.g. `preallocate!(bytes 4095)`.
+
+
+
+use core::time::Duration;
+
+use crate::time::{ sleep, with_timeout, TimeSource };
+
+use super::super::{ DriverError, ErrorKind };
+use super::{
+    CanAsyncRxDriver, CanAsyncTxDriver, CanError, CanFrame, CanId,
+    CAN_MAX_LEN, CANFD_MAX_LEN, padded_len,
+};
+
+
+
+/// Single frame.
+const SINGLE: u8 = 0x0;
+
+/// First frame of a segmented message.
+const FIRST: u8 = 0x1;
+
+/// Consecutive frame of a segmented message.
+const CONSECUTIVE: u8 = 0x2;
+
+/// Flow control frame.
+const FLOW: u8 = 0x3;
+
+/// Flow status: continue to send.
+const CONTINUE: u8 = 0x0;
+
+/// Flow status: wait for another flow control frame.
+const WAIT: u8 = 0x1;
+
+/// Flow status: the message does not fit in the receiver.
+const OVERFLOW: u8 = 0x2;
+
+/// Largest message length of a first frame without escape sequence.
+const FIRST_MAX: usize = 0xFFF;
+
+/// Default padding byte of CAN FD frames.
+const PADDING: u8 = 0xCC;
+
+
+
+/// Errors reported by the ISO-TP layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsoTpError {
+    /// The CAN driver reported an error.
+    Can(CanError),
+
+    /// A flow control (N_Bs) or consecutive frame (N_Cr) was not received in time.
+    Timeout,
+
+    /// A consecutive frame was received with the wrong sequence number.
+    Sequence,
+
+    /// The message does not fit in the reassembly buffer, or the receiver
+    /// reported that it does not fit in its own.
+    Overflow,
+
+    /// A flow control frame with an invalid flow status was received.
+    Malformed,
+
+    /// The message is empty or too long for the protocol.
+    Length,
+
+    /// The receiver requested more waits than allowed.
+    WaitLimit,
+}
+
+impl DriverError for IsoTpError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            IsoTpError::Can(e) => e.kind(),
+            IsoTpError::Timeout => ErrorKind::Timeout,
+            IsoTpError::Sequence | IsoTpError::Malformed => ErrorKind::Framing,
+            IsoTpError::Overflow => ErrorKind::Overrun,
+            IsoTpError::Length | IsoTpError::WaitLimit => ErrorKind::Other,
+        }
+    }
+}
+
+impl From<CanError> for IsoTpError {
+    fn from(e: CanError) -> Self {
+        IsoTpError::Can(e)
+    }
+}
+
+
+
+/// Configuration of an ISO-TP endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IsoTpConfig {
+    /// Identifier of the transmitted frames.
+    pub tx: CanId,
+
+    /// Identifier of the received frames.
+    pub rx: CanId,
+
+    /// Number of consecutive frames the sender may send between flow control
+    /// frames. 0 lets the sender send the whole message.
+    pub block_size: u8,
+
+    /// Minimum separation time between consecutive frames requested to the sender.
+    pub st_min: Duration,
+
+    /// Maximum time to wait for a flow control frame (N_Bs).
+    pub n_bs: Duration,
+
+    /// Maximum time to wait for a consecutive frame (N_Cr).
+    pub n_cr: Duration,
+
+    /// Maximum number of consecutive wait flow control frames accepted.
+    pub wait_max: u8,
+
+    /// Length of the transmitted frames (8, or a CAN FD length up to 64).
+    pub dl: usize,
+
+    /// Transmit CAN FD frames with bit rate switch.
+    pub brs: bool,
+
+    /// Padding byte of classic frames shorter than 8 bytes. `None` sends
+    /// them unpadded. CAN FD frames are always padded (with 0xCC by default).
+    pub padding: Option<u8>,
+}
+
+impl IsoTpConfig {
+    /// Creates a classic CAN configuration without block size or separation
+    /// time limits and with 1 second timeouts.
+    pub const fn new(tx: CanId, rx: CanId) -> Self {
+        Self {
+            tx, rx,
+            block_size: 0,
+            st_min: Duration::from_micros(0),
+            n_bs: Duration::from_secs(1),
+            n_cr: Duration::from_secs(1),
+            wait_max: 8,
+            dl: CAN_MAX_LEN,
+            brs: false,
+            padding: None,
+        }
+    }
+
+    /// Configures the block size requested to the sender.
+    #[inline]
+    pub const fn block_size(mut self, block_size: u8) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Configures the separation time requested to the sender.
+    /// Rounded up to 100 µs below 1 ms and to 1 ms above, up to 127 ms.
+    #[inline]
+    pub const fn st_min(mut self, st_min: Duration) -> Self {
+        self.st_min = st_min;
+        self
+    }
+
+    /// Configures the flow control (N_Bs) and consecutive frame (N_Cr) timeouts.
+    #[inline]
+    pub const fn timeouts(mut self, n_bs: Duration, n_cr: Duration) -> Self {
+        self.n_bs = n_bs;
+        self.n_cr = n_cr;
+        self
+    }
+
+    /// Configures the maximum number of consecutive wait flow control frames.
+    #[inline]
+    pub const fn wait_max(mut self, wait_max: u8) -> Self {
+        self.wait_max = wait_max;
+        self
+    }
+
+    /// Transmits CAN FD frames of the given length, rounded up to a valid
+    /// CAN FD length.
+    #[inline]
+    pub const fn fd(mut self, dl: usize, brs: bool) -> Self {
+        self.dl = match padded_len(dl) {
+            Some(dl) if dl > CAN_MAX_LEN => dl,
+            Some(_) => CAN_MAX_LEN,
+            _ => CANFD_MAX_LEN,
+        };
+
+        self.brs = brs;
+        self
+    }
+
+    /// Configures the padding byte of classic frames.
+    #[inline]
+    pub const fn padding(mut self, padding: Option<u8>) -> Self {
+        self.padding = padding;
+        self
+    }
+}
+
+
+
+/// ISO-TP endpoint over an asynchronous CAN driver.
+pub struct IsoTp<'a, C, S> {
+    /// Wrapped CAN driver.
+    can: C,
+
+    /// Time source.
+    source: S,
+
+    /// Configuration.
+    config: IsoTpConfig,
+
+    /// Reassembly buffer of received messages.
+    buffer: &'a mut [u8],
+}
+
+impl<'a, C: CanAsyncTxDriver + CanAsyncRxDriver, S: TimeSource> IsoTp<'a, C, S> {
+    /// Creates an endpoint that reassembles received messages in the buffer.
+    pub fn new(can: C, source: S, config: IsoTpConfig, buffer: &'a mut [u8]) -> Self {
+        Self { can, source, config, buffer }
+    }
+
+    /// Configuration of the endpoint.
+    pub fn config(&self) -> &IsoTpConfig {
+        &self.config
+    }
+
+    /// Consumes the endpoint and returns the CAN driver and the time source.
+    pub fn release(self) -> (C, S) {
+        (self.can, self.source)
+    }
+
+    /// Sends a message, segmented if it does not fit in a single frame.
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), IsoTpError> {
+        let dl = self.config.dl;
+        let mut pdu = [0; CANFD_MAX_LEN];
+
+        if data.is_empty() || (data.len() > u32::MAX as usize) { return Err(IsoTpError::Length) }
+
+        // Single frame.
+        if data.len() < CAN_MAX_LEN {
+            pdu[0] = (SINGLE << 4) | data.len() as u8;
+            pdu[1..=data.len()].copy_from_slice(data);
+
+            return self.transmit(&pdu[..=data.len()]).await;
+        }
+
+        if data.len() <= dl - 2 {
+            pdu[1] = data.len() as u8;
+            pdu[2..2 + data.len()].copy_from_slice(data);
+
+            return self.transmit(&pdu[..2 + data.len()]).await;
+        }
+
+        // First frame.
+        let header = match data.len() {
+            len @ ..=FIRST_MAX => {
+                pdu[0] = (FIRST << 4) | (len >> 8) as u8;
+                pdu[1] = len as u8;
+                2
+            },
+
+            len => {
+                pdu[0] = FIRST << 4;
+                pdu[2..6].copy_from_slice(&(len as u32).to_be_bytes());
+                6
+            },
+        };
+
+        let mut offset = dl - header;
+        pdu[header..dl].copy_from_slice(&data[..offset]);
+        self.transmit(&pdu[..dl]).await?;
+
+        // Consecutive frames, in blocks acknowledged by flow control.
+        let mut sn = 1;
+
+        loop {
+            let (block_size, st_min) = self.flow_control().await?;
+            let mut count: usize = 0;
+
+            while offset < data.len() {
+                if (count > 0) && !st_min.is_zero() {
+                    sleep(&self.source, st_min).await;
+                }
+
+                let n = core::cmp::min(dl - 1, data.len() - offset);
+
+                pdu[0] = (CONSECUTIVE << 4) | sn;
+                pdu[1..=n].copy_from_slice(&data[offset..offset + n]);
+                self.transmit(&pdu[..=n]).await?;
+
+                offset += n;
+                sn = (sn + 1) & 0xF;
+                count += 1;
+
+                if count == block_size as usize { break }
+            }
+
+            if offset == data.len() { return Ok(()) }
+        }
+    }
+
+    /// Receives a message. Waits indefinitely for its first frame (wrap it
+    /// with `time::with_timeout` to bound the wait) and returns the message.
+    ///
+    /// A single or first frame received during a segmented reception aborts
+    /// it and starts a new one, as required by the specification.
+    pub async fn receive(&mut self) -> Result<&[u8], IsoTpError> {
+        let mut frame = recv(&mut self.can, self.config.rx).await?;
+
+        'message: loop {
+            let data = frame.data();
+
+            match data.first().map(|pci| pci >> 4) {
+                Some(SINGLE) => if let Some(payload) = single(data) {
+                    if payload.len() > self.buffer.len() { return Err(IsoTpError::Overflow) }
+
+                    self.buffer[..payload.len()].copy_from_slice(payload);
+                    return Ok(&self.buffer[..payload.len()]);
+                },
+
+                Some(FIRST) => if let Some((len, payload)) = first(data) {
+                    if len > self.buffer.len() {
+                        self.flow(OVERFLOW).await?;
+                        return Err(IsoTpError::Overflow);
+                    }
+
+                    let mut n = payload.len();
+                    self.buffer[..n].copy_from_slice(payload);
+
+                    self.flow(CONTINUE).await?;
+
+                    let mut sn = 1;
+                    let mut count: usize = 0;
+                    let mut deadline = self.deadline(self.config.n_cr);
+
+                    loop {
+                        frame = self.recv_until(deadline).await?;
+                        let data = frame.data();
+
+                        match data.first().map(|pci| pci >> 4) {
+                            Some(CONSECUTIVE) => {
+                                if (data[0] & 0xF) != sn { return Err(IsoTpError::Sequence) }
+
+                                let m = core::cmp::min(len - n, data.len() - 1);
+                                self.buffer[n..n + m].copy_from_slice(&data[1..=m]);
+                                n += m;
+
+                                if n == len { return Ok(&self.buffer[..len]) }
+
+                                sn = (sn + 1) & 0xF;
+                                count += 1;
+
+                                if count == self.config.block_size as usize {
+                                    count = 0;
+                                    self.flow(CONTINUE).await?;
+                                }
+
+                                deadline = self.deadline(self.config.n_cr);
+                            },
+
+                            Some(SINGLE) | Some(FIRST) => continue 'message,
+
+                            _ => (),
+                        }
+                    }
+                },
+
+                _ => (),
+            }
+
+            frame = recv(&mut self.can, self.config.rx).await?;
+        }
+    }
+
+    /// Waits for a flow control frame that allows the transmission.
+    /// Returns the block size and separation time requested by the receiver.
+    async fn flow_control(&mut self) -> Result<(u8, Duration), IsoTpError> {
+        let mut waits = 0;
+        let mut deadline = self.deadline(self.config.n_bs);
+
+        loop {
+            let frame = self.recv_until(deadline).await?;
+            let data = frame.data();
+
+            if (data.len() < 3) || ((data[0] >> 4) != FLOW) { continue }
+
+            match data[0] & 0xF {
+                CONTINUE => return Ok((data[1], separation(data[2]))),
+
+                WAIT => {
+                    waits += 1;
+                    if waits > self.config.wait_max { return Err(IsoTpError::WaitLimit) }
+
+                    deadline = self.deadline(self.config.n_bs);
+                },
+
+                OVERFLOW => return Err(IsoTpError::Overflow),
+
+                _ => return Err(IsoTpError::Malformed),
+            }
+        }
+    }
+
+    /// Sends a flow control frame with the given status.
+    async fn flow(&mut self, status: u8) -> Result<(), IsoTpError> {
+        let pdu = [(FLOW << 4) | status, self.config.block_size, st_min(self.config.st_min)];
+        self.transmit(&pdu).await
+    }
+
+    /// Transmits a frame with the given PDU, padded as configured.
+    async fn transmit(&mut self, pdu: &[u8]) -> Result<(), IsoTpError> {
+        let mut data = [self.config.padding.unwrap_or(PADDING); CANFD_MAX_LEN];
+        data[..pdu.len()].copy_from_slice(pdu);
+
+        let frame = match (self.config.dl > CAN_MAX_LEN, padded_len(pdu.len())) {
+            (true, Some(len)) => CanFrame::fd(self.config.tx, &data[..len]).map(|frame| frame.bitrate_switch(self.config.brs)),
+            _ if self.config.padding.is_some() => CanFrame::new(self.config.tx, &data[..CAN_MAX_LEN]),
+            _ => CanFrame::new(self.config.tx, pdu),
+        };
+
+        let Some(frame) = frame else { return Err(IsoTpError::Length) };
+
+        Ok(self.can.transmit(&frame).await?)
+    }
+
+    /// Receives a frame before the deadline.
+    async fn recv_until(&mut self, deadline: u64) -> Result<CanFrame, IsoTpError> {
+        let timeout = Duration::from_micros(deadline.saturating_sub(self.source.now()));
+
+        match with_timeout(&self.source, recv(&mut self.can, self.config.rx), timeout).await {
+            Ok(result) => Ok(result?),
+            _ => Err(IsoTpError::Timeout),
+        }
+    }
+
+    /// Deadline after the given timeout, in microseconds of the time source.
+    fn deadline(&self, timeout: Duration) -> u64 {
+        self.source.now().saturating_add(timeout.as_micros() as u64)
+    }
+}
+
+
+
+/// Receives the next data frame with the given identifier.
+async fn recv<C: CanAsyncRxDriver>(can: &mut C, id: CanId) -> Result<CanFrame, CanError> {
+    loop {
+        let frame = can.receive().await?;
+        if (frame.id() == id) && !frame.is_remote() { return Ok(frame) }
+    }
+}
+
+/// Payload of a valid single frame.
+fn single(data: &[u8]) -> Option<&[u8]> {
+    match data[0] & 0xF {
+        0 if data.len() > CAN_MAX_LEN => {
+            let len = data[1] as usize;
+
+            match (len > 0) && (len <= data.len() - 2) {
+                true => Some(&data[2..2 + len]),
+                _ => None,
+            }
+        },
+
+        0 => None,
+
+        len => match (len as usize) < data.len() {
+            true => Some(&data[1..=len as usize]),
+            _ => None,
+        },
+    }
+}
+
+/// Message length and payload of a valid first frame.
+fn first(data: &[u8]) -> Option<(usize, &[u8])> {
+    if data.len() < CAN_MAX_LEN { return None }
+
+    let (len, payload) = match (((data[0] & 0xF) as usize) << 8) | data[1] as usize {
+        0 => (u32::from_be_bytes([data[2], data[3], data[4], data[5]]) as usize, &data[6..]),
+        len => (len, &data[2..]),
+    };
+
+    match len > payload.len() {
+        true => Some((len, payload)),
+        _ => None,
+    }
+}
+
+/// Encodes a separation time.
+fn st_min(st: Duration) -> u8 {
+    match st.as_micros() {
+        0 => 0,
+        us @ 1..=900 => 0xF0 + us.div_ceil(100) as u8,
+        us => core::cmp::min(us.div_ceil(1000), 0x7F) as u8,
+    }
+}
+
+/// Decodes a separation time. Reserved values are read as the maximum of 127 ms.
+fn separation(st: u8) -> Duration {
+    match st {
+        0x00..=0x7F => Duration::from_millis(st as u64),
+        0xF1..=0xF9 => Duration::from_micros((st - 0xF0) as u64 * 100),
+        _ => Duration::from_millis(0x7F),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::{ CanConfig, CanDriver, CanRxDriver };
+    use super::super::super::mock::{ block_on, join, CanLink, CanMock, MockClock };
+
+    const TESTER: CanId = CanId::Standard(0x7E0);
+    const ECU: CanId = CanId::Standard(0x7E8);
+
+    /// Message of the given length with a recognisable pattern.
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 256) as u8).collect()
+    }
+
+    /// Lets the other future run a few times.
+    async fn yield_now() {
+        let mut polls = 0;
+
+        core::future::poll_fn(|_| {
+            polls += 1;
+            match polls > 8 {
+                true => core::task::Poll::Ready(()),
+                _ => core::task::Poll::Pending,
+            }
+        }).await
+    }
+
+    /// Receives the next frame on the raw side of the link.
+    async fn frame(can: &mut CanMock<'_, 8>) -> CanFrame {
+        CanAsyncRxDriver::receive(can).await.unwrap()
+    }
+
+    /// Transmits a frame on the raw side of the link.
+    async fn reply(can: &mut CanMock<'_, 8>, data: &[u8]) {
+        CanAsyncTxDriver::transmit(can, &CanFrame::new(ECU, data).unwrap()).await.unwrap()
+    }
+
+    /// Sends a message from one endpoint and receives it on the other one.
+    fn exchange(tx: IsoTpConfig, rx: IsoTpConfig, data: &[u8], buffer: &mut [u8]) -> (Result<(), IsoTpError>, Result<Vec<u8>, IsoTpError>) {
+        let mut link = CanLink::<8>::new();
+        let (mut a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let fd = CanConfig::new(500_000).fd(2_000_000);
+        a.configure(&fd).unwrap();
+        b.configure(&fd).unwrap();
+
+        let mut sender = IsoTp::new(a, &clock, tx, &mut []);
+        let mut receiver = IsoTp::new(b, &clock, rx, buffer);
+
+        block_on(join(sender.send(data), async { receiver.receive().await.map(|m| m.to_vec()) }))
+    }
+
+    #[test]
+    fn single_frame() {
+        let mut buffer = [0; 16];
+        let data = message(7);
+
+        let (sent, received) = exchange(IsoTpConfig::new(TESTER, ECU), IsoTpConfig::new(ECU, TESTER), &data, &mut buffer);
+
+        assert_eq!(sent, Ok(()));
+        assert_eq!(received, Ok(data));
+    }
+
+    #[test]
+    fn segmented_messages() {
+        let mut buffer = std::vec![0; 5000];
+
+        for len in [8, 62, 63, 4095, 4096, 5000] {
+            let data = message(len);
+
+            let (sent, received) = exchange(IsoTpConfig::new(TESTER, ECU), IsoTpConfig::new(ECU, TESTER), &data, &mut buffer);
+
+            assert_eq!(sent, Ok(()), "length {}", len);
+            assert_eq!(received, Ok(data), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn first_frame_length_escape() {
+        let mut link = CanLink::<8>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let config = IsoTpConfig::new(TESTER, ECU).timeouts(Duration::from_millis(10), Duration::from_millis(10));
+        let mut sender = IsoTp::new(a, &clock, config, &mut []);
+
+        let (sent, (short, long)) = block_on(join(
+            async {
+                sender.send(&message(4095)).await.unwrap_err();
+                sender.send(&message(4096)).await
+            },
+            async { (frame(&mut b).await, frame(&mut b).await) },
+        ));
+
+        assert_eq!(sent, Err(IsoTpError::Timeout));
+        assert_eq!(short.data()[..2], [0x1F, 0xFF]);
+        assert_eq!(long.data()[..6], [0x10, 0x00, 0x00, 0x00, 0x10, 0x00]);
+        assert_eq!(long.data()[6..], message(2)[..]);
+    }
+
+    #[test]
+    fn block_size_and_separation() {
+        let mut buffer = [0; 256];
+        let data = message(200);
+
+        let rx = IsoTpConfig::new(ECU, TESTER).block_size(3).st_min(Duration::from_micros(500));
+        let (sent, received) = exchange(IsoTpConfig::new(TESTER, ECU), rx, &data, &mut buffer);
+
+        assert_eq!(sent, Ok(()));
+        assert_eq!(received, Ok(data));
+    }
+
+    #[test]
+    fn sender_stops_after_each_block() {
+        let mut link = CanLink::<8>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut sender = IsoTp::new(a, &clock, IsoTpConfig::new(TESTER, ECU), &mut []);
+        let data = message(40);
+
+        let (sent, blocks) = block_on(join(sender.send(&data), async {
+            let mut blocks = Vec::new();
+            let mut received = frame(&mut b).await.data()[2..].to_vec();
+
+            while received.len() < data.len() {
+                reply(&mut b, &[0x30, 2, 0]).await;
+
+                let mut count = 0;
+
+                while (count < 2) && (received.len() < data.len()) {
+                    received.extend_from_slice(&frame(&mut b).await.data()[1..]);
+                    count += 1;
+                }
+
+                sleep(&clock, Duration::from_millis(1)).await;
+                assert_eq!(b.pending(), 0);
+
+                blocks.push(count);
+            }
+
+            assert_eq!(received, data);
+            blocks
+        }));
+
+        assert_eq!(sent, Ok(()));
+        assert_eq!(blocks, [2, 2, 1]);
+    }
+
+    #[test]
+    fn wait_flow_control() {
+        let mut link = CanLink::<8>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let config = IsoTpConfig::new(TESTER, ECU).timeouts(Duration::from_millis(10), Duration::from_millis(10)).wait_max(2);
+        let mut sender = IsoTp::new(a, &clock, config, &mut []);
+        let data = message(10);
+
+        // Two waits, each shorter than N_Bs, are accepted.
+        let (sent, ()) = block_on(join(sender.send(&data), async {
+            frame(&mut b).await;
+
+            for _ in 0..2 {
+                sleep(&clock, Duration::from_millis(8)).await;
+                reply(&mut b, &[0x31, 0, 0]).await;
+            }
+
+            reply(&mut b, &[0x30, 0, 0]).await;
+            assert_eq!(frame(&mut b).await.data(), [&[0x21][..], &data[6..]].concat());
+        }));
+
+        assert_eq!(sent, Ok(()));
+
+        // A third wait exceeds the limit.
+        let (sent, ()) = block_on(join(sender.send(&data), async {
+            frame(&mut b).await;
+
+            for _ in 0..3 {
+                reply(&mut b, &[0x31, 0, 0]).await;
+            }
+        }));
+
+        assert_eq!(sent, Err(IsoTpError::WaitLimit));
+
+        // Invalid flow status.
+        let (sent, ()) = block_on(join(sender.send(&data), async {
+            frame(&mut b).await;
+            reply(&mut b, &[0x35, 0, 0]).await;
+        }));
+
+        assert_eq!(sent, Err(IsoTpError::Malformed));
+    }
+
+    #[test]
+    fn timeouts() {
+        let mut link = CanLink::<8>::new();
+        let (a, b) = link.split();
+        let clock = MockClock::new();
+
+        let config = IsoTpConfig::new(TESTER, ECU).timeouts(Duration::from_millis(10), Duration::from_millis(20));
+        let mut sender = IsoTp::new(a, &clock, config, &mut []);
+
+        // N_Bs: no flow control after the first frame.
+        let (sent, ()) = block_on(join(sender.send(&message(10)), async {
+            yield_now().await;
+            clock.advance(Duration::from_millis(9));
+            yield_now().await;
+            assert_eq!(b.pending(), 1);
+
+            clock.advance(Duration::from_millis(1));
+        }));
+
+        assert_eq!(sent, Err(IsoTpError::Timeout));
+        assert_eq!(clock.now(), 10_000);
+
+        // N_Cr: no consecutive frame after the flow control.
+        let (a, _) = sender.release();
+        let mut b = b;
+
+        assert!(CanRxDriver::try_receive(&mut b).unwrap().is_some());
+        b.inject(&CanFrame::new(TESTER, &[0x10, 20, 0, 1, 2, 3, 4, 5]).unwrap());
+
+        let mut buffer = [0; 64];
+        let mut receiver = IsoTp::new(b, &clock, IsoTpConfig::new(ECU, TESTER).timeouts(Duration::from_millis(10), Duration::from_millis(20)), &mut buffer);
+        let start = clock.now();
+
+        let (received, ()) = block_on(join(async { receiver.receive().await.map(|m| m.len()) }, async {
+            yield_now().await;
+            clock.advance(Duration::from_millis(19));
+            yield_now().await;
+            assert_eq!(a.pending(), 1);
+
+            clock.advance(Duration::from_millis(1));
+        }));
+
+        assert_eq!(received, Err(IsoTpError::Timeout));
+        assert_eq!(clock.now() - start, 20_000);
+    }
+
+    #[test]
+    fn receiver_overflow() {
+        let mut buffer = [0; 32];
+        let data = message(33);
+
+        let (sent, received) = exchange(IsoTpConfig::new(TESTER, ECU), IsoTpConfig::new(ECU, TESTER), &data, &mut buffer);
+
+        assert_eq!(sent, Err(IsoTpError::Overflow));
+        assert_eq!(received, Err(IsoTpError::Overflow));
+    }
+
+    #[test]
+    fn sequence_error() {
+        let mut link = CanLink::<8>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut buffer = [0; 32];
+        let mut receiver = IsoTp::new(a, &clock, IsoTpConfig::new(TESTER, ECU), &mut buffer);
+
+        let (received, ()) = block_on(join(async { receiver.receive().await.map(|m| m.len()) }, async {
+            reply(&mut b, &[0x10, 20, 0, 1, 2, 3, 4, 5]).await;
+            assert_eq!(frame(&mut b).await.data(), [0x30, 0, 0]);
+            reply(&mut b, &[0x22, 6, 7, 8, 9, 10, 11, 12]).await;
+        }));
+
+        assert_eq!(received, Err(IsoTpError::Sequence));
+    }
+
+    #[test]
+    fn fd_frames() {
+        let mut buffer = [0; 512];
+        let data = message(300);
+
+        let config = |tx, rx| IsoTpConfig::new(tx, rx).fd(64, true);
+        let (sent, received) = exchange(config(TESTER, ECU), config(ECU, TESTER), &data, &mut buffer);
+
+        assert_eq!(sent, Ok(()));
+        assert_eq!(received, Ok(data));
+
+        // Single frames up to 62 bytes, with escape and padding.
+        let mut link = CanLink::<8>::new();
+        let (mut a, mut b) = link.split();
+        a.configure(&CanConfig::new(500_000).fd(2_000_000)).unwrap();
+
+        let clock = MockClock::ticking(10);
+        let mut sender = IsoTp::new(a, &clock, config(TESTER, ECU), &mut []);
+
+        block_on(sender.send(&message(50))).unwrap();
+        let single = block_on(frame(&mut b));
+
+        assert!(single.is_fd() && single.brs());
+        assert_eq!(single.len(), 64);
+        assert_eq!(single.data()[..2], [0x00, 50]);
+        assert_eq!(single.data()[2..52], message(50)[..]);
+        assert!(single.data()[52..].iter().all(|b| *b == PADDING));
+
+        // Short single frames keep the classic format and are not padded.
+        block_on(sender.send(&message(5))).unwrap();
+        let single = block_on(frame(&mut b));
+
+        assert!(single.is_fd());
+        assert_eq!(single.data(), [&[0x05][..], &message(5)].concat());
+    }
+
+    #[test]
+    fn separation_times() {
+        for (st, code) in [(0, 0x00), (100, 0xF1), (450, 0xF5), (900, 0xF9), (901, 0x01), (5000, 0x05), (200_000, 0x7F)] {
+            assert_eq!(st_min(Duration::from_micros(st)), code);
+        }
+
+        assert_eq!(separation(0x05), Duration::from_millis(5));
+        assert_eq!(separation(0xF3), Duration::from_micros(300));
+        assert_eq!(separation(0xFA), Duration::from_millis(127));
+        assert_eq!(separation(0x80), Duration::from_millis(127));
+    }
+}
@@ -0,0 +1,151 @@
+//! CAN Peripheral abstracted driver.
+
+
+
+mod config;
+mod filter;
+mod frame;
+
+pub mod isotp;
+
+
+
+use core::future::Future;
+
+use super::{ CommunicationError, DriverError, ErrorKind };
+
+
+
+pub use self::config::{ CanConfig, CanMode };
+pub use self::filter::CanFilter;
+pub use self::frame::{
+    CanFrame, CanId, CAN_MAX_LEN, CANFD_MAX_LEN,
+    dlc_to_len, len_to_dlc, padded_len,
+};
+
+
+
+/// Errors detected on a CAN bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanError {
+    /// A transmitted bit was read back with the opposite level.
+    Bit,
+
+    /// More than 5 consecutive bits of the same level were received.
+    Stuff,
+
+    /// A fixed format field contained an illegal value.
+    Form,
+
+    /// The received CRC does not match the frame.
+    Crc,
+
+    /// No node acknowledged the transmitted frame.
+    Acknowledge,
+
+    /// A frame was received while the reception queue was full.
+    Overrun,
+
+    /// The controller is in the bus-off state and does not take part in the bus.
+    BusOff,
+
+    /// The frame or configuration is not supported by the controller
+    /// (e.g. a CAN FD frame on a classic controller, or too many filters).
+    Unsupported,
+}
+
+impl DriverError for CanError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            CanError::Bit | CanError::BusOff => ErrorKind::Bus,
+            CanError::Stuff | CanError::Form | CanError::Crc => ErrorKind::Framing,
+            CanError::Acknowledge => ErrorKind::AddressNack,
+            CanError::Overrun => ErrorKind::Overrun,
+            CanError::Unsupported => ErrorKind::Other,
+        }
+    }
+}
+
+
+
+/// Fault confinement state of a CAN controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanState {
+    /// Both error counters are below 128. Errors are signalled with active error flags.
+    ErrorActive,
+
+    /// An error counter reached 128. Errors are signalled with passive error
+    /// flags and transmissions are delayed.
+    ErrorPassive,
+
+    /// The transmit error counter exceeded 255. The controller does not take
+    /// part in the bus until it recovers.
+    BusOff,
+}
+
+
+
+/// CAN Driver common trait.
+///
+/// Errors raised by the bus are reported by the operations that observe them.
+/// Transmissions fail with `CanError::BusOff` while the controller is bus-off.
+/// The error-passive state does not fail operations, and is reported by `state`.
+pub trait CanDriver: CommunicationError<Error = CanError> {
+    /// Applies the given configuration.
+    fn configure(&mut self, config: &CanConfig) -> Result<(), Self::Error>;
+
+    /// Replaces the acceptance filters. Frames that pass any filter are received.
+    /// An empty list rejects all frames. Drivers start accepting all frames.
+    /// Fails with `CanError::Unsupported` if the controller has fewer filters.
+    fn filters(&mut self, filters: &[CanFilter]) -> Result<(), Self::Error>;
+
+    /// Current fault confinement state.
+    fn state(&self) -> CanState;
+}
+
+
+/// CAN Driver for frame transmission.
+pub trait CanTxDriver: CanDriver {
+    /// Queues a frame, blocking until a transmit mailbox is available.
+    fn transmit(&mut self, frame: &CanFrame) -> Result<(), Self::Error>;
+
+    /// Queues a frame if a transmit mailbox is available. Returns `Ok(false)`
+    /// if all mailboxes are busy.
+    fn try_transmit(&mut self, frame: &CanFrame) -> Result<bool, Self::Error>;
+}
+
+
+/// CAN Driver for frame reception.
+pub trait CanRxDriver: CanDriver {
+    /// Receives a frame, blocking until one is available.
+    fn receive(&mut self) -> Result<CanFrame, Self::Error>;
+
+    /// Receives a frame if one is available.
+    fn try_receive(&mut self) -> Result<Option<CanFrame>, Self::Error>;
+}
+
+
+
+/// Asynchronous CAN Driver for frame transmission.
+/// Mirrors the operations of `CanTxDriver`.
+///
+/// Dropping the future before it completes may leave the frame queued.
+pub trait CanAsyncTxDriver: CanDriver {
+    type TransmitFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+
+    /// Queues a frame, waiting until a transmit mailbox is available.
+    fn transmit<'a>(&'a mut self, frame: &'a CanFrame) -> Self::TransmitFuture<'a>;
+}
+
+
+/// Asynchronous CAN Driver for frame reception.
+/// Mirrors the operations of `CanRxDriver`.
+///
+/// Waits indefinitely for a frame (wrap it with `time::with_timeout` to bound
+/// the wait). Dropping the future before it completes loses no frame.
+pub trait CanAsyncRxDriver: CanDriver {
+    type ReceiveFuture<'a>: Future<Output = Result<CanFrame, Self::Error>> + 'a where Self: 'a;
+
+    /// Receives a frame.
+    fn receive<'a>(&'a mut self) -> Self::ReceiveFuture<'a>;
+}
@@ -5,7 +5,10 @@ use crate::drivers::Data;
 
 
 /// Common trait for I2C Address types.
-pub trait I2CAddress: Sized + Data + Into<u16> + Into<u32> + PartialEq + Eq {}
+pub trait I2CAddress: Sized + Data + Into<u16> + Into<u32> + PartialEq + Eq + 'static {
+    /// `true` if the address must be sent in 10-bit mode.
+    const TENBIT: bool;
+}
 
 
 /// 7-bit I2C address.
@@ -40,7 +43,7 @@ impl core::convert::From<I2CAddress7bit>  for u32 {
 pub struct I2CAddress10bit(pub u16);
 
 impl I2CAddress10bit {
-    /// Creates a new I2c Address in 7 bit mode.
+    /// Creates a new I2c Address in 10 bit mode.
     pub const fn new(addr: u16) -> Self {
         I2CAddress10bit(addr & 0x3FF)
     }
@@ -65,5 +68,10 @@ impl core::convert::From<I2CAddress10bit>  for u32 {
 impl Data for I2CAddress7bit {}
 impl Data for I2CAddress10bit {}
 
-impl I2CAddress for I2CAddress7bit {}
-impl I2CAddress for I2CAddress10bit {}
+impl I2CAddress for I2CAddress7bit {
+    const TENBIT: bool = false;
+}
+
+impl I2CAddress for I2CAddress10bit {
+    const TENBIT: bool = true;
+}
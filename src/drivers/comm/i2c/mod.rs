@@ -0,0 +1,101 @@
+//! I2C Driver trait.
+
+
+
+mod addr;
+mod target;
+
+pub mod pmbus;
+pub mod scan;
+pub mod smbus;
+
+
+
+use super::CommunicationError;
+
+
+
+pub use self::addr::{ I2CAddress, I2CAddress7bit, I2CAddress10bit };
+pub use self::target::{
+    I2CTargetConfig, I2CTargetDriver, I2CTargetEvent, I2CTargetHandler, I2CTargetResponse,
+};
+
+
+
+/// Single operation of an I2C transaction.
+/// The transaction starts with a START and the target address. Adjacent
+/// operations in the same direction are merged into a single transfer, a
+/// repeated START and the target address are issued whenever the direction
+/// changes. The transaction ends with a STOP after the last operation.
+#[derive(Debug, PartialEq, Eq)]
+pub enum I2COperation<'a> {
+    /// Writes the buffer to the target.
+    Write(&'a [u8]),
+
+    /// Reads from the target until the buffer is full.
+    Read(&'a mut [u8]),
+
+    /// Writes the first buffer, issues a repeated START and reads until the
+    /// second buffer is full.
+    WriteRead(&'a [u8], &'a mut [u8]),
+}
+
+
+
+/// I2C communication driver.
+/// Generic over the address mode, which defaults to 7-bit addresses.
+pub trait I2CDriver<A: I2CAddress = I2CAddress7bit>: CommunicationError {
+    /// Executes the list of operations on the target as a single bus transaction.
+    fn transaction(&mut self, addr: A, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error>;
+
+    /// Sends a buffer to the target.
+    fn send(&mut self, addr: A, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(addr, &mut [I2COperation::Write(buffer)])
+    }
+
+    /// Receives bytes from the target until the buffer is full.
+    fn receive(&mut self, addr: A, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(addr, &mut [I2COperation::Read(buffer)])
+    }
+
+    /// Sends a byte buffer then receives bytes until a buffer is full, with a repeated START in between.
+    fn transfer(&mut self, addr: A, send: &[u8], receive: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(addr, &mut [I2COperation::WriteRead(send, receive)])
+    }
+}
+
+
+
+/// Asynchronous I2C communication driver.
+/// Mirrors the operations of `I2CDriver`.
+///
+/// The returned futures borrow both the driver and the buffers for `'a`, so
+/// implementations may hold the buffers (e.g. for DMA or ISR access) until
+/// the future completes or is dropped.
+///
+/// Cancellation: dropping a future before it completes cancels the transfer.
+/// Before `drop` returns, the implementation must abort the peripheral
+/// operation, stop any DMA access to the buffers, and release the bus with a
+/// STOP condition (running bus recovery if a target is holding SDA low). The
+/// driver must then be ready for a new transaction. The contents of receive
+/// buffers after a cancelled transfer are unspecified.
+/// Futures may pend forever if a target stops responding, `time::with_timeout`
+/// bounds them and relies on this contract to recover the bus.
+pub trait I2CAsyncDriver<A: I2CAddress = I2CAddress7bit>: CommunicationError {
+    type WriteFuture<'a>: core::future::Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type ReadFuture<'a>: core::future::Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type WriteReadFuture<'a>: core::future::Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type TransactionFuture<'a>: core::future::Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+
+    /// Executes the list of operations on the target as a single bus transaction.
+    fn transaction<'a>(&'a mut self, addr: A, operations: &'a mut [I2COperation<'a>]) -> Self::TransactionFuture<'a>;
+
+    /// Sends a buffer to the target.
+    fn send<'a>(&'a mut self, addr: A, data: &'a [u8]) -> Self::WriteFuture<'a>;
+
+    /// Receives bytes from the target until the buffer is full.
+    fn receive<'a>(&'a mut self, addr: A, recv: &'a mut [u8]) -> Self::ReadFuture<'a>;
+
+    /// Sends a byte buffer then receives bytes until a buffer is full, with a repeated START in between.
+    fn transfer<'a>(&'a mut self, addr: A, send: &'a [u8], recv: &'a mut [u8]) -> Self::WriteReadFuture<'a>;
+}
@@ -0,0 +1,57 @@
+//! PMBus number formats.
+
+
+
+/// Decodes a LINEAR11 value.
+/// The upper 5 bits are a two's complement exponent and the lower 11 bits a
+/// two's complement mantissa: `value = mantissa * 2^exponent`.
+pub fn linear11(raw: u16) -> f32 {
+    let exponent = (raw as i16) >> 11;
+    let mantissa = ((raw << 5) as i16) >> 5;
+
+    scale(mantissa as f32, exponent as i32)
+}
+
+/// Decodes a LINEAR16 value (output voltage).
+/// The mantissa is unsigned and the exponent is the 5-bit two's complement
+/// parameter in the lower bits of `VOUT_MODE`.
+pub fn linear16(raw: u16, mode: u8) -> f32 {
+    let exponent = ((mode << 3) as i8) >> 3;
+
+    scale(raw as f32, exponent as i32)
+}
+
+/// Extracts the mode bits of `VOUT_MODE`. Returns `true` if the output voltage
+/// is in LINEAR16 format.
+pub fn vout_linear(mode: u8) -> bool {
+    (mode >> 5) & 0x3 == 0
+}
+
+/// Multiplies the value by `2^exponent`.
+fn scale(value: f32, exponent: i32) -> f32 {
+    if exponent >= 0 { value * (1u32 << exponent) as f32 }
+    else { value / (1u32 << -exponent) as f32 }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear11_values() {
+        assert_eq!(linear11(0xF064), 25.0);
+        assert_eq!(linear11(0x079C), -100.0);
+        assert_eq!(linear11(0x1805), 40.0);
+        assert_eq!(linear11(0xF7FF), -0.25);
+    }
+
+    #[test]
+    fn linear16_values() {
+        assert_eq!(linear16(0x0A00, 0x17), 5.0);
+        assert_eq!(linear16(0x0003, 0x02), 12.0);
+        assert!(vout_linear(0x17));
+        assert!(!vout_linear(0x40));
+    }
+}
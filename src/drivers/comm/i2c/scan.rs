@@ -0,0 +1,127 @@
+//! I2C bus scanner and bus recovery.
+
+
+
+use crate::drivers::gpio::{ InputPin, OutputPin };
+use crate::time::Delay;
+
+use super::super::{ DriverError, ErrorKind };
+use super::{ I2CAddress7bit, I2CDriver, I2COperation };
+
+
+
+/// First non reserved 7-bit address.
+pub const SCAN_FIRST: u8 = 0x08;
+
+/// Last non reserved 7-bit address.
+pub const SCAN_LAST: u8 = 0x77;
+
+
+
+/// Bitmap of the 7-bit addresses that answered a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct I2CScan(pub [u32; 4]);
+
+impl I2CScan {
+    /// Creates an empty bitmap.
+    pub const fn new() -> Self {
+        Self([0; 4])
+    }
+
+    /// Marks the address as present.
+    pub fn insert(&mut self, addr: I2CAddress7bit) {
+        self.0[(addr.0 >> 5) as usize] |= 1 << (addr.0 & 0x1F);
+    }
+
+    /// Returns `true` if the address answered.
+    pub fn contains(&self, addr: I2CAddress7bit) -> bool {
+        (self.0[(addr.0 >> 5) as usize] & (1 << (addr.0 & 0x1F))) != 0
+    }
+
+    /// Number of addresses that answered.
+    pub fn count(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Iterates over the addresses that answered.
+    pub fn iter(&self) -> impl Iterator<Item = I2CAddress7bit> + '_ {
+        (0..128u8).map(I2CAddress7bit::new).filter(move |addr| self.contains(*addr))
+    }
+}
+
+
+
+/// Probes all the non reserved 7-bit addresses and returns the ones that answered.
+/// Like `i2cdetect`, a one byte read is used for the ranges where write-only
+/// quick commands could corrupt devices (0x30-0x37 and 0x50-0x5F, EEPROMs),
+/// and a quick write everywhere else.
+pub fn scan<D: I2CDriver>(bus: &mut D) -> I2CScan {
+    let mut found = I2CScan::new();
+
+    for addr in (SCAN_FIRST..=SCAN_LAST).map(I2CAddress7bit::new) {
+        let mut byte = [0u8];
+
+        let operation = match addr.0 {
+            0x30..=0x37 | 0x50..=0x5F => I2COperation::Read(&mut byte),
+            _ => I2COperation::Write(&[]),
+        };
+
+        if bus.transaction(addr, &mut [operation]).is_ok() { found.insert(addr) }
+    }
+
+    found
+}
+
+
+
+/// Errors of the bus recovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryError {
+    /// SDA is still held low after nine clock pulses.
+    SDAStuck,
+}
+
+impl DriverError for RecoveryError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Bus
+    }
+}
+
+/// Releases a bus with SDA stuck low.
+/// Clocks out up to nine pulses on SCL until the target releases SDA and then
+/// generates a STOP condition. Both pins must be configured as open drain by
+/// the caller, and the peripheral must not be driving them.
+/// `period` is the SCL period in microseconds.
+pub fn recover<SCL, SDA, D>(scl: &mut SCL, sda: &mut SDA, delay: &mut D, period: u32) -> Result<(), RecoveryError>
+    where SCL: OutputPin, SDA: OutputPin + InputPin, D: Delay
+{
+    let half = (period / 2).max(1);
+
+    // Release both lines.
+    sda.set_high();
+    scl.set_high();
+    delay.delay_us(half);
+
+    for _ in 0..9 {
+        if sda.is_high() { break }
+
+        scl.set_low();
+        delay.delay_us(half);
+        scl.set_high();
+        delay.delay_us(half);
+    }
+
+    if sda.is_low() { return Err(RecoveryError::SDAStuck) }
+
+    // STOP condition: SDA rises while SCL is high.
+    scl.set_low();
+    delay.delay_us(half);
+    sda.set_low();
+    delay.delay_us(half);
+    scl.set_high();
+    delay.delay_us(half);
+    sda.set_high();
+    delay.delay_us(half);
+
+    Ok(())
+}
@@ -0,0 +1,394 @@
+//! SMBus protocol layer.
+//! Implements the SMBus transactions over any 7-bit `I2CDriver`, with
+//! optional Packet Error Checking (PEC).
+
+
+
+use crate::crc::{ Crc, CRC_8_SMBUS };
+
+use super::super::{ DriverError, ErrorKind };
+use super::{ I2CAddress7bit, I2CDriver, I2COperation };
+
+
+
+/// Maximum number of data bytes in a block transfer (SMBus 3.0).
+pub const SMBUS_BLOCK_MAX: usize = 255;
+
+/// Alert Response Address.
+pub const SMBUS_ALERT_RESPONSE: I2CAddress7bit = I2CAddress7bit::new(0x0C);
+
+/// CRC-8 used by the PEC.
+static PEC: Crc<u8> = Crc::<u8>::new(&CRC_8_SMBUS);
+
+
+
+/// Errors reported by the SMBus layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SMBusError<E> {
+    /// The I2C driver reported an error.
+    Bus(E),
+
+    /// The received PEC byte does not match the transaction.
+    Pec,
+
+    /// The block does not fit in the buffer or exceeds the SMBus maximum.
+    Length,
+}
+
+impl<E: DriverError> DriverError for SMBusError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SMBusError::Bus(e) => e.kind(),
+            SMBusError::Pec | SMBusError::Length => ErrorKind::Other,
+        }
+    }
+}
+
+impl<E> From<E> for SMBusError<E> {
+    fn from(e: E) -> Self {
+        SMBusError::Bus(e)
+    }
+}
+
+
+
+/// SMBus controller over an I2C driver.
+pub struct SMBus<D> {
+    /// Wrapped I2C driver.
+    bus: D,
+
+    /// Packet Error Checking enabled.
+    pec: bool,
+}
+
+impl<D: I2CDriver> SMBus<D> {
+    /// Wraps the given I2C driver, with PEC disabled.
+    pub const fn new(bus: D) -> Self {
+        Self { bus, pec: false }
+    }
+
+    /// Enables / Disables Packet Error Checking.
+    pub fn pec(&mut self, enable: bool) {
+        self.pec = enable;
+    }
+
+    /// Consumes the layer and returns the I2C driver.
+    pub fn release(self) -> D {
+        self.bus
+    }
+
+    /// Quick Command. The R/W bit is the data.
+    pub fn quick(&mut self, addr: I2CAddress7bit, read: bool) -> Result<(), SMBusError<D::Error>> {
+        let operation = match read {
+            true => I2COperation::Read(&mut []),
+            _ => I2COperation::Write(&[]),
+        };
+
+        Ok(self.bus.transaction(addr, &mut [operation])?)
+    }
+
+    /// Send Byte.
+    pub fn send_byte(&mut self, addr: I2CAddress7bit, byte: u8) -> Result<(), SMBusError<D::Error>> {
+        self.write(addr, &[byte], &[])
+    }
+
+    /// Receive Byte.
+    pub fn receive_byte(&mut self, addr: I2CAddress7bit) -> Result<u8, SMBusError<D::Error>> {
+        let mut buf = [0u8; 2];
+        let n = 1 + self.pec as usize;
+
+        self.bus.receive(addr, &mut buf[..n])?;
+
+        if self.pec && (pec(&[&[read(addr)], &buf[..1]]) != buf[1]) {
+            return Err(SMBusError::Pec);
+        }
+
+        Ok(buf[0])
+    }
+
+    /// Write Byte.
+    pub fn write_byte(&mut self, addr: I2CAddress7bit, command: u8, byte: u8) -> Result<(), SMBusError<D::Error>> {
+        self.write(addr, &[command], &[byte])
+    }
+
+    /// Write Word. Words are sent LSB first.
+    pub fn write_word(&mut self, addr: I2CAddress7bit, command: u8, word: u16) -> Result<(), SMBusError<D::Error>> {
+        self.write(addr, &[command], &word.to_le_bytes())
+    }
+
+    /// Read Byte.
+    pub fn read_byte(&mut self, addr: I2CAddress7bit, command: u8) -> Result<u8, SMBusError<D::Error>> {
+        let mut buf = [0u8; 1];
+        self.read(addr, &[command], &mut buf)?;
+
+        Ok(buf[0])
+    }
+
+    /// Read Word. Words are received LSB first.
+    pub fn read_word(&mut self, addr: I2CAddress7bit, command: u8) -> Result<u16, SMBusError<D::Error>> {
+        let mut buf = [0u8; 2];
+        self.read(addr, &[command], &mut buf)?;
+
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Process Call. Writes a word and reads back a word.
+    pub fn process_call(&mut self, addr: I2CAddress7bit, command: u8, word: u16) -> Result<u16, SMBusError<D::Error>> {
+        let [lo, hi] = word.to_le_bytes();
+        let mut buf = [0u8; 2];
+        self.read(addr, &[command, lo, hi], &mut buf)?;
+
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Block Write.
+    pub fn block_write(&mut self, addr: I2CAddress7bit, command: u8, data: &[u8]) -> Result<(), SMBusError<D::Error>> {
+        if data.is_empty() || (data.len() > SMBUS_BLOCK_MAX) { return Err(SMBusError::Length) }
+
+        self.write(addr, &[command, data.len() as u8], data)
+    }
+
+    /// Block Read. Returns the number of bytes received into the buffer.
+    /// Reads as many bytes as the buffer can hold, the count sent by the
+    /// target determines how many of them are valid. Size the buffer for the
+    /// expected block so that the read stops right after it.
+    pub fn block_read(&mut self, addr: I2CAddress7bit, command: u8, data: &mut [u8]) -> Result<usize, SMBusError<D::Error>> {
+        self.block(addr, &[command], data)
+    }
+
+    /// Block Write - Block Read Process Call.
+    /// Returns the number of bytes received into the buffer.
+    pub fn block_process_call(&mut self, addr: I2CAddress7bit, command: u8, send: &[u8], recv: &mut [u8]) -> Result<usize, SMBusError<D::Error>> {
+        if send.is_empty() || (send.len() > SMBUS_BLOCK_MAX - 1) { return Err(SMBusError::Length) }
+
+        let mut header = [0u8; SMBUS_BLOCK_MAX + 1];
+        header[0] = command;
+        header[1] = send.len() as u8;
+        header[2..2 + send.len()].copy_from_slice(send);
+
+        self.block(addr, &header[..2 + send.len()], recv)
+    }
+
+    /// Reads the Alert Response Address.
+    /// Returns the address of the device that asserted SMBALERT#, or `None`
+    /// if no device answered.
+    pub fn alert_response(&mut self) -> Option<I2CAddress7bit> {
+        let mut buf = [0u8; 2];
+        let n = 1 + self.pec as usize;
+
+        self.bus.receive(SMBUS_ALERT_RESPONSE, &mut buf[..n]).ok()?;
+
+        if self.pec && (pec(&[&[read(SMBUS_ALERT_RESPONSE)], &buf[..1]]) != buf[1]) {
+            return None;
+        }
+
+        Some(I2CAddress7bit::new(buf[0] >> 1))
+    }
+
+    /// Writes the header and the data, followed by the PEC byte if enabled.
+    fn write(&mut self, addr: I2CAddress7bit, header: &[u8], data: &[u8]) -> Result<(), SMBusError<D::Error>> {
+        let pec = [pec(&[&[write(addr)], header, data])];
+
+        let mut operations = [
+            I2COperation::Write(header),
+            I2COperation::Write(data),
+            I2COperation::Write(if self.pec { &pec } else { &[] }),
+        ];
+
+        Ok(self.bus.transaction(addr, &mut operations)?)
+    }
+
+    /// Writes the header then reads the data, followed by the PEC byte if enabled.
+    fn read(&mut self, addr: I2CAddress7bit, header: &[u8], data: &mut [u8]) -> Result<(), SMBusError<D::Error>> {
+        let mut crc = [0u8];
+
+        {
+            let mut operations = [
+                I2COperation::WriteRead(header, data),
+                I2COperation::Read(if self.pec { &mut crc } else { &mut [] }),
+            ];
+
+            self.bus.transaction(addr, &mut operations)?;
+        }
+
+        if self.pec && (pec(&[&[write(addr)], header, &[read(addr)], data]) != crc[0]) {
+            return Err(SMBusError::Pec);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the header then reads a block.
+    /// The operations of a transaction are fixed before it starts, so the
+    /// length of the read cannot follow the count byte sent by the target.
+    /// The count is read into its own buffer and the data straight into
+    /// `data` (adjacent reads are merged into a single transfer), followed by
+    /// the PEC byte: exactly `count + PEC` bytes are read when the buffer
+    /// matches the block. A shorter block is followed by its PEC and filler
+    /// bytes from the target, a longer one fails with `SMBusError::Length`.
+    fn block(&mut self, addr: I2CAddress7bit, header: &[u8], data: &mut [u8]) -> Result<usize, SMBusError<D::Error>> {
+        let len = data.len().min(SMBUS_BLOCK_MAX);
+        let mut count = [0u8];
+        let mut crc = [0u8];
+
+        {
+            let mut operations = [
+                I2COperation::WriteRead(header, &mut count),
+                I2COperation::Read(&mut data[..len]),
+                I2COperation::Read(if self.pec { &mut crc } else { &mut [] }),
+            ];
+
+            self.bus.transaction(addr, &mut operations)?;
+        }
+
+        let count = count[0] as usize;
+        if (count == 0) || (count > len) { return Err(SMBusError::Length) }
+
+        // The PEC byte follows the last data byte sent by the target.
+        let received = match count == len {
+            true => crc[0],
+            _ => data[count],
+        };
+
+        if self.pec && (pec(&[&[write(addr)], header, &[read(addr), count as u8], &data[..count]]) != received) {
+            return Err(SMBusError::Pec);
+        }
+
+        Ok(count)
+    }
+}
+
+
+
+/// Address byte of a write.
+#[inline(always)]
+fn write(addr: I2CAddress7bit) -> u8 {
+    addr.0 << 1
+}
+
+/// Address byte of a read.
+#[inline(always)]
+fn read(addr: I2CAddress7bit) -> u8 {
+    (addr.0 << 1) | 1
+}
+
+/// Computes the PEC of the bytes of a transaction.
+fn pec(parts: &[&[u8]]) -> u8 {
+    let mut digest = PEC.digest();
+
+    for part in parts {
+        digest.update(part);
+    }
+
+    digest.finalize()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::super::mock::{ I2CMock, MockError };
+
+    const DEVICE: I2CAddress7bit = I2CAddress7bit::new(0x5A);
+
+    /// Target with the given bytes stored from the register `at`.
+    fn target(at: usize, bytes: &[u8]) -> I2CMock<256> {
+        let mut memory = [0xFF; 256];
+        memory[at..at + bytes.len()].copy_from_slice(bytes);
+
+        I2CMock::new(DEVICE, memory)
+    }
+
+    #[test]
+    fn write_byte_without_pec() {
+        let mut smbus = SMBus::new(target(0, &[]));
+
+        assert_eq!(smbus.write_byte(DEVICE, 0x10, 0x42), Ok(()));
+        assert_eq!(smbus.bus.memory()[0x10..0x12], [0x42, 0xFF]);
+    }
+
+    #[test]
+    fn write_byte_with_pec() {
+        let mut smbus = SMBus::new(target(0, &[]));
+        smbus.pec(true);
+
+        assert_eq!(smbus.write_byte(DEVICE, 0x10, 0x42), Ok(()));
+        assert_eq!(smbus.bus.memory()[0x10..0x12], [0x42, 0xDF]);
+    }
+
+    #[test]
+    fn read_word_checks_pec() {
+        let mut smbus = SMBus::new(target(0x20, &[0x34, 0x12, 0x79]));
+        smbus.pec(true);
+        assert_eq!(smbus.read_word(DEVICE, 0x20), Ok(0x1234));
+
+        let mut smbus = SMBus::new(target(0x20, &[0x34, 0x12, 0x78]));
+        smbus.pec(true);
+        assert_eq!(smbus.read_word(DEVICE, 0x20), Err(SMBusError::Pec));
+    }
+
+    #[test]
+    fn receive_byte_with_pec() {
+        let mut smbus = SMBus::new(target(0, &[0x55, 0xA2]));
+        smbus.pec(true);
+
+        assert_eq!(smbus.receive_byte(DEVICE), Ok(0x55));
+    }
+
+    #[test]
+    fn block_write_sends_count_and_pec() {
+        let mut smbus = SMBus::new(target(0, &[]));
+
+        assert_eq!(smbus.block_write(DEVICE, 0x30, &[1, 2, 3]), Ok(()));
+        assert_eq!(smbus.bus.memory()[0x30..0x35], [3, 1, 2, 3, 0xFF]);
+
+        smbus.pec(true);
+        assert_eq!(smbus.block_write(DEVICE, 0x30, &[1, 2, 3]), Ok(()));
+        assert_eq!(smbus.bus.memory()[0x30..0x35], [3, 1, 2, 3, 0xC9]);
+        assert_eq!(smbus.block_write(DEVICE, 0x30, &[]), Err(SMBusError::Length));
+    }
+
+    #[test]
+    fn block_read_of_expected_length() {
+        let mut smbus = SMBus::new(target(0x40, &[3, 0xAA, 0xBB, 0xCC, 0x90]));
+        let mut data = [0; 3];
+
+        assert_eq!(smbus.block_read(DEVICE, 0x40, &mut data), Ok(3));
+        assert_eq!(data, [0xAA, 0xBB, 0xCC]);
+
+        smbus.pec(true);
+        data = [0; 3];
+        assert_eq!(smbus.block_read(DEVICE, 0x40, &mut data), Ok(3));
+        assert_eq!(data, [0xAA, 0xBB, 0xCC]);
+    }
+
+    #[test]
+    fn block_read_shorter_than_buffer() {
+        // The PEC follows the last data byte, then the target sends filler bytes.
+        let mut smbus = SMBus::new(target(0x40, &[2, 0xAA, 0xBB, 0x97]));
+        smbus.pec(true);
+
+        let mut data = [0; 4];
+        assert_eq!(smbus.block_read(DEVICE, 0x40, &mut data), Ok(2));
+        assert_eq!(data[..2], [0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn block_read_rejects_invalid_count() {
+        let mut data = [0; 3];
+
+        let mut smbus = SMBus::new(target(0x40, &[4, 1, 2, 3]));
+        assert_eq!(smbus.block_read(DEVICE, 0x40, &mut data), Err(SMBusError::Length));
+
+        let mut smbus = SMBus::new(target(0x40, &[0, 1, 2, 3]));
+        assert_eq!(smbus.block_read(DEVICE, 0x40, &mut data), Err(SMBusError::Length));
+    }
+
+    #[test]
+    fn bus_errors_are_forwarded() {
+        let mut smbus = SMBus::new(target(0, &[]));
+
+        assert_eq!(smbus.write_byte(I2CAddress7bit::new(0x5B), 0x10, 0x42), Err(SMBusError::Bus(MockError::AddressNack)));
+    }
+}
@@ -0,0 +1,180 @@
+//! I2C target (slave) mode.
+//! Events on the bus are delivered from the ISR of the peripheral to a
+//! `I2CTargetHandler`, which answers each one with a `I2CTargetResponse`.
+
+
+
+use super::{ I2CAddress, I2CAddress7bit };
+use super::super::CommunicationError;
+
+
+
+/// Target mode configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I2CTargetConfig<A: I2CAddress = I2CAddress7bit> {
+    /// Primary address of the target.
+    pub primary: A,
+
+    /// Optional second address of the target.
+    pub secondary: Option<A>,
+
+    /// Answer to the general call address (0x00).
+    pub general: bool,
+
+    /// Allow the target to stretch the clock.
+    pub stretching: bool,
+}
+
+impl<A: I2CAddress> I2CTargetConfig<A> {
+    /// Creates a configuration answering only to the given address, with clock stretching enabled.
+    pub const fn new(primary: A) -> Self {
+        Self { primary, secondary: None, general: false, stretching: true }
+    }
+
+    /// Configures the second address.
+    #[inline]
+    pub const fn secondary(mut self, addr: A) -> Self {
+        self.secondary = Some(addr);
+        self
+    }
+
+    /// Enables / Disables answering to the general call address.
+    #[inline]
+    pub const fn general(mut self, general: bool) -> Self {
+        self.general = general;
+        self
+    }
+
+    /// Enables / Disables clock stretching.
+    #[inline]
+    pub const fn stretching(mut self, stretching: bool) -> Self {
+        self.stretching = stretching;
+        self
+    }
+}
+
+
+
+/// Bus event seen by the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2CTargetEvent<A: I2CAddress = I2CAddress7bit> {
+    /// The controller addressed the target.
+    AddressMatch {
+        /// Matched address. For the general call this is the primary address.
+        addr: A,
+
+        /// `true` if the general call address was matched.
+        general: bool,
+
+        /// `true` if the controller is going to read from the target.
+        read: bool,
+    },
+
+    /// The controller requests the next byte.
+    ReadRequest,
+
+    /// The controller wrote a byte.
+    WriteReceived(u8),
+
+    /// A STOP (or a repeated START to another target) ended the transaction.
+    Stop,
+}
+
+
+
+/// Answer of the target to a bus event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2CTargetResponse {
+    /// Acknowledge the address or the written byte.
+    Ack,
+
+    /// Do not acknowledge the address or the written byte.
+    Nack,
+
+    /// Byte to send for a read request.
+    Data(u8),
+
+    /// Hold the clock low until `I2CTargetDriver::release` is called.
+    /// Only valid if clock stretching is enabled.
+    Stretch,
+}
+
+
+
+/// I2C target mode driver.
+pub trait I2CTargetDriver<A: I2CAddress = I2CAddress7bit>: CommunicationError {
+    /// Applies the given target configuration.
+    fn configure(&mut self, config: &I2CTargetConfig<A>) -> Result<(), Self::Error>;
+
+    /// Installs the event handler and starts listening on the bus.
+    /// The handler is called from the ISR of the peripheral.
+    fn listen(&mut self, handler: I2CTargetHandler<A>) -> Result<(), Self::Error>;
+
+    /// Stops listening on the bus. The target will not acknowledge its addresses.
+    fn disable(&mut self) -> Result<(), Self::Error>;
+
+    /// Enables / Disables clock stretching.
+    fn stretching(&mut self, enable: bool) -> Result<(), Self::Error>;
+
+    /// Answers the pending event and releases the clock after a `Stretch` response.
+    fn release(&mut self, response: I2CTargetResponse) -> Result<(), Self::Error>;
+}
+
+
+
+/// Target event handler with an optional user provided context.
+/// Same design as `UserHandler`, with the event as argument and the response as output.
+pub struct I2CTargetHandler<A: I2CAddress = I2CAddress7bit> {
+    /// Pointer to the function.
+    handler: *const (),
+
+    /// Pointer to the context of this function.
+    context: Option<*mut ()>,
+
+    /// Initialization flag.
+    init: bool,
+
+    #[doc(hidden)]
+    _addr: core::marker::PhantomData<A>,
+}
+
+impl<A: I2CAddress> I2CTargetHandler<A> {
+    /// Static intializer.
+    pub const fn empty() -> Self {
+        Self { handler: core::ptr::null(), context: None, init: false, _addr: core::marker::PhantomData }
+    }
+
+    /// Calls the handler function with the given event.
+    /// Uninitialized handlers do not acknowledge anything.
+    pub fn call(&self, event: I2CTargetEvent<A>) -> I2CTargetResponse {
+        if !self.init { return I2CTargetResponse::Nack }
+
+        match self.context {
+            Some(cx) => {
+                // Empty reference to the context.
+                let cxref: &mut () = unsafe { &mut *(cx) };
+
+                // Empty reference to the function.
+                let fnref: fn(&mut (), I2CTargetEvent<A>) -> I2CTargetResponse = unsafe { core::mem::transmute::<*const (), _>(self.handler) };
+
+                fnref( cxref, event )
+            },
+            _ => {
+                // Empty reference to the function.
+                let fnref: fn(I2CTargetEvent<A>) -> I2CTargetResponse = unsafe { core::mem::transmute::<*const (), _>(self.handler) };
+
+                fnref( event )
+            },
+        }
+    }
+
+    /// Creates a handler without context.
+    pub const fn isolated(handler: fn(I2CTargetEvent<A>) -> I2CTargetResponse) -> Self {
+        Self { handler: handler as *const (), context: None, init: true, _addr: core::marker::PhantomData }
+    }
+
+    /// Creates a handler with the given context.
+    pub const fn contextualized<T: Sized>(handler: fn(&mut T, I2CTargetEvent<A>) -> I2CTargetResponse, context: &'static mut T) -> Self {
+        Self { handler: handler as *const (), context: Some( context as *mut T as *mut () ), init: true, _addr: core::marker::PhantomData }
+    }
+}
@@ -0,0 +1,273 @@
+//! Host-side CAN mock.
+//! Either two nodes connected through a pair of frame queues, or a single node
+//! receiving its own frames (loopback). Transmitted frames are delivered to
+//! the queue of the peer at once and the peer applies its acceptance filters
+//! when reading them. The fault confinement state can be forced to exercise
+//! the error paths of a driver.
+
+
+
+use core::future::{ poll_fn, Future };
+use core::task::Poll;
+
+use crate::buffer::RingBuffer;
+
+use super::super::{
+    CanAsyncRxDriver, CanAsyncTxDriver, CanConfig, CanDriver, CanError, CanFilter,
+    CanFrame, CanRxDriver, CanState, CanTxDriver, CommunicationError,
+};
+
+
+
+/// Number of acceptance filters of the mock.
+const FILTERS: usize = 8;
+
+
+
+/// CAN bus with `N` frames of buffering for each node.
+pub struct CanLink<const N: usize> {
+    /// Frames from the first node to the second one.
+    forward: RingBuffer<CanFrame, N>,
+
+    /// Frames from the second node to the first one.
+    backward: RingBuffer<CanFrame, N>,
+}
+
+impl<const N: usize> CanLink<N> {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self { forward: RingBuffer::new(), backward: RingBuffer::new() }
+    }
+
+    /// Returns both nodes of the bus.
+    pub fn split(&mut self) -> (CanMock<'_, N>, CanMock<'_, N>) {
+        (CanMock::new(&self.forward, &self.backward), CanMock::new(&self.backward, &self.forward))
+    }
+
+    /// Returns a single node that receives its own frames.
+    pub fn loopback(&mut self) -> CanMock<'_, N> {
+        CanMock::new(&self.forward, &self.forward)
+    }
+}
+
+impl<const N: usize> Default for CanLink<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+/// Node of a `CanLink`.
+pub struct CanMock<'a, const N: usize> {
+    /// Transmission queue.
+    tx: &'a RingBuffer<CanFrame, N>,
+
+    /// Reception queue.
+    rx: &'a RingBuffer<CanFrame, N>,
+
+    /// Last applied configuration.
+    config: CanConfig,
+
+    /// Acceptance filters.
+    filters: [CanFilter; FILTERS],
+
+    /// Number of active filters.
+    nfilters: usize,
+
+    /// Forced fault confinement state.
+    state: CanState,
+}
+
+impl<'a, const N: usize> CanMock<'a, N> {
+    /// Creates a node accepting all frames.
+    fn new(tx: &'a RingBuffer<CanFrame, N>, rx: &'a RingBuffer<CanFrame, N>) -> Self {
+        Self { tx, rx, config: CanConfig::default(), filters: [CanFilter::All; FILTERS], nfilters: 1, state: CanState::ErrorActive }
+    }
+
+    /// Last applied configuration.
+    pub fn config(&self) -> &CanConfig {
+        &self.config
+    }
+
+    /// Forces the fault confinement state, e.g. `BusOff` to make transmissions fail.
+    pub fn force_state(&mut self, state: CanState) {
+        self.state = state;
+    }
+
+    /// Number of frames waiting to be read by this node, before filtering.
+    pub fn pending(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Injects a frame as if received from the bus.
+    /// Returns `false` if the queue is full.
+    pub fn inject(&self, frame: &CanFrame) -> bool {
+        self.rx.push(*frame).is_ok()
+    }
+
+    /// Checks that the frame can be transmitted.
+    fn check(&self, frame: &CanFrame) -> Result<(), CanError> {
+        if self.state == CanState::BusOff { return Err(CanError::BusOff) }
+        if frame.is_fd() && !self.config.is_fd() { return Err(CanError::Unsupported) }
+
+        Ok(())
+    }
+
+    /// Pops frames until one passes the filters.
+    fn next(&self) -> Option<CanFrame> {
+        while let Some(frame) = self.rx.pop() {
+            if self.filters[..self.nfilters].iter().any(|filter| filter.matches(frame.id())) { return Some(frame) }
+        }
+
+        None
+    }
+}
+
+impl<'a, const N: usize> CommunicationError for CanMock<'a, N> {
+    type Error = CanError;
+}
+
+impl<'a, const N: usize> CanDriver for CanMock<'a, N> {
+    fn configure(&mut self, config: &CanConfig) -> Result<(), Self::Error> {
+        self.config = *config;
+        Ok(())
+    }
+
+    fn filters(&mut self, filters: &[CanFilter]) -> Result<(), Self::Error> {
+        if filters.len() > FILTERS { return Err(CanError::Unsupported) }
+
+        self.filters[..filters.len()].copy_from_slice(filters);
+        self.nfilters = filters.len();
+
+        Ok(())
+    }
+
+    fn state(&self) -> CanState {
+        self.state
+    }
+}
+
+impl<'a, const N: usize> CanTxDriver for CanMock<'a, N> {
+    fn transmit(&mut self, frame: &CanFrame) -> Result<(), Self::Error> {
+        while !self.try_transmit(frame)? {}
+        Ok(())
+    }
+
+    fn try_transmit(&mut self, frame: &CanFrame) -> Result<bool, Self::Error> {
+        self.check(frame)?;
+        Ok(self.tx.push(*frame).is_ok())
+    }
+}
+
+impl<'a, const N: usize> CanRxDriver for CanMock<'a, N> {
+    fn receive(&mut self) -> Result<CanFrame, Self::Error> {
+        loop {
+            if let Some(frame) = self.next() { return Ok(frame) }
+        }
+    }
+
+    fn try_receive(&mut self) -> Result<Option<CanFrame>, Self::Error> {
+        Ok(self.next())
+    }
+}
+
+impl<'a, const N: usize> CanAsyncTxDriver for CanMock<'a, N> {
+    type TransmitFuture<'b> = impl Future<Output = Result<(), Self::Error>> + 'b where Self: 'b;
+
+    fn transmit<'b>(&'b mut self, frame: &'b CanFrame) -> Self::TransmitFuture<'b> {
+        poll_fn(move |cx| {
+            if CanTxDriver::try_transmit(self, frame)? { return Poll::Ready(Ok(())) }
+
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+}
+
+impl<'a, const N: usize> CanAsyncRxDriver for CanMock<'a, N> {
+    type ReceiveFuture<'b> = impl Future<Output = Result<CanFrame, Self::Error>> + 'b where Self: 'b;
+
+    fn receive<'b>(&'b mut self) -> Self::ReceiveFuture<'b> {
+        poll_fn(move |cx| {
+            if let Some(frame) = self.next() { return Poll::Ready(Ok(frame)) }
+
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::block_on;
+    use super::super::super::CanId;
+
+    #[test]
+    fn frames_reach_the_peer() {
+        let mut link = CanLink::<4>::new();
+        let (mut a, mut b) = link.split();
+
+        let frame = CanFrame::new(CanId::Standard(0x100), &[1, 2, 3]).unwrap();
+
+        assert_eq!(CanTxDriver::transmit(&mut a, &frame), Ok(()));
+        assert_eq!(block_on(CanAsyncRxDriver::receive(&mut b)), Ok(frame));
+        assert_eq!(CanRxDriver::try_receive(&mut a), Ok(None));
+    }
+
+    #[test]
+    fn filters_apply_on_reception() {
+        let mut link = CanLink::<4>::new();
+        let (mut a, mut b) = link.split();
+
+        b.filters(&[CanFilter::exact(CanId::Standard(0x200))]).unwrap();
+
+        let other = CanFrame::new(CanId::Standard(0x100), &[]).unwrap();
+        let frame = CanFrame::new(CanId::Standard(0x200), &[]).unwrap();
+
+        CanTxDriver::transmit(&mut a, &other).unwrap();
+        CanTxDriver::transmit(&mut a, &frame).unwrap();
+
+        assert_eq!(CanRxDriver::try_receive(&mut b), Ok(Some(frame)));
+        assert_eq!(b.pending(), 0);
+
+        assert_eq!(b.filters(&[CanFilter::All; FILTERS + 1]), Err(CanError::Unsupported));
+    }
+
+    #[test]
+    fn bus_off_fails_transmissions() {
+        let mut link = CanLink::<4>::new();
+        let (mut a, b) = link.split();
+
+        let frame = CanFrame::new(CanId::Standard(0x100), &[]).unwrap();
+
+        a.force_state(CanState::BusOff);
+        assert_eq!(a.state(), CanState::BusOff);
+        assert_eq!(CanTxDriver::transmit(&mut a, &frame), Err(CanError::BusOff));
+        assert_eq!(CanTxDriver::try_transmit(&mut a, &frame), Err(CanError::BusOff));
+        assert_eq!(block_on(CanAsyncTxDriver::transmit(&mut a, &frame)), Err(CanError::BusOff));
+        assert_eq!(b.pending(), 0);
+
+        // Error passive does not fail operations.
+        a.force_state(CanState::ErrorPassive);
+        assert_eq!(CanTxDriver::transmit(&mut a, &frame), Ok(()));
+        assert_eq!(b.pending(), 1);
+    }
+
+    #[test]
+    fn fd_frames_need_fd_configuration() {
+        let mut link = CanLink::<4>::new();
+        let mut node = link.loopback();
+
+        let frame = CanFrame::fd(CanId::Standard(0x100), &[0; 12]).unwrap();
+
+        assert_eq!(CanTxDriver::transmit(&mut node, &frame), Err(CanError::Unsupported));
+
+        node.configure(&CanConfig::new(500_000).fd(2_000_000)).unwrap();
+        assert_eq!(CanTxDriver::transmit(&mut node, &frame), Ok(()));
+        assert_eq!(CanRxDriver::receive(&mut node), Ok(frame));
+    }
+}
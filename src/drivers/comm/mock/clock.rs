@@ -0,0 +1,52 @@
+//! Host-side time source.
+
+
+
+use core::cell::Cell;
+use core::time::Duration;
+
+use crate::time::TimeSource;
+
+
+
+/// Manually driven time source for host tests.
+/// Time only moves when advanced, or by a fixed step on every read so that
+/// timeouts expire while an executor busy polls.
+pub struct MockClock {
+    /// Current time in microseconds.
+    now: Cell<u64>,
+
+    /// Microseconds added after every read.
+    step: u64,
+}
+
+impl MockClock {
+    /// Creates a clock at time 0 that only moves when advanced.
+    pub const fn new() -> Self {
+        Self { now: Cell::new(0), step: 0 }
+    }
+
+    /// Creates a clock at time 0 that advances by `step` on every read.
+    pub const fn ticking(step: u64) -> Self {
+        Self { now: Cell::new(0), step }
+    }
+
+    /// Advances the clock.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration.as_micros() as u64);
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for MockClock {
+    fn now(&self) -> u64 {
+        let now = self.now.get();
+        self.now.set(now + self.step);
+        now
+    }
+}
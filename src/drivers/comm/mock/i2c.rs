@@ -0,0 +1,365 @@
+//! Host-side I2C mock.
+//! Simulates a register based target (EEPROM style) on a bus. The first byte
+//! written in a transaction selects the register pointer, the following bytes
+//! are written from it and reads return data from it, auto-incrementing.
+//! Adjacent operations in the same direction form a single transfer, as
+//! required by `I2COperation`.
+//! Asynchronous transfers move one byte per poll, which makes cancellation
+//! observable: dropping a future mid-transfer is recorded as an abort.
+//! The target can also be stalled to simulate a device that stopped
+//! responding, transfers then pend until they are dropped.
+
+
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{ Context, Poll };
+
+use super::{ MockError, Operations };
+use super::super::{ CommunicationError, I2CAddress7bit, I2CAsyncDriver, I2CDriver, I2COperation };
+
+
+
+/// Mock I2C bus with a single register based target.
+pub struct I2CMock<const N: usize> {
+    /// Address of the simulated target.
+    address: I2CAddress7bit,
+
+    /// Registers of the simulated target.
+    memory: [u8; N],
+
+    /// Register pointer.
+    pointer: usize,
+
+    /// `true` while a transaction is in progress.
+    busy: bool,
+
+    /// `true` while the target holds the bus without answering.
+    stalled: bool,
+
+    /// Number of transfers cancelled mid-transaction.
+    aborts: usize,
+
+    /// Number of completed transactions.
+    transactions: usize,
+}
+
+impl<const N: usize> I2CMock<N> {
+    /// Creates a mock with a target at the given address and initial register contents.
+    pub const fn new(address: I2CAddress7bit, memory: [u8; N]) -> Self {
+        Self { address, memory, pointer: 0, busy: false, stalled: false, aborts: 0, transactions: 0 }
+    }
+
+    /// Registers of the simulated target.
+    pub fn memory(&self) -> &[u8; N] {
+        &self.memory
+    }
+
+    /// Stalls / Releases the target. Asynchronous transfers make no progress while stalled.
+    pub fn stall(&mut self, stalled: bool) {
+        self.stalled = stalled;
+    }
+
+    /// Returns `true` if a transaction is in progress.
+    pub fn busy(&self) -> bool {
+        self.busy
+    }
+
+    /// Number of transfers cancelled mid-transaction.
+    pub fn aborts(&self) -> usize {
+        self.aborts
+    }
+
+    /// Number of completed transactions.
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+
+    /// Starts a transaction.
+    fn start(&mut self, addr: I2CAddress7bit) -> Result<(), MockError> {
+        if self.busy { return Err(MockError::Busy) }
+        if addr != self.address { return Err(MockError::AddressNack) }
+
+        self.busy = true;
+
+        Ok(())
+    }
+
+    /// Ends the current transaction with a STOP.
+    fn stop(&mut self, completed: bool) {
+        if !self.busy { return }
+
+        self.busy = false;
+
+        if completed { self.transactions += 1 }
+        else { self.aborts += 1 }
+    }
+
+    /// Transfers the byte at the cursor. Returns `false` when all operations are done.
+    fn step(&mut self, operations: &mut [I2COperation<'_>], cursor: &mut Cursor) -> bool {
+        while let Some(operation) = operations.get_mut(cursor.op) {
+            let (write, read): (&[u8], &mut [u8]) = match operation {
+                I2COperation::Write(w) => (w, &mut []),
+                I2COperation::Read(r) => (&[], r),
+                I2COperation::WriteRead(w, r) => (w, r),
+            };
+
+            if cursor.byte < write.len() {
+                let byte = write[cursor.byte];
+
+                // The first byte of the transaction selects the register.
+                if cursor.first { self.pointer = byte as usize }
+                else { self.write(byte) }
+
+                cursor.first = false;
+                cursor.byte += 1;
+
+                return true;
+            }
+
+            if let Some(byte) = read.get_mut(cursor.byte - write.len()) {
+                *byte = self.read();
+                cursor.byte += 1;
+
+                return true;
+            }
+
+            cursor.op += 1;
+            cursor.byte = 0;
+        }
+
+        false
+    }
+
+    /// Writes a register and increments the pointer.
+    fn write(&mut self, byte: u8) {
+        if N == 0 { return }
+        self.memory[self.pointer % N] = byte;
+        self.pointer = (self.pointer + 1) % N;
+    }
+
+    /// Reads a register and increments the pointer.
+    fn read(&mut self) -> u8 {
+        if N == 0 { return 0xFF }
+        let byte = self.memory[self.pointer % N];
+        self.pointer = (self.pointer + 1) % N;
+        byte
+    }
+}
+
+impl<const N: usize> CommunicationError for I2CMock<N> {
+    type Error = MockError;
+}
+
+impl<const N: usize> I2CDriver for I2CMock<N> {
+    fn transaction(&mut self, addr: I2CAddress7bit, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error> {
+        self.start(addr)?;
+
+        // A stalled target holds the bus until the controller gives up.
+        if self.stalled {
+            self.stop(false);
+            return Err(MockError::Timeout);
+        }
+
+        let mut cursor = Cursor::new();
+        while self.step(operations, &mut cursor) {}
+
+        self.stop(true);
+
+        Ok(())
+    }
+}
+
+impl<const N: usize> I2CAsyncDriver for I2CMock<N> {
+    type WriteFuture<'a> = MockTransfer<'a, N>;
+    type ReadFuture<'a> = MockTransfer<'a, N>;
+    type WriteReadFuture<'a> = MockTransfer<'a, N>;
+    type TransactionFuture<'a> = MockTransfer<'a, N>;
+
+    fn transaction<'a>(&'a mut self, addr: I2CAddress7bit, operations: &'a mut [I2COperation<'a>]) -> Self::TransactionFuture<'a> {
+        MockTransfer::new(self, addr, Operations::Many(operations))
+    }
+
+    fn send<'a>(&'a mut self, addr: I2CAddress7bit, data: &'a [u8]) -> Self::WriteFuture<'a> {
+        MockTransfer::new(self, addr, Operations::One([I2COperation::Write(data)]))
+    }
+
+    fn receive<'a>(&'a mut self, addr: I2CAddress7bit, recv: &'a mut [u8]) -> Self::ReadFuture<'a> {
+        MockTransfer::new(self, addr, Operations::One([I2COperation::Read(recv)]))
+    }
+
+    fn transfer<'a>(&'a mut self, addr: I2CAddress7bit, send: &'a [u8], recv: &'a mut [u8]) -> Self::WriteReadFuture<'a> {
+        MockTransfer::new(self, addr, Operations::One([I2COperation::WriteRead(send, recv)]))
+    }
+}
+
+
+
+/// Asynchronous transfer on the mock. Moves one byte per poll.
+/// Dropping it before completion aborts the transfer and releases the bus.
+pub struct MockTransfer<'a, const N: usize> {
+    /// Borrowed mock.
+    mock: &'a mut I2CMock<N>,
+
+    /// Target address.
+    addr: I2CAddress7bit,
+
+    /// Operations of the transaction.
+    operations: Operations<'a>,
+
+    /// Progress of the transfer.
+    cursor: Cursor,
+
+    /// `true` once the transaction has started.
+    started: bool,
+}
+
+impl<'a, const N: usize> MockTransfer<'a, N> {
+    /// Creates a new transfer.
+    fn new(mock: &'a mut I2CMock<N>, addr: I2CAddress7bit, operations: Operations<'a>) -> Self {
+        Self { mock, addr, operations, cursor: Cursor::new(), started: false }
+    }
+}
+
+impl<'a, const N: usize> Future for MockTransfer<'a, N> {
+    type Output = Result<(), MockError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if !this.started {
+            this.mock.start(this.addr)?;
+            this.started = true;
+        }
+
+        if this.mock.stalled || this.mock.step(this.operations.as_mut(), &mut this.cursor) {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
+        this.mock.stop(true);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<'a, const N: usize> Drop for MockTransfer<'a, N> {
+    fn drop(&mut self) {
+        // Abort the transfer and release the bus if it did not complete.
+        if self.started { self.mock.stop(false) }
+    }
+}
+
+
+
+/// Progress through a list of operations.
+struct Cursor {
+    /// Current operation.
+    op: usize,
+
+    /// Current byte within the operation.
+    byte: usize,
+
+    /// `true` until the first byte has been written.
+    first: bool,
+}
+
+impl Cursor {
+    /// Creates a cursor at the start of the transaction.
+    const fn new() -> Self {
+        Self { op: 0, byte: 0, first: true }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{ Context, Poll, Waker };
+
+    use super::*;
+    use super::super::block_on;
+
+    const TARGET: I2CAddress7bit = I2CAddress7bit::new(0x50);
+
+    #[test]
+    fn dropped_transfer_aborts_and_releases_bus() {
+        let mut mock = I2CMock::new(TARGET, [0u8; 16]);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let data = [0x00, 1, 2, 3];
+            let mut transfer = pin!(I2CAsyncDriver::send(&mut mock, TARGET, &data));
+
+            assert!(transfer.as_mut().poll(&mut cx).is_pending());
+            assert!(transfer.as_mut().poll(&mut cx).is_pending());
+        }
+
+        assert_eq!(mock.aborts(), 1);
+        assert_eq!(mock.transactions(), 0);
+        assert!(!mock.busy());
+
+        // The bus is usable again after the cancellation.
+        assert_eq!(block_on(I2CAsyncDriver::send(&mut mock, TARGET, &[0x04, 0xAA])), Ok(()));
+        assert_eq!(mock.memory()[4], 0xAA);
+        assert_eq!(mock.transactions(), 1);
+    }
+
+    #[test]
+    fn completed_transfer_does_not_abort() {
+        let mut mock = I2CMock::new(TARGET, [0x11, 0x22, 0x33, 0x44]);
+        let mut recv = [0; 2];
+
+        assert_eq!(block_on(I2CAsyncDriver::transfer(&mut mock, TARGET, &[0x01], &mut recv)), Ok(()));
+        assert_eq!(recv, [0x22, 0x33]);
+        assert_eq!(mock.aborts(), 0);
+        assert!(!mock.busy());
+    }
+
+    #[test]
+    fn adjacent_operations_are_merged() {
+        let mut mock = I2CMock::new(TARGET, [0u8; 8]);
+
+        // The register pointer is only selected by the first byte of the transaction.
+        let mut operations = [I2COperation::Write(&[0x02]), I2COperation::Write(&[0xAA, 0xBB])];
+        assert_eq!(I2CDriver::transaction(&mut mock, TARGET, &mut operations), Ok(()));
+        assert_eq!(mock.memory(), &[0, 0, 0xAA, 0xBB, 0, 0, 0, 0]);
+
+        let (mut a, mut b) = ([0; 1], [0; 2]);
+        let mut operations = [I2COperation::Write(&[0x01]), I2COperation::Read(&mut a), I2COperation::Read(&mut b)];
+        assert_eq!(I2CDriver::transaction(&mut mock, TARGET, &mut operations), Ok(()));
+        assert_eq!((a, b), ([0], [0xAA, 0xBB]));
+        assert_eq!(mock.transactions(), 2);
+    }
+
+    #[test]
+    fn stalled_transfer_pends_until_dropped() {
+        let mut mock = I2CMock::new(TARGET, [0u8; 4]);
+        mock.stall(true);
+
+        let mut cx = Context::from_waker(Waker::noop());
+
+        {
+            let mut recv = [0; 1];
+            let mut transfer = pin!(I2CAsyncDriver::receive(&mut mock, TARGET, &mut recv));
+
+            for _ in 0..16 {
+                assert!(matches!(transfer.as_mut().poll(&mut cx), Poll::Pending));
+            }
+        }
+
+        assert_eq!(mock.aborts(), 1);
+        assert!(!mock.busy());
+    }
+
+    #[test]
+    fn wrong_address_is_not_acknowledged() {
+        let mut mock = I2CMock::new(TARGET, [0u8; 4]);
+
+        assert_eq!(block_on(I2CAsyncDriver::send(&mut mock, I2CAddress7bit::new(0x51), &[0])), Err(MockError::AddressNack));
+        assert_eq!(mock.aborts(), 0);
+        assert!(!mock.busy());
+    }
+}
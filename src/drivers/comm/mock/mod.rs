@@ -0,0 +1,133 @@
+//! Mock drivers for host testing of device drivers.
+
+
+
+mod can;
+mod clock;
+mod i2c;
+mod onewire;
+mod script;
+mod uart;
+
+
+
+pub use self::can::{ CanLink, CanMock };
+pub use self::clock::MockClock;
+pub use self::i2c::{ I2CMock, MockTransfer };
+pub use self::onewire::{ OneWireDevice, OneWireMock };
+pub use self::script::{ I2CExpectation, I2CScript, MockOperation, ScriptTransfer };
+pub use self::uart::{ UartLink, UartMock };
+
+
+
+use core::future::{ poll_fn, Future };
+use core::pin::pin;
+use core::task::{ Context, Poll, RawWaker, RawWakerVTable, Waker };
+
+use super::{ DriverError, ErrorKind, I2COperation };
+
+
+
+/// Errors reported by the mock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockError {
+    /// No target answered to the address.
+    AddressNack,
+
+    /// The target did not acknowledge a data byte.
+    DataNack,
+
+    /// Another controller took the bus.
+    ArbitrationLost,
+
+    /// The target stretched the clock for too long.
+    Timeout,
+
+    /// A transaction was started while another one was still in progress.
+    /// Reported when a cancelled transfer did not release the bus.
+    Busy,
+}
+
+impl DriverError for MockError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            MockError::AddressNack => ErrorKind::AddressNack,
+            MockError::DataNack => ErrorKind::DataNack,
+            MockError::ArbitrationLost => ErrorKind::ArbitrationLost,
+            MockError::Timeout => ErrorKind::Timeout,
+            MockError::Busy => ErrorKind::Other,
+        }
+    }
+}
+
+
+
+/// Trivial executor for host tests.
+/// Polls the future in a loop until it completes, ignoring wake-ups.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) { return output }
+    }
+}
+
+/// Runs two futures concurrently until both complete, e.g. both ends of a
+/// `UartLink`.
+pub async fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    let mut a = pin!(a);
+    let mut b = pin!(b);
+
+    let mut outa = None;
+    let mut outb = None;
+
+    poll_fn(|cx| {
+        if outa.is_none() {
+            if let Poll::Ready(output) = a.as_mut().poll(cx) { outa = Some(output) }
+        }
+
+        if outb.is_none() {
+            if let Poll::Ready(output) = b.as_mut().poll(cx) { outb = Some(output) }
+        }
+
+        match (outa.take(), outb.take()) {
+            (Some(a), Some(b)) => Poll::Ready((a, b)),
+            (a, b) => {
+                outa = a;
+                outb = b;
+                Poll::Pending
+            },
+        }
+    }).await
+}
+
+/// Waker that does nothing.
+const VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |_| RawWaker::new(core::ptr::null(), &VTABLE),
+    |_| {},
+    |_| {},
+    |_| {},
+);
+
+
+
+/// Operations owned or borrowed by a transfer.
+enum Operations<'a> {
+    /// Single operation built from the buffers of the call.
+    One([I2COperation<'a>; 1]),
+
+    /// Borrowed list of operations.
+    Many(&'a mut [I2COperation<'a>]),
+}
+
+impl<'a> Operations<'a> {
+    /// Returns the operations as a slice.
+    fn as_mut(&mut self) -> &mut [I2COperation<'a>] {
+        match self {
+            Operations::One(one) => one,
+            Operations::Many(many) => many,
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Host-side 1-Wire bus simulation.
+//! Each simulated device follows the time slots of the bus: it answers
+//! resets and the ROM commands (Search ROM, Alarm Search, Match ROM, Skip ROM,
+//! Read ROM). Once selected it records the bytes written to it, and outputs
+//! its data after its read command. Like real devices it cannot tell a read
+//! slot from a write 1 slot, and relies on the protocol to know which one to
+//! expect. Devices drive the bus as a wired AND.
+
+
+
+use super::super::CommunicationError;
+use super::super::onewire::{
+    OneWireDriver, OneWireError, RomCode,
+    ALARM_SEARCH, MATCH_ROM, READ_ROM, SEARCH_ROM, SKIP_ROM,
+};
+
+
+
+/// Maximum number of data bytes of a simulated device.
+const DATA_MAX: usize = 16;
+
+/// Number of written bytes recorded by a simulated device.
+const LOG_MAX: usize = 16;
+
+
+
+/// ROM layer state of a simulated device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Waiting for a reset.
+    Idle,
+
+    /// Receiving the ROM command.
+    Command,
+
+    /// Taking part in a search, at the given bit and step of the triplet.
+    Search(usize, u8),
+
+    /// Comparing a Match ROM code, at the given bit.
+    Match(usize),
+
+    /// Sending its ROM code, at the given bit.
+    ReadRom(usize),
+
+    /// Selected by the ROM command, receiving bytes.
+    Selected,
+
+    /// Outputting its data after its read command.
+    Output,
+}
+
+
+
+/// Simulated 1-Wire device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OneWireDevice {
+    /// ROM code.
+    rom: RomCode,
+
+    /// Alarm condition.
+    alarm: bool,
+
+    /// Function command that outputs the data.
+    command: u8,
+
+    /// Data output after the read command.
+    data: [u8; DATA_MAX],
+
+    /// Length of the data.
+    len: usize,
+
+    /// Bytes written once selected.
+    log: [u8; LOG_MAX],
+
+    /// Number of bytes written once selected.
+    logged: usize,
+
+    /// ROM layer state.
+    phase: Phase,
+
+    /// Bits received for the byte in progress.
+    shift: u8,
+
+    /// Number of bits received for the byte in progress.
+    bits: usize,
+
+    /// Next data bit to output.
+    out: usize,
+}
+
+impl OneWireDevice {
+    /// Creates a device with the given ROM code, without alarm or data.
+    pub const fn new(rom: RomCode) -> Self {
+        Self {
+            rom, alarm: false,
+            command: 0, data: [0xFF; DATA_MAX], len: 0,
+            log: [0; LOG_MAX], logged: 0,
+            phase: Phase::Idle, shift: 0, bits: 0, out: 0,
+        }
+    }
+
+    /// Configures the alarm condition.
+    #[inline]
+    pub const fn alarm(mut self, alarm: bool) -> Self {
+        self.alarm = alarm;
+        self
+    }
+
+    /// Configures the data output after the given function command (e.g.
+    /// a scratchpad after Read Scratchpad). Truncated to 16 bytes.
+    pub fn data(mut self, command: u8, data: &[u8]) -> Self {
+        self.command = command;
+        self.len = core::cmp::min(data.len(), DATA_MAX);
+        self.data[..self.len].copy_from_slice(&data[..self.len]);
+        self
+    }
+
+    /// ROM code.
+    pub const fn rom(&self) -> &RomCode {
+        &self.rom
+    }
+
+    /// Bytes written to the device since its last selection (first ones if more than 16).
+    pub fn written(&self) -> &[u8] {
+        &self.log[..core::cmp::min(self.logged, LOG_MAX)]
+    }
+
+    /// Returns `true` if the device is selected.
+    pub fn is_selected(&self) -> bool {
+        matches!(self.phase, Phase::Selected | Phase::Output)
+    }
+
+    /// Handles a reset pulse.
+    fn reset(&mut self) {
+        self.phase = Phase::Command;
+        self.shift = 0;
+        self.bits = 0;
+        self.out = 0;
+    }
+
+    /// Handles a write slot. Devices sending bits see it as a read slot.
+    fn write(&mut self, bit: bool) {
+        if matches!(self.phase, Phase::Search(_, 0 | 1) | Phase::ReadRom(_) | Phase::Output) {
+            self.read();
+            return;
+        }
+
+        self.phase = match self.phase {
+            Phase::Command | Phase::Selected => {
+                self.shift |= (bit as u8) << self.bits;
+                self.bits += 1;
+
+                if self.bits < 8 { return }
+
+                let byte = self.shift;
+                self.shift = 0;
+                self.bits = 0;
+
+                match (self.phase, byte) {
+                    (Phase::Selected, byte) => {
+                        if self.logged < LOG_MAX { self.log[self.logged] = byte }
+                        self.logged += 1;
+
+                        match (self.logged == 1) && (byte == self.command) && (self.len > 0) {
+                            true => Phase::Output,
+                            _ => Phase::Selected,
+                        }
+                    },
+
+                    (_, SEARCH_ROM) => Phase::Search(0, 0),
+                    (_, ALARM_SEARCH) if self.alarm => Phase::Search(0, 0),
+                    (_, MATCH_ROM) => Phase::Match(0),
+                    (_, READ_ROM) => Phase::ReadRom(0),
+                    (_, SKIP_ROM) => self.select(),
+                    _ => Phase::Idle,
+                }
+            },
+
+            Phase::Search(n, 2) | Phase::Match(n) if bit != self.rom.bit(n) => Phase::Idle,
+            Phase::Search(63, 2) | Phase::Match(63) => self.select(),
+            Phase::Search(n, 2) => Phase::Search(n + 1, 0),
+            Phase::Match(n) => Phase::Match(n + 1),
+
+            _ => Phase::Idle,
+        };
+    }
+
+    /// Handles a read slot. Returns `false` if the device pulls the bus low.
+    /// Devices receiving bits see it as a write 1 slot.
+    fn read(&mut self) -> bool {
+        match self.phase {
+            Phase::Command | Phase::Match(_) | Phase::Search(_, 2) | Phase::Selected => {
+                self.write(true);
+                true
+            },
+
+            Phase::Search(n, 0) => {
+                self.phase = Phase::Search(n, 1);
+                self.rom.bit(n)
+            },
+
+            Phase::Search(n, 1) => {
+                self.phase = Phase::Search(n, 2);
+                !self.rom.bit(n)
+            },
+
+            Phase::ReadRom(n) => {
+                self.phase = match n {
+                    63 => self.select(),
+                    n => Phase::ReadRom(n + 1),
+                };
+
+                self.rom.bit(n)
+            },
+
+            Phase::Output if self.out < 8 * self.len => {
+                let bit = (self.data[self.out / 8] >> (self.out % 8)) & 1 != 0;
+                self.out += 1;
+                bit
+            },
+
+            _ => true,
+        }
+    }
+
+    /// Selects the device and clears its log.
+    fn select(&mut self) -> Phase {
+        self.logged = 0;
+        self.out = 0;
+        Phase::Selected
+    }
+}
+
+
+
+/// Simulated 1-Wire bus with the given devices.
+pub struct OneWireMock<'a> {
+    /// Devices on the bus.
+    devices: &'a mut [OneWireDevice],
+
+    /// The bus is held low.
+    short: bool,
+}
+
+impl<'a> OneWireMock<'a> {
+    /// Creates a bus with the given devices.
+    pub fn new(devices: &'a mut [OneWireDevice]) -> Self {
+        Self { devices, short: false }
+    }
+
+    /// Holds the bus low (or releases it), e.g. to simulate a short circuit.
+    pub fn short(&mut self, short: bool) {
+        self.short = short;
+    }
+
+    /// Devices on the bus.
+    pub fn devices(&self) -> &[OneWireDevice] {
+        self.devices
+    }
+}
+
+impl<'a> CommunicationError for OneWireMock<'a> {
+    type Error = OneWireError;
+}
+
+impl<'a> OneWireDriver for OneWireMock<'a> {
+    fn reset(&mut self) -> Result<bool, Self::Error> {
+        if self.short { return Err(OneWireError::Short) }
+
+        self.devices.iter_mut().for_each(OneWireDevice::reset);
+
+        Ok(!self.devices.is_empty())
+    }
+
+    fn write_bit(&mut self, bit: bool) -> Result<(), Self::Error> {
+        if self.short { return Err(OneWireError::Short) }
+
+        self.devices.iter_mut().for_each(|device| device.write(bit));
+
+        Ok(())
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Self::Error> {
+        if self.short { return Ok(false) }
+
+        Ok(self.devices.iter_mut().fold(true, |bus, device| device.read() & bus))
+    }
+}
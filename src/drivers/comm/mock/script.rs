@@ -0,0 +1,463 @@
+//! Scripted I2C mock.
+//! Checks the transactions issued by a device driver against a script of
+//! expected transactions. Each expectation describes the address, the bytes
+//! the driver must write, the bytes returned to its reads and optionally an
+//! error to inject. Adjacent operations in the same direction are merged
+//! before comparing, so the driver is free to split its buffers.
+//! Any deviation panics with the expected and actual transactions, and
+//! dropping the mock panics if the script was not fully consumed.
+
+
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{ Context, Poll };
+
+use super::{ MockError, Operations };
+use super::super::{ CommunicationError, I2CAddress7bit, I2CAsyncDriver, I2CDriver, I2COperation };
+
+
+
+/// Expected operation of a scripted transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockOperation<'a> {
+    /// The driver writes these bytes.
+    Write(&'a [u8]),
+
+    /// The driver reads, and receives these bytes.
+    Read(&'a [u8]),
+}
+
+
+
+/// Expected transaction of a script.
+#[derive(Debug, Clone, Copy)]
+pub struct I2CExpectation<'a> {
+    /// Target address.
+    addr: I2CAddress7bit,
+
+    /// Expected operations.
+    operations: Expected<'a>,
+
+    /// Error returned instead of completing the transaction.
+    error: Option<MockError>,
+}
+
+impl<'a> I2CExpectation<'a> {
+    /// Expects a write of the given bytes.
+    pub const fn write(addr: I2CAddress7bit, data: &'a [u8]) -> Self {
+        Self::pair(addr, MockOperation::Write(data), MockOperation::Write(&[]))
+    }
+
+    /// Expects a read, which returns the given bytes.
+    /// An empty response expects a quick read command.
+    pub const fn read(addr: I2CAddress7bit, response: &'a [u8]) -> Self {
+        Self::pair(addr, MockOperation::Read(response), MockOperation::Read(&[]))
+    }
+
+    /// Expects a write of the given bytes followed by a read, which returns the response.
+    pub const fn write_read(addr: I2CAddress7bit, data: &'a [u8], response: &'a [u8]) -> Self {
+        Self::pair(addr, MockOperation::Write(data), MockOperation::Read(response))
+    }
+
+    /// Expects an arbitrary sequence of operations.
+    pub const fn transaction(addr: I2CAddress7bit, operations: &'a [MockOperation<'a>]) -> Self {
+        Self { addr, operations: Expected::Many(operations), error: None }
+    }
+
+    /// Injects an error. The transaction must still match the expectation,
+    /// but the driver receives the error and its read buffers are untouched.
+    pub const fn error(mut self, error: MockError) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    /// Builds an expectation of two operations.
+    const fn pair(addr: I2CAddress7bit, a: MockOperation<'a>, b: MockOperation<'a>) -> Self {
+        Self { addr, operations: Expected::Pair([a, b]), error: None }
+    }
+
+    /// Expected operations.
+    fn operations(&self) -> &[MockOperation<'a>] {
+        match &self.operations {
+            Expected::Pair(pair) => pair,
+            Expected::Many(many) => many,
+        }
+    }
+
+    /// Returns `true` if the transaction issued by the driver matches the expectation.
+    fn matches(&self, addr: I2CAddress7bit, operations: &[I2COperation<'_>]) -> bool {
+        if addr != self.addr { return false }
+
+        let expected = expected(self.operations()).map(|(read, byte)| (read, if read { 0 } else { byte }));
+
+        if !expected.eq(actual(operations)) { return false }
+
+        // Quick commands only carry the direction.
+        match actual(operations).next() {
+            None => quick(self.operations()) == operations.iter().any(|o| matches!(o, I2COperation::Read(_))),
+            _ => true,
+        }
+    }
+
+    /// Fills the read buffers of the driver with the expected response.
+    fn respond(&self, operations: &mut [I2COperation<'_>]) {
+        let mut response = expected(self.operations()).filter(|(read, _)| *read).map(|(_, byte)| byte);
+
+        for operation in operations.iter_mut() {
+            let read: &mut [u8] = match operation {
+                I2COperation::Read(r) | I2COperation::WriteRead(_, r) => r,
+                _ => continue,
+            };
+
+            for byte in read.iter_mut() {
+                *byte = response.next().unwrap_or(0xFF);
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for I2CExpectation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        segments(f, self.addr, expected(self.operations()), quick(self.operations()), true)?;
+
+        match self.error {
+            Some(error) => write!(f, " -> {:?}", error),
+            _ => Ok(()),
+        }
+    }
+}
+
+
+
+/// Scripted I2C mock.
+/// Panics when the driver deviates from the script, and when dropped before
+/// the whole script was consumed.
+pub struct I2CScript<'a> {
+    /// Expected transactions.
+    script: &'a [I2CExpectation<'a>],
+
+    /// Index of the next expected transaction.
+    next: usize,
+
+    /// `true` once a deviation has been reported.
+    failed: bool,
+}
+
+impl<'a> I2CScript<'a> {
+    /// Creates a mock that expects the given transactions in order.
+    pub const fn new(script: &'a [I2CExpectation<'a>]) -> Self {
+        Self { script, next: 0, failed: false }
+    }
+
+    /// Number of transactions not yet issued by the driver.
+    pub fn remaining(&self) -> usize {
+        self.script.len() - self.next
+    }
+
+    /// Checks that the whole script was consumed.
+    pub fn done(&mut self) {
+        if let Some(next) = self.script.get(self.next) {
+            self.failed = true;
+            panic!("I2C script: {} transaction(s) not issued\n  next:     {}", self.remaining(), next);
+        }
+    }
+
+    /// Checks a transaction against the script and answers it.
+    fn execute(&mut self, addr: I2CAddress7bit, operations: &mut [I2COperation<'_>]) -> Result<(), MockError> {
+        let script = self.script;
+        let index = self.next;
+
+        let expected = match script.get(index) {
+            Some(expected) => expected,
+            _ => {
+                self.failed = true;
+                panic!("I2C script: unexpected transaction #{}\n  actual:   {}", index, Actual(addr, operations));
+            },
+        };
+
+        if !expected.matches(addr, operations) {
+            self.failed = true;
+            panic!("I2C script: transaction #{} deviates from the script\n  expected: {}\n  actual:   {}",
+                index, expected, Actual(addr, operations));
+        }
+
+        self.next += 1;
+
+        if let Some(error) = expected.error { return Err(error) }
+
+        expected.respond(operations);
+
+        Ok(())
+    }
+}
+
+impl<'a> Drop for I2CScript<'a> {
+    fn drop(&mut self) {
+        // Do not panic again while unwinding, from a reported deviation or
+        // from a failed assertion of the test.
+        if !self.failed && !std::thread::panicking() { self.done() }
+    }
+}
+
+impl<'a> CommunicationError for I2CScript<'a> {
+    type Error = MockError;
+}
+
+impl<'a> I2CDriver for I2CScript<'a> {
+    fn transaction(&mut self, addr: I2CAddress7bit, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error> {
+        self.execute(addr, operations)
+    }
+}
+
+impl<'s> I2CAsyncDriver for I2CScript<'s> {
+    type WriteFuture<'a> = ScriptTransfer<'a, 's> where Self: 'a;
+    type ReadFuture<'a> = ScriptTransfer<'a, 's> where Self: 'a;
+    type WriteReadFuture<'a> = ScriptTransfer<'a, 's> where Self: 'a;
+    type TransactionFuture<'a> = ScriptTransfer<'a, 's> where Self: 'a;
+
+    fn transaction<'a>(&'a mut self, addr: I2CAddress7bit, operations: &'a mut [I2COperation<'a>]) -> Self::TransactionFuture<'a> {
+        ScriptTransfer { mock: self, addr, operations: Operations::Many(operations) }
+    }
+
+    fn send<'a>(&'a mut self, addr: I2CAddress7bit, data: &'a [u8]) -> Self::WriteFuture<'a> {
+        ScriptTransfer { mock: self, addr, operations: Operations::One([I2COperation::Write(data)]) }
+    }
+
+    fn receive<'a>(&'a mut self, addr: I2CAddress7bit, recv: &'a mut [u8]) -> Self::ReadFuture<'a> {
+        ScriptTransfer { mock: self, addr, operations: Operations::One([I2COperation::Read(recv)]) }
+    }
+
+    fn transfer<'a>(&'a mut self, addr: I2CAddress7bit, send: &'a [u8], recv: &'a mut [u8]) -> Self::WriteReadFuture<'a> {
+        ScriptTransfer { mock: self, addr, operations: Operations::One([I2COperation::WriteRead(send, recv)]) }
+    }
+}
+
+
+
+/// Asynchronous transaction on the scripted mock.
+/// The transaction is checked against the script when first polled, and
+/// completes immediately. A future dropped before being polled issues nothing.
+pub struct ScriptTransfer<'a, 's> {
+    /// Borrowed mock.
+    mock: &'a mut I2CScript<'s>,
+
+    /// Target address.
+    addr: I2CAddress7bit,
+
+    /// Operations of the transaction.
+    operations: Operations<'a>,
+}
+
+impl<'a, 's> Future for ScriptTransfer<'a, 's> {
+    type Output = Result<(), MockError>;
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        Poll::Ready(this.mock.execute(this.addr, this.operations.as_mut()))
+    }
+}
+
+
+
+/// Operations of an expectation.
+#[derive(Debug, Clone, Copy)]
+enum Expected<'a> {
+    /// Two operations, possibly empty.
+    Pair([MockOperation<'a>; 2]),
+
+    /// Borrowed list of operations.
+    Many(&'a [MockOperation<'a>]),
+}
+
+/// Transaction issued by the driver, for display.
+struct Actual<'b, 'c>(I2CAddress7bit, &'b [I2COperation<'c>]);
+
+impl<'b, 'c> fmt::Display for Actual<'b, 'c> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let quick = self.1.iter().any(|o| matches!(o, I2COperation::Read(_)));
+
+        segments(f, self.0, actual(self.1), quick, false)
+    }
+}
+
+
+
+/// Flattens the expected operations into `(read, byte)` pairs.
+fn expected<'b>(operations: &'b [MockOperation<'_>]) -> impl Iterator<Item = (bool, u8)> + 'b {
+    operations.iter().flat_map(|operation| {
+        let (write, read): (&[u8], &[u8]) = match operation {
+            MockOperation::Write(w) => (w, &[]),
+            MockOperation::Read(r) => (&[], r),
+        };
+
+        write.iter().map(|b| (false, *b)).chain(read.iter().map(|b| (true, *b)))
+    })
+}
+
+/// Flattens the operations of the driver into `(read, byte)` pairs.
+/// Read bytes are not known yet and are reported as 0.
+fn actual<'b>(operations: &'b [I2COperation<'_>]) -> impl Iterator<Item = (bool, u8)> + 'b {
+    operations.iter().flat_map(|operation| {
+        let (write, read): (&[u8], &[u8]) = match operation {
+            I2COperation::Write(w) => (w, &[]),
+            I2COperation::Read(r) => (&[], r),
+            I2COperation::WriteRead(w, r) => (w, r),
+        };
+
+        write.iter().map(|b| (false, *b)).chain(read.iter().map(|_| (true, 0)))
+    })
+}
+
+/// Direction of an expected quick command.
+fn quick(operations: &[MockOperation<'_>]) -> bool {
+    matches!(operations.first(), Some(MockOperation::Read(_)))
+}
+
+/// Formats a transaction as its merged segments, e.g. `0x50 W[00 10] R[AA BB]`.
+/// Reads show their data if `values` is set, or their length otherwise.
+fn segments(f: &mut fmt::Formatter, addr: I2CAddress7bit, stream: impl Iterator<Item = (bool, u8)>, quick: bool, values: bool) -> fmt::Result {
+    write!(f, "0x{:02X}", addr.0)?;
+
+    let mut direction = None;
+    let mut count = 0;
+
+    for (read, byte) in stream {
+        if direction != Some(read) {
+            close(f, direction, count, values)?;
+            f.write_str(match (read, values) { (false, _) => " W[", (true, true) => " R[", _ => " R" })?;
+
+            direction = Some(read);
+            count = 0;
+        }
+
+        if !read || values {
+            if count > 0 { f.write_str(" ")? }
+            write!(f, "{:02X}", byte)?;
+        }
+
+        count += 1;
+    }
+
+    match direction {
+        None => f.write_str(if quick { " R (quick)" } else { " W (quick)" }),
+        _ => close(f, direction, count, values),
+    }
+}
+
+/// Closes a segment.
+fn close(f: &mut fmt::Formatter, direction: Option<bool>, count: usize, values: bool) -> fmt::Result {
+    match direction {
+        Some(true) if !values => write!(f, "({})", count),
+        Some(_) => f.write_str("]"),
+        _ => Ok(()),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::block_on;
+
+    const TARGET: I2CAddress7bit = I2CAddress7bit::new(0x50);
+
+    #[test]
+    fn matching_script_is_answered() {
+        let script = [
+            I2CExpectation::write(TARGET, &[0x10, 0x20]),
+            I2CExpectation::write_read(TARGET, &[0x01], &[0xAA, 0xBB]),
+        ];
+        let mut mock = I2CScript::new(&script);
+
+        assert_eq!(I2CDriver::send(&mut mock, TARGET, &[0x10, 0x20]), Ok(()));
+
+        let mut recv = [0; 2];
+        assert_eq!(block_on(I2CAsyncDriver::transfer(&mut mock, TARGET, &[0x01], &mut recv)), Ok(()));
+        assert_eq!(recv, [0xAA, 0xBB]);
+        assert_eq!(mock.remaining(), 0);
+    }
+
+    #[test]
+    fn split_buffers_are_merged() {
+        let script = [I2CExpectation::write_read(TARGET, &[0x01, 0x02], &[0xAA, 0xBB, 0xCC])];
+        let mut mock = I2CScript::new(&script);
+
+        let (mut a, mut b) = ([0; 1], [0; 2]);
+        let mut operations = [
+            I2COperation::Write(&[0x01]),
+            I2COperation::WriteRead(&[0x02], &mut a),
+            I2COperation::Read(&mut b),
+        ];
+
+        assert_eq!(I2CDriver::transaction(&mut mock, TARGET, &mut operations), Ok(()));
+        assert_eq!((a, b), ([0xAA], [0xBB, 0xCC]));
+    }
+
+    #[test]
+    fn injected_error_is_returned() {
+        let script = [I2CExpectation::read(TARGET, &[0xAA]).error(MockError::AddressNack)];
+        let mut mock = I2CScript::new(&script);
+
+        let mut recv = [0x55];
+        assert_eq!(I2CDriver::receive(&mut mock, TARGET, &mut recv), Err(MockError::AddressNack));
+        assert_eq!(recv, [0x55]);
+    }
+
+    #[test]
+    #[should_panic(expected = "transaction #1 deviates from the script")]
+    fn deviation_is_reported() {
+        let script = [
+            I2CExpectation::write(TARGET, &[0x10]),
+            I2CExpectation::write(TARGET, &[0x20]),
+        ];
+        let mut mock = I2CScript::new(&script);
+
+        let _ = I2CDriver::send(&mut mock, TARGET, &[0x10]);
+        let _ = I2CDriver::send(&mut mock, TARGET, &[0x21]);
+    }
+
+    #[test]
+    #[should_panic(expected = "deviates from the script")]
+    fn wrong_address_is_reported() {
+        let script = [I2CExpectation::write(TARGET, &[0x10])];
+        let mut mock = I2CScript::new(&script);
+
+        let _ = I2CDriver::send(&mut mock, I2CAddress7bit::new(0x51), &[0x10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected transaction #1")]
+    fn extra_transaction_is_reported() {
+        let script = [I2CExpectation::write(TARGET, &[0x10])];
+        let mut mock = I2CScript::new(&script);
+
+        let _ = I2CDriver::send(&mut mock, TARGET, &[0x10]);
+        let _ = I2CDriver::send(&mut mock, TARGET, &[0x10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 transaction(s) not issued")]
+    fn unconsumed_script_is_reported_on_drop() {
+        let script = [
+            I2CExpectation::write(TARGET, &[0x10]),
+            I2CExpectation::write(TARGET, &[0x20]),
+        ];
+        let mut mock = I2CScript::new(&script);
+
+        let _ = I2CDriver::send(&mut mock, TARGET, &[0x10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "failed assertion")]
+    fn failed_assertion_does_not_panic_again_on_drop() {
+        let script = [I2CExpectation::write(TARGET, &[0x10])];
+        let _mock = I2CScript::new(&script);
+
+        // Panicking again while unwinding would abort the test binary.
+        panic!("failed assertion");
+    }
+}
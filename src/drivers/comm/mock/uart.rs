@@ -0,0 +1,204 @@
+//! Host-side UART mock.
+//! Two ends connected back to back through a pair of rings, so that a driver
+//! and its peer (e.g. a protocol client and server) can be run against each
+//! other on the host. Each `send` queues its words at once, and the line is
+//! considered idle whenever no word is queued, so a frame must be sent in a
+//! single call and fit in the ring. Breaks are queued in order with the words
+//! and reported as `UartError::Break` by the receiving end.
+
+
+
+use core::future::{ poll_fn, Future };
+use core::task::Poll;
+
+use crate::buffer::RingBuffer;
+
+use super::super::{
+    CommunicationError, UartAsyncBreakDriver, UartAsyncFrameDriver, UartAsyncRxDriver,
+    UartAsyncTxDriver, UartConfig, UartDriver, UartError,
+};
+
+
+
+/// Line symbol of a break condition. Bytes are queued as their value.
+const BREAK: u16 = 0x100;
+
+
+
+/// Pair of connected UART lines with `N` bytes of buffering in each direction.
+pub struct UartLink<const N: usize> {
+    /// Line from the first end to the second one.
+    forward: RingBuffer<u16, N>,
+
+    /// Line from the second end to the first one.
+    backward: RingBuffer<u16, N>,
+}
+
+impl<const N: usize> UartLink<N> {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self { forward: RingBuffer::new(), backward: RingBuffer::new() }
+    }
+
+    /// Returns both ends of the link.
+    pub fn split(&mut self) -> (UartMock<'_, N>, UartMock<'_, N>) {
+        let a = UartMock { tx: &self.forward, rx: &self.backward, config: UartConfig::default() };
+        let b = UartMock { tx: &self.backward, rx: &self.forward, config: UartConfig::default() };
+
+        (a, b)
+    }
+}
+
+impl<const N: usize> Default for UartLink<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+/// One end of a `UartLink`.
+pub struct UartMock<'a, const N: usize> {
+    /// Transmission line.
+    tx: &'a RingBuffer<u16, N>,
+
+    /// Reception line.
+    rx: &'a RingBuffer<u16, N>,
+
+    /// Last applied configuration.
+    config: UartConfig,
+}
+
+impl<'a, const N: usize> UartMock<'a, N> {
+    /// Last applied configuration.
+    pub fn config(&self) -> &UartConfig {
+        &self.config
+    }
+
+    /// Number of bytes (and breaks) waiting to be received by this end.
+    pub fn pending(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Injects bytes as if received from the line, e.g. to simulate noise.
+    /// Returns the number of bytes that fit in the ring.
+    pub fn inject(&self, data: &[u8]) -> usize {
+        push(self.rx, data)
+    }
+}
+
+impl<'a, const N: usize> CommunicationError for UartMock<'a, N> {
+    type Error = UartError;
+}
+
+impl<'a, const N: usize> UartDriver for UartMock<'a, N> {
+    fn configure(&mut self, config: &UartConfig) -> Result<(), Self::Error> {
+        self.config = *config;
+        Ok(())
+    }
+}
+
+impl<'a, const N: usize> UartAsyncTxDriver for UartMock<'a, N> {
+    type WriteFuture<'b> = impl Future<Output = Result<(), Self::Error>> + 'b where Self: 'b;
+    type FlushFuture<'b> = impl Future<Output = Result<(), Self::Error>> + 'b where Self: 'b;
+
+    fn send<'b>(&'b mut self, data: &'b [u8]) -> Self::WriteFuture<'b> {
+        let mut data = data;
+
+        poll_fn(move |cx| {
+            data = &data[push(self.tx, data)..];
+
+            if data.is_empty() { return Poll::Ready(Ok(())) }
+
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+
+    fn flush<'b>(&'b mut self) -> Self::FlushFuture<'b> {
+        async { Ok(()) }
+    }
+}
+
+impl<'a, const N: usize> UartAsyncRxDriver for UartMock<'a, N> {
+    type ReadFuture<'b> = impl Future<Output = Result<(), Self::Error>> + 'b where Self: 'b;
+
+    fn recv<'b>(&'b mut self, data: &'b mut [u8]) -> Self::ReadFuture<'b> {
+        let mut n = 0;
+
+        poll_fn(move |cx| {
+            while n < data.len() {
+                match self.rx.pop() {
+                    Some(BREAK) => return Poll::Ready(Err(UartError::Break)),
+                    Some(symbol) => data[n] = symbol as u8,
+                    _ => break,
+                }
+
+                n += 1;
+            }
+
+            if n == data.len() { return Poll::Ready(Ok(())) }
+
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+}
+
+impl<'a, const N: usize> UartAsyncBreakDriver for UartMock<'a, N> {
+    type BreakFuture<'b> = impl Future<Output = Result<(), Self::Error>> + 'b where Self: 'b;
+
+    fn send_break<'b>(&'b mut self) -> Self::BreakFuture<'b> {
+        poll_fn(move |cx| {
+            if self.tx.push(BREAK).is_ok() { return Poll::Ready(Ok(())) }
+
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+}
+
+impl<'a, const N: usize> UartAsyncFrameDriver for UartMock<'a, N> {
+    type ReadUntilFuture<'b> = impl Future<Output = Result<usize, Self::Error>> + 'b where Self: 'b;
+
+    fn read_until_idle<'b>(&'b mut self, data: &'b mut [u8]) -> Self::ReadUntilFuture<'b> {
+        until(self.rx, data, None)
+    }
+
+    fn read_until<'b>(&'b mut self, delimiter: u8, data: &'b mut [u8]) -> Self::ReadUntilFuture<'b> {
+        until(self.rx, data, Some(delimiter))
+    }
+}
+
+
+
+/// Receives bytes until the buffer is full, the delimiter is received or
+/// (without delimiter) no more bytes are queued.
+fn until<'a, const N: usize>(rx: &'a RingBuffer<u16, N>, data: &'a mut [u8], delimiter: Option<u8>) -> impl Future<Output = Result<usize, UartError>> + 'a {
+    let mut n = 0;
+
+    poll_fn(move |cx| {
+        while n < data.len() {
+            let byte = match rx.pop() {
+                Some(BREAK) => return Poll::Ready(Err(UartError::Break)),
+                Some(symbol) => symbol as u8,
+                _ => break,
+            };
+
+            data[n] = byte;
+            n += 1;
+
+            if delimiter == Some(byte) { return Poll::Ready(Ok(n)) }
+        }
+
+        if (n == data.len()) || ((n > 0) && delimiter.is_none()) { return Poll::Ready(Ok(n)) }
+
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+}
+
+/// Queues bytes on a line. Returns the number of bytes that fit in the ring.
+fn push<const N: usize>(line: &RingBuffer<u16, N>, data: &[u8]) -> usize {
+    data.iter().take_while(|byte| line.push(**byte as u16).is_ok()).count()
+}
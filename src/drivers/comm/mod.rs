@@ -2,22 +2,107 @@
 
 
 
+mod can;
 mod i2c;
-//mod uart;
+mod spi;
+mod uart;
 
+pub mod bitbang;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod onewire;
+pub mod shared;
 
 
-//pub use self::uart::UartDriver;
-pub use i2c::{I2CDriver, I2CAsyncDriver};
+
+pub use can::{
+    CanDriver, CanTxDriver, CanRxDriver, CanAsyncTxDriver, CanAsyncRxDriver,
+    CanConfig, CanError, CanFilter, CanFrame, CanId, CanMode, CanState,
+    CAN_MAX_LEN, CANFD_MAX_LEN, dlc_to_len, len_to_dlc, padded_len,
+};
+pub use i2c::{
+    I2CDriver, I2CAsyncDriver, I2COperation,
+    I2CAddress, I2CAddress7bit, I2CAddress10bit,
+    I2CTargetConfig, I2CTargetDriver, I2CTargetEvent, I2CTargetHandler, I2CTargetResponse,
+};
+pub use can::isotp;
+pub use i2c::{ pmbus, scan, smbus };
+pub use spi::{
+    SPIDriver, SPIAsyncDriver, SPIOperation,
+    SPIConfig, SPIMode, BitOrder, ChipSelect,
+};
+pub use uart::{
+    UartDriver, UartTxDriver, UartRxDriver, UartDuplexDriver, UartIrqDriver,
+    UartAsyncTxDriver, UartAsyncRxDriver, UartAsyncDuplexDriver, UartAsyncFrameDriver, UartAsyncBreakDriver,
+    UartConfig, UartError, DataBits, FlowControl, Parity, StopBits,
+    BufferedUart, IdleUart, UartStatus,
+};
+pub use uart::{ lin, modbus };
 
 
 
 /// Trait that must be implemented for all communication interfaces to define
 /// their custom error types.
 pub trait CommunicationError {
-    type Error;
+    type Error: DriverError;
 }
 
 impl<T: CommunicationError> CommunicationError for &mut T {
     type Error = T::Error;
 }
+
+
+
+/// Common classification of driver errors.
+/// Allows generic code (retries, bus recovery, protocol layers) to react to
+/// an error without knowing the driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The target did not acknowledge its address.
+    AddressNack,
+
+    /// The target did not acknowledge a data byte.
+    DataNack,
+
+    /// Another controller took the bus.
+    ArbitrationLost,
+
+    /// Misplaced START / STOP condition or another electrical bus error.
+    Bus,
+
+    /// Data was received before the previous data was read.
+    Overrun,
+
+    /// A character or frame was received with an invalid format.
+    Framing,
+
+    /// A character was received with the wrong parity.
+    Parity,
+
+    /// The operation did not complete in time.
+    Timeout,
+
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    /// Returns `true` if the error is a NACK from the target.
+    pub const fn is_nack(&self) -> bool {
+        matches!(self, ErrorKind::AddressNack | ErrorKind::DataNack)
+    }
+}
+
+
+
+/// Common trait for the errors of the communication drivers.
+pub trait DriverError: core::fmt::Debug {
+    /// Classifies the error.
+    fn kind(&self) -> ErrorKind;
+}
+
+impl DriverError for core::convert::Infallible {
+    fn kind(&self) -> ErrorKind {
+        match *self {}
+    }
+}
@@ -0,0 +1,344 @@
+//! 1-Wire master: ROM layer.
+
+
+
+use super::{
+    crc8,
+    OneWireDriver, OneWireError, RomCode,
+    ALARM_SEARCH, MATCH_ROM, READ_ROM, SEARCH_ROM, SKIP_ROM,
+};
+
+
+
+/// State of a ROM search.
+/// Each call to `OneWire::search` finds the next device, in ascending order
+/// of ROM code bits (LSB first), until all devices have been found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomSearch {
+    /// Search command (Search ROM or Alarm Search).
+    command: u8,
+
+    /// Position (1 to 64) of the last branch where 0 was taken. 0 if none.
+    discrepancy: u8,
+
+    /// All devices have been found.
+    done: bool,
+
+    /// ROM code of the last device found.
+    rom: RomCode,
+}
+
+impl RomSearch {
+    /// Search of all the devices.
+    pub const fn new() -> Self {
+        Self { command: SEARCH_ROM, discrepancy: 0, done: false, rom: RomCode([0; 8]) }
+    }
+
+    /// Search of the devices with an alarm condition.
+    pub const fn alarm() -> Self {
+        Self { command: ALARM_SEARCH, discrepancy: 0, done: false, rom: RomCode([0; 8]) }
+    }
+
+    /// Restarts the search from the first device.
+    pub fn restart(&mut self) {
+        self.discrepancy = 0;
+        self.done = false;
+    }
+
+    /// Returns `true` once all devices have been found.
+    pub const fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+impl Default for RomSearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+/// 1-Wire master.
+/// Each transaction starts with a ROM command (`skip_rom`, `match_rom`,
+/// `read_rom`) that resets the bus and selects the devices, followed by the
+/// function commands of the device (`write` / `read`).
+pub struct OneWire<B> {
+    /// Wrapped bus driver.
+    bus: B,
+}
+
+impl<B: OneWireDriver> OneWire<B> {
+    /// Creates a master on the given bus.
+    pub const fn new(bus: B) -> Self {
+        Self { bus }
+    }
+
+    /// Consumes the master and returns the bus driver.
+    pub fn release(self) -> B {
+        self.bus
+    }
+
+    /// Resets the bus. Fails if no device is present.
+    pub fn reset(&mut self) -> Result<(), OneWireError> {
+        match self.bus.reset()? {
+            true => Ok(()),
+            _ => Err(OneWireError::NoPresence),
+        }
+    }
+
+    /// Resets the bus and selects all the devices.
+    pub fn skip_rom(&mut self) -> Result<(), OneWireError> {
+        self.reset()?;
+        self.bus.write_byte(SKIP_ROM)
+    }
+
+    /// Resets the bus and selects the device with the given ROM code.
+    pub fn match_rom(&mut self, rom: &RomCode) -> Result<(), OneWireError> {
+        self.reset()?;
+        self.bus.write_byte(MATCH_ROM)?;
+        self.write(&rom.0)
+    }
+
+    /// Resets the bus and reads the ROM code of the only device on the bus,
+    /// which is then selected.
+    pub fn read_rom(&mut self) -> Result<RomCode, OneWireError> {
+        self.reset()?;
+        self.bus.write_byte(READ_ROM)?;
+
+        let mut rom = RomCode::default();
+        self.read(&mut rom.0)?;
+
+        match rom.is_valid() {
+            true => Ok(rom),
+            _ => Err(OneWireError::Crc),
+        }
+    }
+
+    /// Writes bytes to the selected devices.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), OneWireError> {
+        data.iter().try_for_each(|byte| self.bus.write_byte(*byte))
+    }
+
+    /// Reads bytes from the selected devices.
+    pub fn read(&mut self, data: &mut [u8]) -> Result<(), OneWireError> {
+        for byte in data.iter_mut() {
+            *byte = self.bus.read_byte()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads bytes ending with their CRC-8 (e.g. a scratchpad) and checks it.
+    pub fn read_checked(&mut self, data: &mut [u8]) -> Result<(), OneWireError> {
+        self.read(data)?;
+
+        match crc8(data) {
+            0 => Ok(()),
+            _ => Err(OneWireError::Crc),
+        }
+    }
+
+    /// Finds the next device of the search. Returns `None` once all devices
+    /// have been found, or if no device is present.
+    /// The device found is left selected.
+    pub fn search(&mut self, search: &mut RomSearch) -> Result<Option<RomCode>, OneWireError> {
+        if search.done { return Ok(None) }
+
+        if !self.bus.reset()? {
+            search.done = true;
+            return Ok(None);
+        }
+
+        self.bus.write_byte(search.command)?;
+
+        let mut zero = 0;
+
+        for n in 1..=64u8 {
+            let position = (n - 1) as usize;
+
+            // Take the same branch as the last device before its last
+            // discrepancy, and the 1 branch at it.
+            let direction = match n.cmp(&search.discrepancy) {
+                core::cmp::Ordering::Less => search.rom.bit(position),
+                core::cmp::Ordering::Equal => true,
+                _ => false,
+            };
+
+            let (bit, complement, direction) = self.bus.triplet(direction)?;
+
+            match (bit, complement) {
+                (true, true) if n == 1 => {
+                    search.done = true;
+                    return Ok(None);
+                },
+
+                (true, true) => return Err(OneWireError::Search),
+
+                (false, false) if !direction => zero = n,
+
+                _ => (),
+            }
+
+            search.rom.set_bit(position, direction);
+        }
+
+        search.discrepancy = zero;
+        search.done = zero == 0;
+
+        match search.rom.is_valid() {
+            true => Ok(Some(search.rom)),
+            _ => Err(OneWireError::Crc),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::super::mock::{ OneWireDevice, OneWireMock };
+
+    /// Read Scratchpad command of the DS18B20.
+    const READ_SCRATCHPAD: u8 = 0xBE;
+
+    /// Scratchpad of a DS18B20 at 85 °C, with its CRC.
+    fn scratchpad() -> [u8; 9] {
+        let mut pad = [0x50, 0x05, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10, 0];
+        pad[8] = crc8(&pad[..8]);
+        pad
+    }
+
+    /// Devices of the bus, in search order: bits are compared LSB first and
+    /// the 0 branch is taken first.
+    fn devices() -> [OneWireDevice; 4] {
+        [
+            OneWireDevice::new(RomCode::new(0x10, [9, 0, 0, 0, 0, 0])),
+            OneWireDevice::new(RomCode::new(0x28, [2, 0, 0, 0, 0, 0])).alarm(true),
+            OneWireDevice::new(RomCode::new(0x28, [1, 0, 0, 0, 0, 0])),
+            OneWireDevice::new(RomCode::new(0x28, [3, 0, 0, 0, 0, 0])).alarm(true).data(READ_SCRATCHPAD, &scratchpad()),
+        ]
+    }
+
+    /// Runs a search to its end.
+    fn search_all<B: OneWireDriver>(master: &mut OneWire<B>, mut search: RomSearch) -> Result<Vec<RomCode>, OneWireError> {
+        let mut found = Vec::new();
+
+        while let Some(rom) = master.search(&mut search)? {
+            found.push(rom);
+        }
+
+        assert!(search.is_done());
+        Ok(found)
+    }
+
+    #[test]
+    fn search_finds_all_devices_in_order() {
+        let mut devices = devices();
+        let roms: Vec<RomCode> = devices.iter().map(|device| *device.rom()).collect();
+
+        let mut master = OneWire::new(OneWireMock::new(&mut devices));
+
+        assert_eq!(search_all(&mut master, RomSearch::new()), Ok(roms.clone()));
+
+        // Restarting the search finds them again.
+        let mut search = RomSearch::new();
+        assert_eq!(master.search(&mut search), Ok(Some(roms[0])));
+        search.restart();
+        assert_eq!(master.search(&mut search), Ok(Some(roms[0])));
+    }
+
+    #[test]
+    fn alarm_search() {
+        let mut devices = devices();
+        let alarmed = [*devices[1].rom(), *devices[3].rom()];
+
+        let mut master = OneWire::new(OneWireMock::new(&mut devices));
+        assert_eq!(search_all(&mut master, RomSearch::alarm()), Ok(alarmed.to_vec()));
+
+        let mut devices = [OneWireDevice::new(RomCode::new(0x28, [1, 0, 0, 0, 0, 0]))];
+        let mut master = OneWire::new(OneWireMock::new(&mut devices));
+        let mut search = RomSearch::alarm();
+
+        assert_eq!(master.search(&mut search), Ok(None));
+        assert!(search.is_done());
+    }
+
+    #[test]
+    fn match_rom_selects_one_device() {
+        let mut devices = devices();
+        let rom = *devices[2].rom();
+
+        let mut master = OneWire::new(OneWireMock::new(&mut devices));
+        master.match_rom(&rom).unwrap();
+        master.write(&[0x4E, 0x01, 0x02]).unwrap();
+
+        let bus = master.release();
+
+        for device in bus.devices() {
+            assert_eq!(device.is_selected(), *device.rom() == rom);
+            assert_eq!(device.written(), if *device.rom() == rom { &[0x4E, 0x01, 0x02][..] } else { &[] });
+        }
+    }
+
+    #[test]
+    fn read_checked_verifies_the_crc() {
+        let mut good = scratchpad();
+        let mut bad = scratchpad();
+        bad[0] ^= 0x01;
+
+        let mut devices = [
+            OneWireDevice::new(RomCode::new(0x28, [1, 0, 0, 0, 0, 0])).data(READ_SCRATCHPAD, &good),
+            OneWireDevice::new(RomCode::new(0x28, [2, 0, 0, 0, 0, 0])).data(READ_SCRATCHPAD, &bad),
+        ];
+
+        let (first, second) = (*devices[0].rom(), *devices[1].rom());
+        let mut master = OneWire::new(OneWireMock::new(&mut devices));
+
+        master.match_rom(&first).unwrap();
+        master.write(&[READ_SCRATCHPAD]).unwrap();
+        assert_eq!(master.read_checked(&mut good), Ok(()));
+        assert_eq!(good, scratchpad());
+
+        master.match_rom(&second).unwrap();
+        master.write(&[READ_SCRATCHPAD]).unwrap();
+        assert_eq!(master.read_checked(&mut [0; 9]), Err(OneWireError::Crc));
+
+        // Several devices answering Read ROM collide.
+        assert_eq!(master.read_rom(), Err(OneWireError::Crc));
+    }
+
+    #[test]
+    fn read_rom_of_a_single_device() {
+        let rom = RomCode::new(0x10, [1, 2, 3, 4, 5, 6]);
+        let mut devices = [OneWireDevice::new(rom)];
+
+        let mut master = OneWire::new(OneWireMock::new(&mut devices));
+
+        assert_eq!(master.read_rom(), Ok(rom));
+        assert!(master.release().devices()[0].is_selected());
+    }
+
+    #[test]
+    fn bus_errors() {
+        let mut devices = devices();
+        let mut bus = OneWireMock::new(&mut devices);
+        bus.short(true);
+
+        let mut master = OneWire::new(bus);
+
+        assert_eq!(master.reset(), Err(OneWireError::Short));
+        assert_eq!(master.skip_rom(), Err(OneWireError::Short));
+        assert_eq!(master.search(&mut RomSearch::new()), Err(OneWireError::Short));
+
+        let mut devices = [];
+        let mut master = OneWire::new(OneWireMock::new(&mut devices));
+
+        assert_eq!(master.reset(), Err(OneWireError::NoPresence));
+        assert_eq!(master.match_rom(&RomCode::default()), Err(OneWireError::NoPresence));
+        assert_eq!(search_all(&mut master, RomSearch::new()), Ok(Vec::new()));
+    }
+}
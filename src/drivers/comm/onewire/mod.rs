@@ -0,0 +1,213 @@
+//! 1-Wire bus.
+//! Drivers implement the time slots of the bus (`OneWireDriver`), on a GPIO
+//! pin (`bitbang::BitBangOneWire`) or on a UART in half duplex mode
+//! (`UartOneWire`). The `OneWire` master implements the ROM layer on top of
+//! them: device selection, ROM search and CRC checks.
+
+
+
+mod master;
+mod uart;
+
+
+
+use crate::crc::{ Crc, CRC_8_MAXIM_DOW };
+
+use super::{ CommunicationError, DriverError, ErrorKind, UartError };
+
+
+
+pub use self::master::{ OneWire, RomSearch };
+pub use self::uart::UartOneWire;
+
+
+
+/// Read ROM command. Only valid with a single device on the bus.
+pub const READ_ROM: u8 = 0x33;
+
+/// Match ROM command. Selects the device with the ROM code that follows.
+pub const MATCH_ROM: u8 = 0x55;
+
+/// Skip ROM command. Selects all the devices.
+pub const SKIP_ROM: u8 = 0xCC;
+
+/// Search ROM command.
+pub const SEARCH_ROM: u8 = 0xF0;
+
+/// Alarm Search command. Only devices with an alarm condition take part.
+pub const ALARM_SEARCH: u8 = 0xEC;
+
+
+
+/// Errors reported on a 1-Wire bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OneWireError {
+    /// No device answered the reset with a presence pulse.
+    NoPresence,
+
+    /// The line is held low.
+    Short,
+
+    /// The received CRC does not match the data.
+    Crc,
+
+    /// The devices stopped answering during a ROM search.
+    Search,
+
+    /// The UART driver reported an error.
+    Uart(UartError),
+}
+
+impl DriverError for OneWireError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            OneWireError::NoPresence => ErrorKind::AddressNack,
+            OneWireError::Short | OneWireError::Search => ErrorKind::Bus,
+            OneWireError::Crc => ErrorKind::Framing,
+            OneWireError::Uart(e) => e.kind(),
+        }
+    }
+}
+
+impl From<UartError> for OneWireError {
+    fn from(e: UartError) -> Self {
+        OneWireError::Uart(e)
+    }
+}
+
+
+
+/// 1-Wire Driver. Generates the time slots of the bus.
+/// Bytes are transferred LSB first.
+pub trait OneWireDriver: CommunicationError<Error = OneWireError> {
+    /// Sends a reset pulse. Returns `true` if a device answered with a presence pulse.
+    fn reset(&mut self) -> Result<bool, Self::Error>;
+
+    /// Sends a write time slot.
+    fn write_bit(&mut self, bit: bool) -> Result<(), Self::Error>;
+
+    /// Sends a read time slot and returns the sampled bit.
+    fn read_bit(&mut self) -> Result<bool, Self::Error>;
+
+    /// Writes a byte.
+    fn write_byte(&mut self, byte: u8) -> Result<(), Self::Error> {
+        for i in 0..8 {
+            self.write_bit((byte >> i) & 1 != 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a byte.
+    fn read_byte(&mut self) -> Result<u8, Self::Error> {
+        let mut byte = 0;
+
+        for i in 0..8 {
+            if self.read_bit()? { byte |= 1 << i }
+        }
+
+        Ok(byte)
+    }
+
+    /// Search step: reads a bit and its complement, then writes the bit if
+    /// they differ, or `direction` if both are 0.
+    /// Returns the two bits read and the bit written.
+    fn triplet(&mut self, direction: bool) -> Result<(bool, bool, bool), Self::Error> {
+        let bit = self.read_bit()?;
+        let complement = self.read_bit()?;
+
+        let direction = match bit != complement {
+            true => bit,
+            _ => direction,
+        };
+
+        self.write_bit(direction)?;
+
+        Ok((bit, complement, direction))
+    }
+}
+
+impl<D: OneWireDriver> OneWireDriver for &mut D {
+    fn reset(&mut self) -> Result<bool, Self::Error> {
+        D::reset(self)
+    }
+
+    fn write_bit(&mut self, bit: bool) -> Result<(), Self::Error> {
+        D::write_bit(self, bit)
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Self::Error> {
+        D::read_bit(self)
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), Self::Error> {
+        D::write_byte(self, byte)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Self::Error> {
+        D::read_byte(self)
+    }
+
+    fn triplet(&mut self, direction: bool) -> Result<(bool, bool, bool), Self::Error> {
+        D::triplet(self, direction)
+    }
+}
+
+
+
+/// 64-bit ROM code of a device: family code, 48-bit serial number and CRC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct RomCode(pub [u8; 8]);
+
+impl RomCode {
+    /// Creates the ROM code of a family and serial number, with its CRC.
+    pub fn new(family: u8, serial: [u8; 6]) -> Self {
+        let mut rom = [family, serial[0], serial[1], serial[2], serial[3], serial[4], serial[5], 0];
+        rom[7] = crc8(&rom[..7]);
+
+        Self(rom)
+    }
+
+    /// Family code.
+    pub const fn family(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Serial number.
+    pub const fn serial(&self) -> [u8; 6] {
+        [self.0[1], self.0[2], self.0[3], self.0[4], self.0[5], self.0[6]]
+    }
+
+    /// CRC byte.
+    pub const fn crc(&self) -> u8 {
+        self.0[7]
+    }
+
+    /// Returns `true` if the CRC matches.
+    pub fn is_valid(&self) -> bool {
+        crc8(&self.0) == 0
+    }
+
+    /// Value of the bit transmitted at the given position (0 to 63).
+    pub const fn bit(&self, n: usize) -> bool {
+        (self.0[n / 8] >> (n % 8)) & 1 != 0
+    }
+
+    /// Sets the bit transmitted at the given position (0 to 63).
+    pub fn set_bit(&mut self, n: usize, bit: bool) {
+        match bit {
+            true => self.0[n / 8] |= 1 << (n % 8),
+            _ => self.0[n / 8] &= !(1 << (n % 8)),
+        }
+    }
+}
+
+
+
+/// CRC of ROM codes and scratchpads.
+static CRC: Crc<u8> = Crc::<u8>::new(&CRC_8_MAXIM_DOW);
+
+/// Dallas / Maxim CRC-8 of the data. Data followed by its CRC yields 0.
+pub fn crc8(data: &[u8]) -> u8 {
+    CRC.checksum(data)
+}
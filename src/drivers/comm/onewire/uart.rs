@@ -0,0 +1,230 @@
+//! 1-Wire on a UART in half duplex mode.
+//! TX and RX are connected to the bus (open drain), so that every character
+//! sent is also received. A reset is a 0xF0 character at 9600 baud, which
+//! devices answer by pulling the upper bits low. A time slot is a character
+//! at 115200 baud: 0xFF writes a 1 (and reads the bus), 0x00 writes a 0.
+
+
+
+use super::super::{ CommunicationError, UartConfig, UartDuplexDriver, UartError };
+use super::{ OneWireDriver, OneWireError };
+
+
+
+/// Baud rate of the reset pulse.
+const RESET_BAUD: u32 = 9600;
+
+/// Baud rate of the time slots.
+const SLOT_BAUD: u32 = 115200;
+
+/// Character of the reset pulse.
+const RESET: u8 = 0xF0;
+
+/// Character of a write 1 or read slot.
+const ONE: u8 = 0xFF;
+
+
+
+/// 1-Wire driver on a half duplex UART.
+pub struct UartOneWire<U> {
+    /// Wrapped UART driver.
+    uart: U,
+}
+
+impl<U: UartDuplexDriver> UartOneWire<U> {
+    /// Creates the driver. The UART is configured at each reset.
+    pub const fn new(uart: U) -> Self {
+        Self { uart }
+    }
+
+    /// Consumes the driver and returns the UART driver.
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    /// Sends the characters of a sequence of time slots and replaces them
+    /// with the characters read back.
+    fn slots(&mut self, slots: &mut [u8]) -> Result<(), OneWireError> {
+        let mut send = [0; 8];
+        send[..slots.len()].copy_from_slice(slots);
+
+        Ok(self.uart.send_recv(&send[..slots.len()], slots)?)
+    }
+}
+
+impl<U> CommunicationError for UartOneWire<U> {
+    type Error = OneWireError;
+}
+
+impl<U: UartDuplexDriver> OneWireDriver for UartOneWire<U> {
+    fn reset(&mut self) -> Result<bool, Self::Error> {
+        self.uart.configure(&UartConfig::new(RESET_BAUD))?;
+
+        let mut echo = [RESET];
+        let result = self.slots(&mut echo);
+
+        self.uart.configure(&UartConfig::new(SLOT_BAUD))?;
+
+        // A bus held low reads as a break or a missing stop bit.
+        match (result, echo[0]) {
+            (Ok(()), 0x00) | (Err(OneWireError::Uart(UartError::Framing | UartError::Break)), _) => Err(OneWireError::Short),
+            (Ok(()), echo) => Ok(echo != RESET),
+            (Err(e), _) => Err(e),
+        }
+    }
+
+    fn write_bit(&mut self, bit: bool) -> Result<(), Self::Error> {
+        let mut slot = [if bit { ONE } else { 0x00 }];
+        self.slots(&mut slot)
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Self::Error> {
+        let mut slot = [ONE];
+        self.slots(&mut slot)?;
+
+        Ok(slot[0] == ONE)
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), Self::Error> {
+        let mut slots = [0; 8];
+
+        for (i, slot) in slots.iter_mut().enumerate() {
+            if (byte >> i) & 1 != 0 { *slot = ONE }
+        }
+
+        self.slots(&mut slots)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Self::Error> {
+        let mut slots = [ONE; 8];
+        self.slots(&mut slots)?;
+
+        Ok(slots.iter().enumerate().fold(0, |byte, (i, slot)| match *slot == ONE {
+            true => byte | (1 << i),
+            _ => byte,
+        }))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::super::{ UartDriver, UartRxDriver, UartTxDriver };
+
+    /// Half duplex UART on a simulated bus.
+    struct Echo {
+        /// Baud rate of each configuration.
+        bauds: Vec<u32>,
+
+        /// Echo of the reset character.
+        reset: Result<u8, UartError>,
+
+        /// Bits answered to the next read slots.
+        answer: Vec<bool>,
+
+        /// Characters sent at the slot baud rate.
+        sent: Vec<u8>,
+    }
+
+    impl Echo {
+        fn new(reset: Result<u8, UartError>) -> Self {
+            Self { bauds: Vec::new(), reset, answer: Vec::new(), sent: Vec::new() }
+        }
+    }
+
+    impl CommunicationError for Echo {
+        type Error = UartError;
+    }
+
+    impl UartDriver for Echo {
+        fn configure(&mut self, config: &UartConfig) -> Result<(), UartError> {
+            self.bauds.push(config.baud);
+            Ok(())
+        }
+    }
+
+    impl UartTxDriver for Echo {
+        fn send(&mut self, _: &[u8]) -> Result<(), UartError> {
+            unimplemented!()
+        }
+
+        fn flush(&mut self) -> Result<(), UartError> {
+            Ok(())
+        }
+    }
+
+    impl UartRxDriver for Echo {
+        fn recv(&mut self, _: &mut [u8]) -> Result<(), UartError> {
+            unimplemented!()
+        }
+    }
+
+    impl UartDuplexDriver for Echo {
+        fn send_recv(&mut self, send: &[u8], recv: &mut [u8]) -> Result<(), UartError> {
+            if self.bauds.last() == Some(&RESET_BAUD) {
+                recv[0] = self.reset?;
+                return Ok(());
+            }
+
+            for (s, r) in send.iter().zip(recv.iter_mut()) {
+                self.sent.push(*s);
+
+                // A slave answering 0 pulls the first data bits low.
+                *r = match (*s, self.answer.first().copied()) {
+                    (ONE, Some(bit)) => { self.answer.remove(0); if bit { ONE } else { 0xF8 } },
+                    _ => *s,
+                };
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reset_echo() {
+        let cases = [
+            (Ok(RESET), Ok(false)),
+            (Ok(0xE0), Ok(true)),
+            (Ok(0x10), Ok(true)),
+            (Ok(0x00), Err(OneWireError::Short)),
+            (Err(UartError::Framing), Err(OneWireError::Short)),
+            (Err(UartError::Break), Err(OneWireError::Short)),
+            (Err(UartError::Noise), Err(OneWireError::Uart(UartError::Noise))),
+        ];
+
+        for (echo, result) in cases {
+            let mut wire = UartOneWire::new(Echo::new(echo));
+
+            assert_eq!(wire.reset(), result, "echo {:?}", echo);
+
+            // The slot baud rate is restored, even on error.
+            assert_eq!(wire.release().bauds, [RESET_BAUD, SLOT_BAUD]);
+        }
+    }
+
+    #[test]
+    fn bytes_are_sent_lsb_first() {
+        let mut wire = UartOneWire::new(Echo::new(Ok(RESET)));
+
+        assert_eq!(wire.write_byte(0xA5), Ok(()));
+        assert_eq!(wire.write_bit(false), Ok(()));
+
+        assert_eq!(wire.release().sent, [ONE, 0x00, ONE, 0x00, 0x00, ONE, 0x00, ONE, 0x00]);
+    }
+
+    #[test]
+    fn bytes_are_read_lsb_first() {
+        let mut echo = Echo::new(Ok(RESET));
+        echo.answer = (0..8).map(|i| (0x3C >> i) & 1 != 0).chain([false]).collect();
+
+        let mut wire = UartOneWire::new(echo);
+
+        assert_eq!(wire.read_byte(), Ok(0x3C));
+        assert_eq!(wire.read_bit(), Ok(false));
+        assert_eq!(wire.release().sent, [ONE; 9]);
+    }
+}
@@ -0,0 +1,778 @@
+//! Shared bus managers.
+//! Allow several device drivers to use the same I2C or SPI bus. Each device
+//! gets a proxy that implements the driver traits and holds exclusive access
+//! to the bus for the length of each transaction.
+//!
+//! Two flavours are provided:
+//!  - `SharedBus`: arbitrated with an acquire / release lock flag. Blocking
+//!    calls fail with `SharedError::Busy` if the bus is in use, asynchronous
+//!    calls wait until the bus is released, and are woken when it is.
+//!  - `CriticalBus`: every transaction runs inside a critical section.
+//!    Only blocking calls are supported.
+
+
+
+use core::cell::{ Cell, RefCell, UnsafeCell };
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{ AtomicBool, Ordering };
+use core::task::{ Context, Poll, Waker };
+
+use crate::drivers::Data;
+use crate::drivers::gpio::OutputPin;
+
+use super::{
+    CommunicationError, DriverError, ErrorKind,
+    I2CAddress, I2CAsyncDriver, I2CDriver, I2COperation,
+    SPIAsyncDriver, SPIConfig, SPIDriver, SPIOperation,
+};
+
+
+
+/// Errors reported by the proxies of a `SharedBus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedError<E> {
+    /// The bus is in use by another device.
+    Busy,
+
+    /// The bus driver reported an error.
+    Bus(E),
+}
+
+impl<E: DriverError> DriverError for SharedError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SharedError::Busy => ErrorKind::Other,
+            SharedError::Bus(e) => e.kind(),
+        }
+    }
+}
+
+
+
+/// Bus shared between several devices, arbitrated with a lock flag.
+pub struct SharedBus<B> {
+    /// Lock flag.
+    locked: AtomicBool,
+
+    /// Task waiting for the bus, woken when it is released.
+    waiter: critical_section::Mutex<Cell<Option<Waker>>>,
+
+    /// Wrapped bus driver.
+    bus: UnsafeCell<B>,
+}
+
+unsafe impl<B: Send> Sync for SharedBus<B> {}
+
+impl<B> SharedBus<B> {
+    /// Wraps the given bus driver.
+    pub const fn new(bus: B) -> Self {
+        Self { locked: AtomicBool::new(false), waiter: critical_section::Mutex::new(Cell::new(None)), bus: UnsafeCell::new(bus) }
+    }
+
+    /// Creates a new device proxy.
+    pub fn device(&self) -> SharedDevice<'_, B> {
+        SharedDevice { shared: self }
+    }
+
+    /// Failable acquisition of the bus.
+    /// The bus is released when the guard is dropped.
+    pub fn acquire(&self) -> Result<BusGuard<'_, B>, SharedError<core::convert::Infallible>> {
+        if !self.lock() { return Err(SharedError::Busy) }
+
+        Ok(BusGuard { shared: self })
+    }
+
+    /// Consumes the manager and returns the bus driver.
+    pub fn release(self) -> B {
+        self.bus.into_inner()
+    }
+
+    /// Tries to lock the bus.
+    fn lock(&self) -> bool {
+        #[cfg(target_has_atomic = "8")]
+        { self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok() }
+
+        // Targets without CAS instructions (ARMv6-M) lock inside a critical section.
+        #[cfg(not(target_has_atomic = "8"))]
+        critical_section::with(|_| {
+            if self.locked.load(Ordering::Relaxed) { return false }
+            self.locked.store(true, Ordering::Acquire);
+            true
+        })
+    }
+
+    /// Unlocks the bus and wakes the waiting task.
+    fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+
+        if let Some(waker) = critical_section::with(|cs| self.waiter.borrow(cs).take()) {
+            waker.wake();
+        }
+    }
+
+    /// Registers the task to wake when the bus is released.
+    /// A single task is stored: a task displaced by another one is woken, so
+    /// that it polls again and registers back.
+    fn register(&self, waker: &Waker) {
+        let displaced = critical_section::with(|cs| {
+            let waiter = self.waiter.borrow(cs);
+
+            match waiter.take() {
+                Some(old) if old.will_wake(waker) => { waiter.set(Some(old)); None },
+                old => { waiter.set(Some(waker.clone())); old },
+            }
+        });
+
+        if let Some(waker) = displaced { waker.wake() }
+    }
+}
+
+
+
+/// Exclusive access to a `SharedBus`.
+/// Dereferences to the bus driver and releases the bus when dropped.
+pub struct BusGuard<'a, B> {
+    /// Locked bus.
+    shared: &'a SharedBus<B>,
+}
+
+impl<'a, B> core::ops::Deref for BusGuard<'a, B> {
+    type Target = B;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.shared.bus.get() }
+    }
+}
+
+impl<'a, B> core::ops::DerefMut for BusGuard<'a, B> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.shared.bus.get() }
+    }
+}
+
+impl<'a, B> Drop for BusGuard<'a, B> {
+    fn drop(&mut self) {
+        self.shared.unlock()
+    }
+}
+
+
+
+/// Device proxy of a `SharedBus`.
+pub struct SharedDevice<'b, B> {
+    /// Shared bus.
+    shared: &'b SharedBus<B>,
+}
+
+impl<'b, B> SharedDevice<'b, B> {
+    /// Locks the bus for the duration of the call.
+    fn locked<T, E, F: FnOnce(&mut B) -> Result<T, E>>(&mut self, f: F) -> Result<T, SharedError<E>> {
+        if !self.shared.lock() { return Err(SharedError::Busy) }
+
+        // The guard releases the bus even if `f` panics.
+        let mut bus = BusGuard { shared: self.shared };
+
+        f(&mut bus).map_err(SharedError::Bus)
+    }
+}
+
+impl<'b, B: CommunicationError> CommunicationError for SharedDevice<'b, B> {
+    type Error = SharedError<B::Error>;
+}
+
+impl<'b, A: I2CAddress, B: I2CDriver<A>> I2CDriver<A> for SharedDevice<'b, B> {
+    fn transaction(&mut self, addr: A, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error> {
+        self.locked(|bus| bus.transaction(addr, operations))
+    }
+}
+
+impl<'b, W: Data, B: SPIDriver<W>> SPIDriver<W> for SharedDevice<'b, B> {
+    fn configure(&mut self, config: &SPIConfig) -> Result<(), Self::Error> {
+        self.locked(|bus| bus.configure(config))
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.locked(|bus| bus.transfer(read, write))
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.locked(|bus| bus.transfer_in_place(words))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.locked(|bus| bus.flush())
+    }
+
+    fn transaction<P: OutputPin>(&mut self, cs: &mut P, operations: &mut [SPIOperation<'_, W>]) -> Result<(), Self::Error> {
+        // The whole transaction is executed under a single lock.
+        self.locked(|bus| bus.transaction(cs, operations))
+    }
+}
+
+impl<'b, A: I2CAddress, B: I2CAsyncDriver<A>> I2CAsyncDriver<A> for SharedDevice<'b, B> {
+    type WriteFuture<'a> = SharedFuture<'a, B, I2CSend<'a, A>> where Self: 'a;
+    type ReadFuture<'a> = SharedFuture<'a, B, I2CReceive<'a, A>> where Self: 'a;
+    type WriteReadFuture<'a> = SharedFuture<'a, B, I2CTransfer<'a, A>> where Self: 'a;
+    type TransactionFuture<'a> = SharedFuture<'a, B, I2CTransaction<'a, A>> where Self: 'a;
+
+    fn transaction<'a>(&'a mut self, addr: A, operations: &'a mut [I2COperation<'a>]) -> Self::TransactionFuture<'a> {
+        SharedFuture::new(self.shared, I2CTransaction(addr, operations))
+    }
+
+    fn send<'a>(&'a mut self, addr: A, data: &'a [u8]) -> Self::WriteFuture<'a> {
+        SharedFuture::new(self.shared, I2CSend(addr, data))
+    }
+
+    fn receive<'a>(&'a mut self, addr: A, recv: &'a mut [u8]) -> Self::ReadFuture<'a> {
+        SharedFuture::new(self.shared, I2CReceive(addr, recv))
+    }
+
+    fn transfer<'a>(&'a mut self, addr: A, send: &'a [u8], recv: &'a mut [u8]) -> Self::WriteReadFuture<'a> {
+        SharedFuture::new(self.shared, I2CTransfer(addr, send, recv))
+    }
+}
+
+impl<'b, W: Data, B: SPIAsyncDriver<W>> SPIAsyncDriver<W> for SharedDevice<'b, B> {
+    type ReadFuture<'a> = SharedFuture<'a, B, SPIRead<'a, W>> where Self: 'a, W: 'a;
+    type WriteFuture<'a> = SharedFuture<'a, B, SPIWrite<'a, W>> where Self: 'a, W: 'a;
+    type TransferFuture<'a> = SharedFuture<'a, B, SPITransfer<'a, W>> where Self: 'a, W: 'a;
+    type TransferInPlaceFuture<'a> = SharedFuture<'a, B, SPITransferInPlace<'a, W>> where Self: 'a, W: 'a;
+    type TransactionFuture<'a, P> = SharedFuture<'a, B, SPITransaction<'a, W, P>> where Self: 'a, W: 'a, P: OutputPin + 'a;
+
+    fn configure(&mut self, config: &SPIConfig) -> Result<(), Self::Error> {
+        self.locked(|bus| bus.configure(config))
+    }
+
+    fn read<'a>(&'a mut self, words: &'a mut [W]) -> Self::ReadFuture<'a> {
+        SharedFuture::new(self.shared, SPIRead(words))
+    }
+
+    fn write<'a>(&'a mut self, words: &'a [W]) -> Self::WriteFuture<'a> {
+        SharedFuture::new(self.shared, SPIWrite(words))
+    }
+
+    fn transfer<'a>(&'a mut self, read: &'a mut [W], write: &'a [W]) -> Self::TransferFuture<'a> {
+        SharedFuture::new(self.shared, SPITransfer(read, write))
+    }
+
+    fn transfer_in_place<'a>(&'a mut self, words: &'a mut [W]) -> Self::TransferInPlaceFuture<'a> {
+        SharedFuture::new(self.shared, SPITransferInPlace(words))
+    }
+
+    fn transaction<'a, P: OutputPin + 'a>(&'a mut self, cs: &'a mut P, operations: &'a mut [SPIOperation<'a, W>]) -> Self::TransactionFuture<'a, P> {
+        SharedFuture::new(self.shared, SPITransaction(cs, operations))
+    }
+}
+
+
+
+/// Operation on a bus driver that is started once the bus is acquired.
+pub trait Request<'a, B: CommunicationError + 'a> {
+    /// Future of the operation.
+    type Future: Future<Output = Result<(), B::Error>> + 'a;
+
+    /// Starts the operation on the locked bus.
+    fn start(self, bus: &'a mut B) -> Self::Future;
+}
+
+/// Asynchronous operation on a `SharedBus`.
+/// Waits until the bus is free, then runs the operation holding the lock.
+/// Dropping it cancels the operation (following the contract of the bus
+/// driver) and releases the bus.
+pub struct SharedFuture<'a, B: CommunicationError + 'a, R: Request<'a, B>> {
+    /// Shared bus.
+    shared: &'a SharedBus<B>,
+
+    /// Progress of the operation.
+    state: State<R, R::Future>,
+}
+
+/// Progress of a `SharedFuture`.
+enum State<R, F> {
+    /// Waiting for the bus.
+    Waiting(Option<R>),
+
+    /// Running with the bus locked.
+    Running(F),
+
+    /// Completed, the bus has been released.
+    Done,
+}
+
+impl<'a, B: CommunicationError + 'a, R: Request<'a, B>> SharedFuture<'a, B, R> {
+    /// Creates a new operation waiting for the bus.
+    fn new(shared: &'a SharedBus<B>, request: R) -> Self {
+        Self { shared, state: State::Waiting(Some(request)) }
+    }
+}
+
+impl<'a, B: CommunicationError + 'a, R: Request<'a, B>> Future for SharedFuture<'a, B, R> {
+    type Output = Result<(), SharedError<B::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The running future is never moved out of `state` until it is dropped.
+        let this = unsafe { self.get_unchecked_mut() };
+
+        if let State::Waiting(request) = &mut this.state {
+            if !this.shared.lock() {
+                this.shared.register(cx.waker());
+
+                // The bus may have been released before the waker was stored.
+                if !this.shared.lock() { return Poll::Pending }
+            }
+
+            let request = request.take().expect("request already started");
+            let bus = unsafe { &mut *this.shared.bus.get() };
+
+            this.state = State::Running(request.start(bus));
+        }
+
+        let future = match &mut this.state {
+            State::Running(future) => unsafe { Pin::new_unchecked(future) },
+            _ => panic!("`SharedFuture` polled after completion"),
+        };
+
+        match future.poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(r) => {
+                this.state = State::Done;
+                this.shared.unlock();
+
+                Poll::Ready(r.map_err(SharedError::Bus))
+            },
+        }
+    }
+}
+
+impl<'a, B: CommunicationError + 'a, R: Request<'a, B>> Drop for SharedFuture<'a, B, R> {
+    fn drop(&mut self) {
+        if let State::Running(_) = self.state {
+            // Cancel the operation before releasing the bus.
+            self.state = State::Done;
+            self.shared.unlock();
+        }
+    }
+}
+
+
+
+/// Pending I2C transaction.
+pub struct I2CTransaction<'a, A>(A, &'a mut [I2COperation<'a>]);
+
+impl<'a, A: I2CAddress, B: I2CAsyncDriver<A> + 'a> Request<'a, B> for I2CTransaction<'a, A> {
+    type Future = B::TransactionFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.transaction(self.0, self.1)
+    }
+}
+
+/// Pending I2C send.
+pub struct I2CSend<'a, A>(A, &'a [u8]);
+
+impl<'a, A: I2CAddress, B: I2CAsyncDriver<A> + 'a> Request<'a, B> for I2CSend<'a, A> {
+    type Future = B::WriteFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.send(self.0, self.1)
+    }
+}
+
+/// Pending I2C receive.
+pub struct I2CReceive<'a, A>(A, &'a mut [u8]);
+
+impl<'a, A: I2CAddress, B: I2CAsyncDriver<A> + 'a> Request<'a, B> for I2CReceive<'a, A> {
+    type Future = B::ReadFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.receive(self.0, self.1)
+    }
+}
+
+/// Pending I2C write-read.
+pub struct I2CTransfer<'a, A>(A, &'a [u8], &'a mut [u8]);
+
+impl<'a, A: I2CAddress, B: I2CAsyncDriver<A> + 'a> Request<'a, B> for I2CTransfer<'a, A> {
+    type Future = B::WriteReadFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.transfer(self.0, self.1, self.2)
+    }
+}
+
+/// Pending SPI read.
+pub struct SPIRead<'a, W>(&'a mut [W]);
+
+impl<'a, W: Data, B: SPIAsyncDriver<W> + 'a> Request<'a, B> for SPIRead<'a, W> {
+    type Future = B::ReadFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.read(self.0)
+    }
+}
+
+/// Pending SPI write.
+pub struct SPIWrite<'a, W>(&'a [W]);
+
+impl<'a, W: Data, B: SPIAsyncDriver<W> + 'a> Request<'a, B> for SPIWrite<'a, W> {
+    type Future = B::WriteFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.write(self.0)
+    }
+}
+
+/// Pending SPI full-duplex transfer.
+pub struct SPITransfer<'a, W>(&'a mut [W], &'a [W]);
+
+impl<'a, W: Data, B: SPIAsyncDriver<W> + 'a> Request<'a, B> for SPITransfer<'a, W> {
+    type Future = B::TransferFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.transfer(self.0, self.1)
+    }
+}
+
+/// Pending SPI in place transfer.
+pub struct SPITransferInPlace<'a, W>(&'a mut [W]);
+
+impl<'a, W: Data, B: SPIAsyncDriver<W> + 'a> Request<'a, B> for SPITransferInPlace<'a, W> {
+    type Future = B::TransferInPlaceFuture<'a>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.transfer_in_place(self.0)
+    }
+}
+
+/// Pending SPI transaction.
+pub struct SPITransaction<'a, W: Data, P>(&'a mut P, &'a mut [SPIOperation<'a, W>]);
+
+impl<'a, W: Data, P: OutputPin + 'a, B: SPIAsyncDriver<W> + 'a> Request<'a, B> for SPITransaction<'a, W, P> {
+    type Future = B::TransactionFuture<'a, P>;
+
+    fn start(self, bus: &'a mut B) -> Self::Future {
+        bus.transaction(self.0, self.1)
+    }
+}
+
+
+
+/// Bus shared between several devices, arbitrated with critical sections.
+pub struct CriticalBus<B> {
+    /// Wrapped bus driver.
+    bus: critical_section::Mutex<RefCell<B>>,
+}
+
+impl<B> CriticalBus<B> {
+    /// Wraps the given bus driver.
+    pub const fn new(bus: B) -> Self {
+        Self { bus: critical_section::Mutex::new(RefCell::new(bus)) }
+    }
+
+    /// Creates a new device proxy.
+    pub fn device(&self) -> CriticalDevice<'_, B> {
+        CriticalDevice { shared: self }
+    }
+
+    /// Executes the closure with exclusive access to the bus inside a critical section.
+    pub fn lock<T, F: FnOnce(&mut B) -> T>(&self, f: F) -> T {
+        critical_section::with(|cs| f(&mut self.bus.borrow_ref_mut(cs)))
+    }
+
+    /// Consumes the manager and returns the bus driver.
+    pub fn release(self) -> B {
+        self.bus.into_inner().into_inner()
+    }
+}
+
+
+
+/// Device proxy of a `CriticalBus`.
+pub struct CriticalDevice<'b, B> {
+    /// Shared bus.
+    shared: &'b CriticalBus<B>,
+}
+
+impl<'b, B: CommunicationError> CommunicationError for CriticalDevice<'b, B> {
+    type Error = B::Error;
+}
+
+impl<'b, A: I2CAddress, B: I2CDriver<A>> I2CDriver<A> for CriticalDevice<'b, B> {
+    fn transaction(&mut self, addr: A, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error> {
+        self.shared.lock(|bus| bus.transaction(addr, operations))
+    }
+}
+
+impl<'b, W: Data, B: SPIDriver<W>> SPIDriver<W> for CriticalDevice<'b, B> {
+    fn configure(&mut self, config: &SPIConfig) -> Result<(), Self::Error> {
+        self.shared.lock(|bus| bus.configure(config))
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.shared.lock(|bus| bus.transfer(read, write))
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.shared.lock(|bus| bus.transfer_in_place(words))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.shared.lock(|bus| bus.flush())
+    }
+
+    fn transaction<P: OutputPin>(&mut self, cs: &mut P, operations: &mut [SPIOperation<'_, W>]) -> Result<(), Self::Error> {
+        // The whole transaction is executed in a single critical section.
+        self.shared.lock(|bus| bus.transaction(cs, operations))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::sync::atomic::AtomicUsize;
+    use core::task::{ Context, Poll };
+
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use super::*;
+    use super::super::I2CAddress7bit;
+    use super::super::mock::{ I2CMock, MockError };
+
+    const TARGET: I2CAddress7bit = I2CAddress7bit::new(0x50);
+
+    /// SPI bus echoing the written words.
+    struct Loopback {
+        /// Number of calls.
+        calls: usize,
+    }
+
+    impl CommunicationError for Loopback {
+        type Error = MockError;
+    }
+
+    impl SPIDriver for Loopback {
+        fn configure(&mut self, _: &SPIConfig) -> Result<(), Self::Error> {
+            self.calls += 1;
+            Ok(())
+        }
+
+        fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+            self.calls += 1;
+            read.iter_mut().zip(write).for_each(|(r, w)| *r = *w);
+            Ok(())
+        }
+
+        fn transfer_in_place(&mut self, _: &mut [u8]) -> Result<(), Self::Error> {
+            self.calls += 1;
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.calls += 1;
+            Ok(())
+        }
+    }
+
+    /// Chip select recording whether the bus was locked at each edge.
+    struct Probe<'a, F: Fn() -> bool> {
+        /// Returns `true` if the bus is locked.
+        locked: F,
+
+        /// Lock state at each edge.
+        edges: [Option<bool>; 4],
+
+        /// Number of edges.
+        n: usize,
+
+        /// `true` if `'a` is used.
+        _bus: core::marker::PhantomData<&'a ()>,
+    }
+
+    impl<'a, F: Fn() -> bool> Probe<'a, F> {
+        fn new(locked: F) -> Self {
+            Self { locked, edges: [None; 4], n: 0, _bus: core::marker::PhantomData }
+        }
+
+        fn edge(&mut self) {
+            self.edges[self.n] = Some((self.locked)());
+            self.n += 1;
+        }
+    }
+
+    impl<'a, F: Fn() -> bool> OutputPin for Probe<'a, F> {
+        fn set_high(&mut self) {
+            self.edge()
+        }
+
+        fn set_low(&mut self) {
+            self.edge()
+        }
+    }
+
+    /// Waker counting its wake-ups.
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn waiting_transfer_is_woken_on_release() {
+        let shared = SharedBus::new(I2CMock::new(TARGET, [0u8; 4]));
+        let mut device = shared.device();
+
+        let counter = Arc::new(Counter(AtomicUsize::new(0)));
+        let waker = counter.clone().into();
+        let mut cx = Context::from_waker(&waker);
+
+        let guard = shared.acquire().unwrap();
+
+        let mut transfer = pin!(I2CAsyncDriver::send(&mut device, TARGET, &[0x01, 0xAA]));
+
+        // Waiting for the bus does not spin.
+        for _ in 0..4 {
+            assert!(transfer.as_mut().poll(&mut cx).is_pending());
+        }
+        assert_eq!(counter.0.load(Ordering::Relaxed), 0);
+
+        drop(guard);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        let result = loop {
+            if let Poll::Ready(r) = transfer.as_mut().poll(&mut cx) { break r }
+        };
+
+        assert_eq!(result, Ok(()));
+        assert!(shared.acquire().is_ok());
+    }
+
+    #[test]
+    fn blocking_call_fails_while_locked() {
+        let shared = SharedBus::new(I2CMock::new(TARGET, [0u8; 4]));
+        let mut device = shared.device();
+
+        let guard = shared.acquire().unwrap();
+        assert_eq!(I2CDriver::send(&mut device, TARGET, &[0x00]), Err(SharedError::Busy));
+
+        drop(guard);
+        assert_eq!(I2CDriver::send(&mut device, TARGET, &[0x00]), Ok(()));
+    }
+
+    #[test]
+    fn displaced_waiter_is_woken() {
+        let shared = SharedBus::new(I2CMock::new(TARGET, [0u8; 4]));
+        let mut first = shared.device();
+        let mut second = shared.device();
+
+        let a = Arc::new(Counter(AtomicUsize::new(0)));
+        let b = Arc::new(Counter(AtomicUsize::new(0)));
+        let (wa, wb) = (a.clone().into(), b.clone().into());
+
+        let guard = shared.acquire().unwrap();
+
+        let mut ta = pin!(I2CAsyncDriver::send(&mut first, TARGET, &[0x00]));
+        let mut tb = pin!(I2CAsyncDriver::send(&mut second, TARGET, &[0x01]));
+
+        assert!(ta.as_mut().poll(&mut Context::from_waker(&wa)).is_pending());
+        assert!(tb.as_mut().poll(&mut Context::from_waker(&wb)).is_pending());
+
+        assert_eq!(a.0.load(Ordering::Relaxed), 1);
+        assert_eq!(b.0.load(Ordering::Relaxed), 0);
+
+        drop(guard);
+        assert_eq!(b.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn panicking_call_releases_the_bus() {
+        let shared = SharedBus::new(I2CMock::new(TARGET, [0u8; 4]));
+        let mut device = shared.device();
+
+        let result = std::panic::catch_unwind(core::panic::AssertUnwindSafe(|| {
+            let _ = device.locked(|_| -> Result<(), MockError> { panic!("driver panicked") });
+        }));
+
+        assert!(result.is_err());
+        assert!(shared.acquire().is_ok());
+    }
+
+    #[test]
+    fn blocking_i2c_proxies() {
+        let shared = SharedBus::new(I2CMock::new(TARGET, [0u8; 4]));
+        let (mut a, mut b) = (shared.device(), shared.device());
+
+        assert_eq!(I2CDriver::send(&mut a, TARGET, &[0x01, 0xAA, 0xBB]), Ok(()));
+
+        let mut recv = [0; 2];
+        assert_eq!(I2CDriver::transfer(&mut b, TARGET, &[0x01], &mut recv), Ok(()));
+        assert_eq!(recv, [0xAA, 0xBB]);
+
+        // Bus errors are wrapped.
+        assert!(matches!(I2CDriver::send(&mut a, I2CAddress7bit::new(0x51), &[0]), Err(SharedError::Bus(_))));
+        assert!(shared.acquire().is_ok());
+
+        let critical = CriticalBus::new(shared.release());
+        let (mut a, mut b) = (critical.device(), critical.device());
+
+        assert_eq!(I2CDriver::send(&mut a, TARGET, &[0x02, 0xCC]), Ok(()));
+        assert_eq!(I2CDriver::transfer(&mut b, TARGET, &[0x02], &mut recv), Ok(()));
+        assert_eq!(recv, [0xCC, 0x00]);
+        assert!(I2CDriver::send(&mut a, I2CAddress7bit::new(0x51), &[0]).is_err());
+
+        assert_eq!(critical.release().memory(), &[0, 0xAA, 0xCC, 0]);
+    }
+
+    #[test]
+    fn blocking_spi_proxies() {
+        let shared = SharedBus::new(Loopback { calls: 0 });
+        let mut device = shared.device();
+
+        let mut read = [0; 2];
+        assert_eq!(SPIDriver::configure(&mut device, &SPIConfig::new(1_000_000)), Ok(()));
+        assert_eq!(SPIDriver::transfer(&mut device, &mut read, &[1, 2]), Ok(()));
+        assert_eq!(SPIDriver::transfer_in_place(&mut device, &mut read), Ok(()));
+        assert_eq!(SPIDriver::flush(&mut device), Ok(()));
+        assert_eq!(read, [1, 2]);
+
+        let guard = shared.acquire().unwrap();
+        assert_eq!(SPIDriver::flush(&mut device), Err(SharedError::Busy));
+        drop(guard);
+
+        let critical = CriticalBus::new(shared.release());
+        let mut device = critical.device();
+
+        assert_eq!(SPIDriver::transfer(&mut device, &mut read, &[3, 4]), Ok(()));
+        assert_eq!(read, [3, 4]);
+        assert_eq!(critical.release().calls, 5);
+    }
+
+    #[test]
+    fn spi_transaction_holds_a_single_lock() {
+        let shared = SharedBus::new(Loopback { calls: 0 });
+        let mut device = shared.device();
+        let mut cs = Probe::new(|| shared.acquire().is_err());
+
+        let mut read = [0; 1];
+        let mut operations = [SPIOperation::Write(&[1]), SPIOperation::Read(&mut read)];
+
+        assert_eq!(SPIDriver::transaction(&mut device, &mut cs, &mut operations), Ok(()));
+        assert_eq!(cs.edges, [Some(true), Some(true), None, None]);
+        assert!(shared.acquire().is_ok());
+
+        let critical = CriticalBus::new(shared.release());
+        let mut device = critical.device();
+        let mut cs = Probe::new(|| critical_section::with(|cs| critical.bus.borrow(cs).try_borrow_mut().is_err()));
+
+        let mut operations = [SPIOperation::Write(&[1]), SPIOperation::Write(&[2])];
+
+        assert_eq!(SPIDriver::transaction(&mut device, &mut cs, &mut operations), Ok(()));
+        assert_eq!(cs.edges, [Some(true), Some(true), None, None]);
+    }
+}
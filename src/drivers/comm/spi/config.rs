@@ -0,0 +1,86 @@
+//! SPI bus configuration.
+
+
+
+/// Configuration of a SPI bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SPIConfig {
+    /// Clock polarity and phase.
+    pub mode: SPIMode,
+
+    /// Order in which the bits of a word are shifted.
+    pub order: BitOrder,
+
+    /// Number of bits per word.
+    pub bits: u8,
+
+    /// Clock frequency in Hz.
+    pub frequency: u32,
+}
+
+impl SPIConfig {
+    /// Creates a new mode 0, MSB first, 8-bit configuration at the given frequency.
+    pub const fn new(frequency: u32) -> Self {
+        Self { mode: SPIMode::Mode0, order: BitOrder::MSBFirst, bits: 8, frequency }
+    }
+
+    /// Configures the clock polarity and phase.
+    #[inline]
+    pub const fn mode(mut self, mode: SPIMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Configures the bit order.
+    #[inline]
+    pub const fn order(mut self, order: BitOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Configures the number of bits per word.
+    #[inline]
+    pub const fn bits(mut self, bits: u8) -> Self {
+        self.bits = bits;
+        self
+    }
+}
+
+
+
+/// SPI clock polarity (CPOL) and phase (CPHA) modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SPIMode {
+    /// Clock idles low, data sampled on the leading edge.
+    Mode0,
+
+    /// Clock idles low, data sampled on the trailing edge.
+    Mode1,
+
+    /// Clock idles high, data sampled on the leading edge.
+    Mode2,
+
+    /// Clock idles high, data sampled on the trailing edge.
+    Mode3,
+}
+
+impl SPIMode {
+    /// Returns `true` if the clock idles high (CPOL = 1).
+    pub const fn cpol(&self) -> bool {
+        matches!(self, SPIMode::Mode2 | SPIMode::Mode3)
+    }
+
+    /// Returns `true` if data is sampled on the trailing edge (CPHA = 1).
+    pub const fn cpha(&self) -> bool {
+        matches!(self, SPIMode::Mode1 | SPIMode::Mode3)
+    }
+}
+
+
+
+/// Order in which the bits of a word are shifted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    MSBFirst,
+    LSBFirst,
+}
@@ -0,0 +1,48 @@
+//! SPI chip select guard.
+
+
+
+use crate::drivers::gpio::OutputPin;
+
+
+
+/// Chip select guard.
+/// Asserts (drives low) the chip select when created and deasserts it when
+/// dropped, even if the transfer in between failed or was cancelled.
+/// Dereferences to the wrapped bus.
+pub struct ChipSelect<'a, B, P: OutputPin> {
+    /// Wrapped bus.
+    bus: &'a mut B,
+
+    /// Chip select pin.
+    cs: &'a mut P,
+}
+
+impl<'a, B, P: OutputPin> ChipSelect<'a, B, P> {
+    /// Asserts the chip select and returns the guard.
+    pub fn new(bus: &'a mut B, cs: &'a mut P) -> Self {
+        cs.set_low();
+
+        Self { bus, cs }
+    }
+}
+
+impl<'a, B, P: OutputPin> core::ops::Deref for ChipSelect<'a, B, P> {
+    type Target = B;
+
+    fn deref(&self) -> &Self::Target {
+        self.bus
+    }
+}
+
+impl<'a, B, P: OutputPin> core::ops::DerefMut for ChipSelect<'a, B, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.bus
+    }
+}
+
+impl<'a, B, P: OutputPin> Drop for ChipSelect<'a, B, P> {
+    fn drop(&mut self) {
+        self.cs.set_high();
+    }
+}
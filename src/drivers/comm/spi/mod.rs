@@ -0,0 +1,308 @@
+//! SPI Driver traits.
+
+
+
+mod config;
+mod cs;
+
+
+
+use core::future::Future;
+
+use crate::drivers::Data;
+use crate::drivers::gpio::OutputPin;
+
+use super::CommunicationError;
+
+
+
+pub use self::config::{ BitOrder, SPIConfig, SPIMode };
+pub use self::cs::ChipSelect;
+
+
+
+/// Single operation of a SPI transaction.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SPIOperation<'a, W: Data = u8> {
+    /// Reads words into the buffer, writing dummy words.
+    Read(&'a mut [W]),
+
+    /// Writes the words, discarding the read words.
+    Write(&'a [W]),
+
+    /// Reads into the first buffer while writing the second.
+    Transfer(&'a mut [W], &'a [W]),
+
+    /// Writes the words of the buffer, replacing them with the read words.
+    TransferInPlace(&'a mut [W]),
+}
+
+
+
+/// SPI communication driver.
+/// Generic over the word type, which defaults to bytes.
+pub trait SPIDriver<W: Data = u8>: CommunicationError {
+    /// Applies the given bus configuration.
+    fn configure(&mut self, config: &SPIConfig) -> Result<(), Self::Error>;
+
+    /// Reads into `read` while writing `write` (full-duplex).
+    /// If the lengths differ, dummy words are written after `write` is
+    /// exhausted and read words are discarded after `read` is full.
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error>;
+
+    /// Writes the words of the buffer, replacing them with the read words.
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error>;
+
+    /// Writes the words, discarding the read words.
+    fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.transfer(&mut [], words)
+    }
+
+    /// Reads words into the buffer, writing dummy words.
+    fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.transfer(words, &[])
+    }
+
+    /// Blocks until all the words have been shifted out.
+    fn flush(&mut self) -> Result<(), Self::Error>;
+
+    /// Executes the list of operations with the given chip select asserted.
+    /// The chip select is deasserted after the bus is flushed, or as soon as an operation fails.
+    fn transaction<P: OutputPin>(&mut self, cs: &mut P, operations: &mut [SPIOperation<'_, W>]) -> Result<(), Self::Error> where Self: Sized {
+        let mut bus = ChipSelect::new(self, cs);
+
+        for operation in operations.iter_mut() {
+            match operation {
+                SPIOperation::Read(r) => bus.read(r)?,
+                SPIOperation::Write(w) => bus.write(w)?,
+                SPIOperation::Transfer(r, w) => bus.transfer(r, w)?,
+                SPIOperation::TransferInPlace(rw) => bus.transfer_in_place(rw)?,
+            }
+        }
+
+        bus.flush()
+    }
+}
+
+
+
+/// Asynchronous SPI communication driver.
+/// Mirrors the operations of `SPIDriver`, without `flush`: the futures only
+/// complete once all the words have been shifted.
+///
+/// The returned futures borrow both the driver and the buffers for `'a`.
+/// Dropping a future before it completes cancels the transfer: before `drop`
+/// returns the implementation must stop the peripheral (and any DMA) from
+/// accessing the buffers and, for transactions, deassert the chip select.
+pub trait SPIAsyncDriver<W: Data = u8>: CommunicationError {
+    type ReadFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type WriteFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type TransferFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type TransferInPlaceFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type TransactionFuture<'a, P>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a, P: OutputPin + 'a;
+
+    /// Applies the given bus configuration.
+    fn configure(&mut self, config: &SPIConfig) -> Result<(), Self::Error>;
+
+    /// Reads words into the buffer, writing dummy words.
+    fn read<'a>(&'a mut self, words: &'a mut [W]) -> Self::ReadFuture<'a>;
+
+    /// Writes the words, discarding the read words.
+    fn write<'a>(&'a mut self, words: &'a [W]) -> Self::WriteFuture<'a>;
+
+    /// Reads into `read` while writing `write` (full-duplex).
+    fn transfer<'a>(&'a mut self, read: &'a mut [W], write: &'a [W]) -> Self::TransferFuture<'a>;
+
+    /// Writes the words of the buffer, replacing them with the read words.
+    fn transfer_in_place<'a>(&'a mut self, words: &'a mut [W]) -> Self::TransferInPlaceFuture<'a>;
+
+    /// Executes the list of operations with the given chip select asserted.
+    /// The chip select is deasserted when the future completes, fails or is dropped.
+    fn transaction<'a, P: OutputPin + 'a>(&'a mut self, cs: &'a mut P, operations: &'a mut [SPIOperation<'a, W>]) -> Self::TransactionFuture<'a, P>;
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use super::super::mock::MockError;
+
+    /// Chip select line.
+    struct Line {
+        /// Level of the line.
+        high: Cell<bool>,
+
+        /// Number of level changes.
+        edges: Cell<usize>,
+    }
+
+    impl Line {
+        const fn new() -> Self {
+            Self { high: Cell::new(true), edges: Cell::new(0) }
+        }
+    }
+
+    /// Pin driving a line.
+    struct Pin<'a>(&'a Line);
+
+    impl<'a> OutputPin for Pin<'a> {
+        fn set_high(&mut self) {
+            if !self.0.high.replace(true) { self.0.edges.set(self.0.edges.get() + 1) }
+        }
+
+        fn set_low(&mut self) {
+            if self.0.high.replace(false) { self.0.edges.set(self.0.edges.get() + 1) }
+        }
+    }
+
+    /// Bus echoing the written words, logging the level of the chip select
+    /// during each call.
+    struct Bus<'a> {
+        /// Chip select line.
+        cs: &'a Line,
+
+        /// Levels of the chip select seen by the calls.
+        log: [bool; 8],
+
+        /// Number of calls.
+        calls: usize,
+
+        /// Call that fails.
+        fail: Option<usize>,
+
+        /// Number of flushes.
+        flushes: usize,
+    }
+
+    impl<'a> Bus<'a> {
+        fn new(cs: &'a Line, fail: Option<usize>) -> Self {
+            Self { cs, log: [true; 8], calls: 0, fail, flushes: 0 }
+        }
+
+        fn call(&mut self) -> Result<(), MockError> {
+            self.log[self.calls] = self.cs.high.get();
+            self.calls += 1;
+
+            match self.fail == Some(self.calls - 1) {
+                true => Err(MockError::Busy),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    impl<'a> CommunicationError for Bus<'a> {
+        type Error = MockError;
+    }
+
+    impl<'a> SPIDriver for Bus<'a> {
+        fn configure(&mut self, _: &SPIConfig) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+            self.call()?;
+            read.iter_mut().enumerate().for_each(|(i, r)| *r = write.get(i).copied().unwrap_or(0xFF));
+            Ok(())
+        }
+
+        fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+            self.call()?;
+            words.iter_mut().for_each(|w| *w = !*w);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.flushes += 1;
+            self.call()
+        }
+    }
+
+    #[test]
+    fn guard_asserts_while_alive() {
+        let line = Line::new();
+        let mut pin = Pin(&line);
+        let mut bus = Bus::new(&line, None);
+
+        {
+            let mut guard = ChipSelect::new(&mut bus, &mut pin);
+            assert!(!line.high.get());
+
+            guard.write(&[1, 2]).unwrap();
+        }
+
+        assert!(line.high.get());
+        assert_eq!(line.edges.get(), 2);
+        assert!(!bus.log[0]);
+    }
+
+    #[test]
+    fn guard_deasserts_on_error() {
+        let line = Line::new();
+        let mut pin = Pin(&line);
+        let mut bus = Bus::new(&line, Some(0));
+
+        let result = (|| {
+            let mut guard = ChipSelect::new(&mut bus, &mut pin);
+            guard.write(&[1])?;
+            guard.write(&[2])
+        })();
+
+        assert_eq!(result, Err(MockError::Busy));
+        assert!(line.high.get());
+        assert_eq!(bus.calls, 1);
+    }
+
+    #[test]
+    fn transaction_runs_operations_with_cs_asserted() {
+        let line = Line::new();
+        let mut pin = Pin(&line);
+        let mut bus = Bus::new(&line, None);
+
+        let mut read = [0; 2];
+        let mut transfer = [0; 2];
+        let mut in_place = [0x0F, 0xF0];
+
+        let mut operations = [
+            SPIOperation::Write(&[0x9F]),
+            SPIOperation::Read(&mut read),
+            SPIOperation::Transfer(&mut transfer, &[1, 2]),
+            SPIOperation::TransferInPlace(&mut in_place),
+        ];
+
+        assert_eq!(bus.transaction(&mut pin, &mut operations), Ok(()));
+
+        assert_eq!(read, [0xFF, 0xFF]);
+        assert_eq!(transfer, [1, 2]);
+        assert_eq!(in_place, [0xF0, 0x0F]);
+
+        // Four operations and the flush, all with the chip select low.
+        assert_eq!(bus.calls, 5);
+        assert_eq!(bus.log[..5], [false; 5]);
+        assert_eq!(bus.flushes, 1);
+        assert!(line.high.get());
+        assert_eq!(line.edges.get(), 2);
+    }
+
+    #[test]
+    fn transaction_stops_at_the_first_error() {
+        let line = Line::new();
+        let mut pin = Pin(&line);
+        let mut bus = Bus::new(&line, Some(1));
+
+        let mut operations = [
+            SPIOperation::Write(&[1]),
+            SPIOperation::Write(&[2]),
+            SPIOperation::Write(&[3]),
+        ];
+
+        assert_eq!(bus.transaction(&mut pin, &mut operations), Err(MockError::Busy));
+
+        assert_eq!(bus.calls, 2);
+        assert_eq!(bus.flushes, 0);
+        assert!(line.high.get());
+        assert_eq!(line.edges.get(), 2);
+    }
+}
@@ -0,0 +1,475 @@
+//! Interrupt driven buffered UART.
+//! Wraps a `UartIrqDriver` with preallocated TX and RX rings. The UART
+//! interrupt moves words between the rings and the peripheral, while the
+//! application reads and writes the rings without blocking on the line.
+
+
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{ AtomicU8, AtomicUsize, Ordering };
+
+use crate::buffer::RingBuffer;
+use crate::int::UserHandler;
+
+use super::{ UartConfig, UartError, UartIrqDriver };
+
+
+
+/// Errors detected in one direction since the last query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UartStatus {
+    /// Number of words lost because a ring or the peripheral was full.
+    pub overruns: usize,
+
+    /// Last line error.
+    pub error: Option<UartError>,
+}
+
+
+
+/// Buffered UART with `TX` and `RX` byte rings.
+/// It must be placed in a `static`, serviced from the UART interrupt (see
+/// `handler`), and written and read from a single context each.
+/// The blocking operations (`core::fmt::Write`, `embedded_io`) spin until
+/// the interrupt has made progress, so they must not be called from a
+/// context that preempts the UART interrupt or with interrupts disabled.
+pub struct BufferedUart<U, const TX: usize, const RX: usize> {
+    /// Wrapped driver.
+    uart: UnsafeCell<U>,
+
+    /// Transmission ring. Filled by the application, drained by the interrupt.
+    tx: RingBuffer<u8, TX>,
+
+    /// Reception ring. Filled by the interrupt, drained by the application.
+    rx: RingBuffer<u8, RX>,
+
+    /// Transmission errors.
+    txstatus: Status,
+
+    /// Reception errors.
+    rxstatus: Status,
+}
+
+impl<U: UartIrqDriver, const TX: usize, const RX: usize> BufferedUart<U, TX, RX> {
+    /// Static initializer.
+    pub const fn new(uart: U) -> Self {
+        Self {
+            uart: UnsafeCell::new(uart),
+            tx: RingBuffer::new(),
+            rx: RingBuffer::new(),
+            txstatus: Status::new(),
+            rxstatus: Status::new(),
+        }
+    }
+
+    /// Creates the interrupt handler that services this UART.
+    pub fn handler(&'static self) -> UserHandler where U: Send {
+        UserHandler::shared(Self::service, self)
+    }
+
+    /// Services the peripheral. Must only be called from the UART interrupt.
+    /// Moves received words into the RX ring and queued words into the
+    /// transmitter, disabling the transmit interrupt once the TX ring is empty.
+    pub fn service(&self) {
+        let uart = unsafe { &mut *self.uart.get() };
+
+        while let Some(received) = uart.try_recv() {
+            match received {
+                Ok(byte) => if self.rx.push(byte).is_err() { self.rxstatus.overrun(1) },
+                Err(UartError::Overrun) => self.rxstatus.overrun(1),
+                Err(error) => self.rxstatus.error(error),
+            }
+        }
+
+        while let Some(byte) = self.tx.peek() {
+            match uart.try_send(byte) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(error) => self.txstatus.error(error),
+            }
+
+            self.tx.pop();
+        }
+
+        if self.tx.is_empty() { uart.tx_interrupt(false) }
+    }
+
+    /// Applies the given line configuration.
+    pub fn configure(&self, config: &UartConfig) -> Result<(), UartError> {
+        critical_section::with(|_| unsafe { (*self.uart.get()).configure(config) })
+    }
+
+    /// Queues as many bytes as fit in the TX ring without blocking. The bytes
+    /// that do not fit are dropped and counted as TX overruns.
+    /// Returns the number of bytes queued.
+    pub fn try_write(&self, data: &[u8]) -> usize {
+        let n = self.tx.push_slice(data);
+
+        if n > 0 { self.kick() }
+        if n < data.len() { self.txstatus.overrun(data.len() - n) }
+
+        n
+    }
+
+    /// Reads the bytes available in the RX ring without blocking.
+    /// Returns the number of bytes read.
+    pub fn try_read(&self, data: &mut [u8]) -> usize {
+        self.rx.pop_slice(data)
+    }
+
+    /// Number of bytes waiting in the RX ring.
+    pub fn available(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Returns and clears the transmission errors.
+    pub fn tx_status(&self) -> UartStatus {
+        self.txstatus.take()
+    }
+
+    /// Returns and clears the reception errors.
+    pub fn rx_status(&self) -> UartStatus {
+        self.rxstatus.take()
+    }
+
+    /// Consumes the wrapper and returns the driver.
+    pub fn release(self) -> U {
+        self.uart.into_inner()
+    }
+
+    /// Queues all the bytes, spinning until there is room in the TX ring.
+    fn write_blocking(&self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let n = self.tx.push_slice(data);
+            if n > 0 { self.kick() }
+
+            data = &data[n..];
+        }
+    }
+
+    /// Enables the transmit interrupt to drain the TX ring.
+    fn kick(&self) {
+        critical_section::with(|_| unsafe { (*self.uart.get()).tx_interrupt(true) })
+    }
+}
+
+unsafe impl<U: Send, const TX: usize, const RX: usize> Sync for BufferedUart<U, TX, RX> {}
+
+impl<U: UartIrqDriver, const TX: usize, const RX: usize> core::fmt::Write for &BufferedUart<U, TX, RX> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_blocking(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<U: UartIrqDriver, const TX: usize, const RX: usize> embedded_io::ErrorType for &BufferedUart<U, TX, RX> {
+    type Error = UartError;
+}
+
+impl<U: UartIrqDriver, const TX: usize, const RX: usize> embedded_io::Write for &BufferedUart<U, TX, RX> {
+    /// Spins until at least one byte fits in the TX ring.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() { return Ok(0) }
+
+        loop {
+            let n = self.tx.push_slice(buf);
+
+            if n > 0 {
+                self.kick();
+                return Ok(n);
+            }
+        }
+    }
+
+    /// Spins until the TX ring is empty and the last byte has left the transmitter.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while !self.tx.is_empty() {}
+
+        while !critical_section::with(|_| unsafe { (*self.uart.get()).tx_complete() }) {}
+
+        Ok(())
+    }
+}
+
+impl<U: UartIrqDriver, const TX: usize, const RX: usize> embedded_io::WriteReady for &BufferedUart<U, TX, RX> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.tx.is_full())
+    }
+}
+
+impl<U: UartIrqDriver, const TX: usize, const RX: usize> embedded_io::Read for &BufferedUart<U, TX, RX> {
+    /// Spins until at least one byte is available. Bytes received before a
+    /// line error are returned first, then the error.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() { return Ok(0) }
+
+        loop {
+            let n = self.rx.pop_slice(buf);
+            if n > 0 { return Ok(n) }
+
+            if let Some(error) = self.rxstatus.take_error() { return Err(error) }
+        }
+    }
+}
+
+impl<U: UartIrqDriver, const TX: usize, const RX: usize> embedded_io::ReadReady for &BufferedUart<U, TX, RX> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.rx.is_empty())
+    }
+}
+
+
+
+/// Error counters of one direction.
+/// Each field is only written from one context, and cleared in a critical section.
+struct Status {
+    /// Number of words lost.
+    overruns: AtomicUsize,
+
+    /// Last line error (`NONE` if none).
+    error: AtomicU8,
+}
+
+impl Status {
+    /// Static initializer.
+    const fn new() -> Self {
+        Self { overruns: AtomicUsize::new(0), error: AtomicU8::new(NONE) }
+    }
+
+    /// Counts lost words.
+    fn overrun(&self, n: usize) {
+        self.overruns.store(self.overruns.load(Ordering::Relaxed) + n, Ordering::Relaxed);
+    }
+
+    /// Records a line error.
+    fn error(&self, error: UartError) {
+        self.error.store(error as u8, Ordering::Relaxed);
+    }
+
+    /// Returns and clears the line error.
+    fn take_error(&self) -> Option<UartError> {
+        critical_section::with(|_| {
+            let error = decode(self.error.load(Ordering::Relaxed));
+            self.error.store(NONE, Ordering::Relaxed);
+            error
+        })
+    }
+
+    /// Returns and clears the status.
+    fn take(&self) -> UartStatus {
+        critical_section::with(|_| {
+            let status = UartStatus {
+                overruns: self.overruns.load(Ordering::Relaxed),
+                error: decode(self.error.load(Ordering::Relaxed)),
+            };
+
+            self.overruns.store(0, Ordering::Relaxed);
+            self.error.store(NONE, Ordering::Relaxed);
+
+            status
+        })
+    }
+}
+
+/// Marker of no line error.
+const NONE: u8 = 0xFF;
+
+/// Decodes a stored line error.
+fn decode(error: u8) -> Option<UartError> {
+    [
+        UartError::Framing, UartError::Parity, UartError::Overrun, UartError::Noise,
+        UartError::Break, UartError::Unsupported, UartError::Other,
+    ].into_iter().find(|e| *e as u8 == error)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicBool;
+
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use embedded_io::{ Read, Write };
+
+    use super::*;
+    use super::super::{ CommunicationError, UartDriver };
+
+    /// Peripheral with a one word transmitter.
+    #[derive(Default)]
+    struct Fake {
+        /// Words and errors waiting in the receiver.
+        rx: VecDeque<Result<u8, UartError>>,
+
+        /// Words transmitted.
+        tx: Vec<u8>,
+
+        /// Byte rejected by the transmitter with a line error.
+        reject: Option<u8>,
+
+        /// `true` if the transmitter holds a word.
+        holding: bool,
+
+        /// Number of `tx_complete` polls before the last word has been shifted out.
+        shifting: usize,
+
+        /// State of the transmit interrupt.
+        irq: bool,
+    }
+
+    impl CommunicationError for Fake {
+        type Error = UartError;
+    }
+
+    impl UartDriver for Fake {
+        fn configure(&mut self, _: &UartConfig) -> Result<(), UartError> {
+            Ok(())
+        }
+    }
+
+    impl UartIrqDriver for Fake {
+        fn try_recv(&mut self) -> Option<Result<u8, UartError>> {
+            self.rx.pop_front()
+        }
+
+        fn try_send(&mut self, word: u8) -> Result<bool, UartError> {
+            if self.reject == Some(word) { return Err(UartError::Other) }
+            if self.holding { return Ok(false) }
+
+            self.tx.push(word);
+            self.holding = true;
+
+            Ok(true)
+        }
+
+        fn tx_interrupt(&mut self, enable: bool) {
+            self.irq = enable;
+        }
+
+        fn tx_complete(&mut self) -> bool {
+            self.shifting = self.shifting.saturating_sub(1);
+            self.shifting == 0
+        }
+    }
+
+    impl Fake {
+        /// Shifts out the word held by the transmitter.
+        fn shift(uart: &BufferedUart<Fake, 4, 4>) {
+            unsafe { (*uart.uart.get()).holding = false }
+        }
+
+        /// Injects received words.
+        fn receive(uart: &BufferedUart<Fake, 4, 4>, words: &[Result<u8, UartError>]) {
+            unsafe { (*uart.uart.get()).rx.extend(words) }
+        }
+    }
+
+    #[test]
+    fn transmission() {
+        let uart = BufferedUart::<_, 4, 4>::new(Fake::default());
+
+        assert_eq!(uart.try_write(&[1, 2, 3]), 3);
+        assert!(unsafe { (*uart.uart.get()).irq });
+
+        // One word per interrupt, the interrupt is disabled once the ring is empty.
+        for _ in 0..3 {
+            uart.service();
+            Fake::shift(&uart);
+        }
+
+        uart.service();
+
+        let fake = uart.release();
+        assert_eq!(fake.tx, [1, 2, 3]);
+        assert!(!fake.irq);
+    }
+
+    #[test]
+    fn tx_overruns_and_errors() {
+        let uart = BufferedUart::<_, 4, 4>::new(Fake { reject: Some(0xEE), ..Fake::default() });
+
+        assert_eq!(uart.try_write(&[1, 0xEE, 2, 3, 4, 5]), 4);
+        assert_eq!(uart.tx_status(), UartStatus { overruns: 2, error: None });
+
+        uart.service();
+        Fake::shift(&uart);
+        uart.service();
+
+        // The rejected byte is dropped, the reception is unaffected.
+        assert_eq!(uart.tx_status(), UartStatus { overruns: 0, error: Some(UartError::Other) });
+        assert_eq!(uart.tx_status(), UartStatus::default());
+        assert_eq!(uart.rx_status(), UartStatus::default());
+        assert_eq!(uart.release().tx, [1, 2]);
+    }
+
+    #[test]
+    fn rx_overruns_and_errors() {
+        let uart = BufferedUart::<_, 4, 4>::new(Fake::default());
+
+        Fake::receive(&uart, &[Ok(1), Ok(2), Err(UartError::Overrun), Ok(3), Ok(4), Ok(5), Err(UartError::Framing)]);
+        uart.service();
+
+        // One word lost by the peripheral and one by the full ring.
+        assert_eq!(uart.available(), 4);
+        assert_eq!(uart.rx_status(), UartStatus { overruns: 2, error: Some(UartError::Framing) });
+        assert_eq!(uart.rx_status(), UartStatus::default());
+        assert_eq!(uart.tx_status(), UartStatus::default());
+
+        let mut data = [0; 8];
+        assert_eq!(uart.try_read(&mut data), 4);
+        assert_eq!(data[..4], [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn read_returns_the_bytes_before_the_error() {
+        let uart = BufferedUart::<_, 4, 4>::new(Fake::default());
+
+        Fake::receive(&uart, &[Ok(1), Ok(2), Err(UartError::Parity)]);
+        uart.service();
+
+        let mut data = [0; 4];
+        assert_eq!((&uart).read(&mut data), Ok(2));
+        assert_eq!((&uart).read(&mut data), Err(UartError::Parity));
+        assert_eq!((&uart).read(&mut []), Ok(0));
+    }
+
+    #[test]
+    fn flush_waits_for_transmission_complete() {
+        let uart = BufferedUart::<_, 4, 4>::new(Fake { shifting: 3, ..Fake::default() });
+
+        assert_eq!((&uart).write(&[1, 2]), Ok(2));
+
+        uart.service();
+        Fake::shift(&uart);
+        uart.service();
+
+        // The ring is empty but the transmitter is still shifting.
+        assert_eq!((&uart).flush(), Ok(()));
+
+        let fake = uart.release();
+        assert_eq!(fake.tx, [1, 2]);
+        assert_eq!(fake.shifting, 0);
+    }
+
+    #[test]
+    fn blocking_write_waits_for_the_interrupt() {
+        let uart = BufferedUart::<_, 4, 4>::new(Fake::default());
+        let done = AtomicBool::new(false);
+
+        std::thread::scope(|s| {
+            // Interrupt.
+            s.spawn(|| while !done.load(Ordering::Acquire) {
+                uart.service();
+                Fake::shift(&uart);
+            });
+
+            assert_eq!(core::fmt::Write::write_str(&mut &uart, "more than four bytes"), Ok(()));
+            assert_eq!((&uart).flush(), Ok(()));
+
+            done.store(true, Ordering::Release);
+        });
+
+        assert_eq!(uart.release().tx, b"more than four bytes");
+    }
+}
@@ -0,0 +1,137 @@
+//! UART line configuration.
+
+
+
+use core::time::Duration;
+
+
+
+/// Line configuration of a UART.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    /// Baud rate in bits per second.
+    pub baud: u32,
+
+    /// Number of data bits per character.
+    pub data: DataBits,
+
+    /// Parity bit.
+    pub parity: Parity,
+
+    /// Number of stop bits.
+    pub stop: StopBits,
+
+    /// Hardware flow control.
+    pub flow: FlowControl,
+}
+
+impl UartConfig {
+    /// Creates a new 8N1 configuration without flow control at the given baud rate.
+    pub const fn new(baud: u32) -> Self {
+        Self {
+            baud,
+            data: DataBits::Eight,
+            parity: Parity::None,
+            stop: StopBits::One,
+            flow: FlowControl::None,
+        }
+    }
+
+    /// Configures the number of data bits.
+    #[inline]
+    pub const fn databits(mut self, data: DataBits) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Configures the parity bit.
+    #[inline]
+    pub const fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    /// Configures the number of stop bits.
+    #[inline]
+    pub const fn stopbits(mut self, stop: StopBits) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Configures the hardware flow control.
+    #[inline]
+    pub const fn flow(mut self, flow: FlowControl) -> Self {
+        self.flow = flow;
+        self
+    }
+
+    /// Number of bit times of a character, including start, parity and stop bits.
+    /// One and a half stop bits are rounded up.
+    pub const fn character_bits(&self) -> u32 {
+        let parity = match self.parity {
+            Parity::None => 0,
+            _ => 1,
+        };
+
+        let stop = match self.stop {
+            StopBits::One => 1,
+            _ => 2,
+        };
+
+        1 + (self.data as u32) + parity + stop
+    }
+
+    /// Duration of a character on the line, rounded up to the microsecond.
+    pub const fn character_time(&self) -> Duration {
+        let us = (self.character_bits() as u64 * 1_000_000).div_ceil(if self.baud == 0 { 1 } else { self.baud as u64 });
+
+        Duration::from_micros(us)
+    }
+}
+
+impl Default for UartConfig {
+    fn default() -> Self {
+        Self::new(115200)
+    }
+}
+
+
+
+/// Number of data bits per character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    Five  = 5,
+    Six   = 6,
+    Seven = 7,
+    Eight = 8,
+    Nine  = 9,
+}
+
+
+
+/// Parity bit of a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+
+
+/// Number of stop bits of a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    OneAndHalf,
+    Two,
+}
+
+
+
+/// Hardware flow control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowControl {
+    None,
+    RtsCts,
+}
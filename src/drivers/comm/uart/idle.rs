@@ -0,0 +1,276 @@
+//! Software idle line detection.
+//! Implements `UartAsyncFrameDriver` over any asynchronous UART by receiving
+//! one word at a time and treating an inter-byte timeout as an idle line.
+
+
+
+use core::future::Future;
+use core::time::Duration;
+
+use crate::drivers::Data;
+use crate::time::{ with_timeout, TimeSource };
+
+use super::{
+    CommunicationError, UartAsyncBreakDriver, UartAsyncFrameDriver, UartAsyncRxDriver,
+    UartAsyncTxDriver, UartConfig, UartDriver, UartError,
+};
+
+
+
+/// Asynchronous UART with software idle line detection.
+/// The line is considered idle when no word arrives within `gap` of the
+/// previous one. Interrupt and executor latency add to the measured gap, so
+/// it should be at least two character times (see `UartConfig::character_time`).
+pub struct IdleUart<U, S> {
+    /// Wrapped driver.
+    uart: U,
+
+    /// Time source.
+    source: S,
+
+    /// Inter-byte timeout.
+    gap: Duration,
+}
+
+impl<U, S: TimeSource> IdleUart<U, S> {
+    /// Creates the wrapper with the given inter-byte timeout.
+    pub const fn new(uart: U, source: S, gap: Duration) -> Self {
+        Self { uart, source, gap }
+    }
+
+    /// Configures the inter-byte timeout.
+    pub fn gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    /// Consumes the wrapper and returns the driver and the time source.
+    pub fn release(self) -> (U, S) {
+        (self.uart, self.source)
+    }
+
+    /// Receives words until the buffer is full, the delimiter is received or
+    /// (if `idle` is set) the inter-byte timeout elapses.
+    async fn until<W>(&mut self, data: &mut [W], delimiter: Option<W>, idle: bool) -> Result<usize, UartError>
+        where W: Data + PartialEq, U: UartAsyncRxDriver<W>
+    {
+        let mut n = 0;
+
+        while n < data.len() {
+            let word = &mut data[n..n+1];
+
+            if idle && (n > 0) {
+                match with_timeout(&self.source, self.uart.recv(word), self.gap).await {
+                    Ok(result) => result?,
+                    _ => break,
+                }
+            } else {
+                self.uart.recv(word).await?;
+            }
+
+            n += 1;
+
+            if delimiter == Some(data[n-1]) { break }
+        }
+
+        Ok(n)
+    }
+}
+
+impl<U: UartDriver, S> CommunicationError for IdleUart<U, S> {
+    type Error = UartError;
+}
+
+impl<U: UartDriver, S> UartDriver for IdleUart<U, S> {
+    fn configure(&mut self, config: &UartConfig) -> Result<(), Self::Error> {
+        self.uart.configure(config)
+    }
+}
+
+impl<W: Data, U: UartAsyncTxDriver<W>, S> UartAsyncTxDriver<W> for IdleUart<U, S> {
+    type WriteFuture<'a> = U::WriteFuture<'a> where Self: 'a, W: 'a;
+    type FlushFuture<'a> = U::FlushFuture<'a> where Self: 'a, W: 'a;
+
+    fn send<'a>(&'a mut self, data: &'a [W]) -> Self::WriteFuture<'a> {
+        self.uart.send(data)
+    }
+
+    fn flush<'a>(&'a mut self) -> Self::FlushFuture<'a> {
+        self.uart.flush()
+    }
+}
+
+impl<W: Data, U: UartAsyncBreakDriver<W>, S> UartAsyncBreakDriver<W> for IdleUart<U, S> {
+    type BreakFuture<'a> = U::BreakFuture<'a> where Self: 'a, W: 'a;
+
+    fn send_break<'a>(&'a mut self) -> Self::BreakFuture<'a> {
+        self.uart.send_break()
+    }
+}
+
+impl<W: Data, U: UartAsyncRxDriver<W>, S> UartAsyncRxDriver<W> for IdleUart<U, S> {
+    type ReadFuture<'a> = U::ReadFuture<'a> where Self: 'a, W: 'a;
+
+    fn recv<'a>(&'a mut self, data: &'a mut [W]) -> Self::ReadFuture<'a> {
+        self.uart.recv(data)
+    }
+}
+
+impl<W: Data + PartialEq, U: UartAsyncRxDriver<W>, S: TimeSource> UartAsyncFrameDriver<W> for IdleUart<U, S> {
+    type ReadUntilFuture<'a> = impl Future<Output = Result<usize, Self::Error>> + 'a where Self: 'a, W: 'a;
+
+    fn read_until_idle<'a>(&'a mut self, data: &'a mut [W]) -> Self::ReadUntilFuture<'a> {
+        self.until(data, None, true)
+    }
+
+    fn read_until<'a>(&'a mut self, delimiter: W, data: &'a mut [W]) -> Self::ReadUntilFuture<'a> {
+        self.until(data, Some(delimiter), false)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use core::task::{ Context, Poll, Waker };
+
+    use super::*;
+    use super::super::super::mock::{ block_on, MockClock, UartLink };
+
+    const GAP: Duration = Duration::from_micros(100);
+
+    #[test]
+    fn gap_ends_the_frame() {
+        let mut link = UartLink::<16>::default();
+        let (a, mut b) = link.split();
+        let clock = MockClock::new();
+        let mut idle = IdleUart::new(a, &clock, GAP);
+        let mut data = [0; 8];
+
+        {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut frame = pin!(idle.read_until_idle(&mut data));
+
+            assert_eq!(block_on(b.send(&[1, 2])), Ok(()));
+            assert!(frame.as_mut().poll(&mut cx).is_pending());
+
+            // Every word restarts the timeout.
+            clock.advance(Duration::from_micros(99));
+            assert!(frame.as_mut().poll(&mut cx).is_pending());
+
+            assert_eq!(block_on(b.send(&[3])), Ok(()));
+            assert!(frame.as_mut().poll(&mut cx).is_pending());
+
+            clock.advance(Duration::from_micros(99));
+            assert!(frame.as_mut().poll(&mut cx).is_pending());
+
+            clock.advance(Duration::from_micros(1));
+            assert_eq!(frame.as_mut().poll(&mut cx), Poll::Ready(Ok(3)));
+        }
+
+        assert_eq!(data[..3], [1, 2, 3]);
+
+        // The next frame starts with the next word.
+        assert_eq!(block_on(b.send(&[4])), Ok(()));
+
+        {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut frame = pin!(idle.read_until_idle(&mut data));
+
+            assert!(frame.as_mut().poll(&mut cx).is_pending());
+
+            clock.advance(GAP);
+            assert_eq!(frame.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+        }
+
+        assert_eq!(data[0], 4);
+    }
+
+    #[test]
+    fn first_word_waits_for_the_line() {
+        let mut link = UartLink::<16>::default();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(1000);
+        let mut idle = IdleUart::new(a, &clock, GAP);
+        let mut data = [0; 8];
+
+        {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut frame = pin!(idle.read_until_idle(&mut data));
+
+            // Far longer than the gap, without a word.
+            for _ in 0..10 {
+                assert!(frame.as_mut().poll(&mut cx).is_pending());
+            }
+
+            // The clock ticks past the gap right after the word.
+            assert_eq!(block_on(b.send(&[7])), Ok(()));
+            assert_eq!(frame.as_mut().poll(&mut cx), Poll::Ready(Ok(1)));
+        }
+
+        assert_eq!(data[0], 7);
+    }
+
+    #[test]
+    fn delimiter_ends_the_frame() {
+        let mut link = UartLink::<16>::default();
+        let (a, mut b) = link.split();
+        let clock = MockClock::new();
+        let mut idle = IdleUart::new(a, &clock, GAP);
+        let mut data = [0; 8];
+
+        {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut frame = pin!(idle.read_until(b'\n', &mut data));
+
+            assert_eq!(block_on(b.send(b"ab")), Ok(()));
+            assert!(frame.as_mut().poll(&mut cx).is_pending());
+
+            // Delimited reads ignore the gap.
+            clock.advance(Duration::from_secs(1));
+            assert!(frame.as_mut().poll(&mut cx).is_pending());
+
+            assert_eq!(block_on(b.send(b"c\nd")), Ok(()));
+            assert_eq!(frame.as_mut().poll(&mut cx), Poll::Ready(Ok(4)));
+        }
+
+        assert_eq!(&data[..4], b"abc\n");
+        assert_eq!(idle.release().0.pending(), 1);
+    }
+
+    #[test]
+    fn full_buffer_ends_the_frame() {
+        let mut link = UartLink::<16>::default();
+        let (a, mut b) = link.split();
+        let clock = MockClock::new();
+        let mut idle = IdleUart::new(a, &clock, GAP);
+        let mut data = [0; 3];
+
+        assert_eq!(block_on(b.send(&[1, 2, 3, 4, 5, 6, b'\n'])), Ok(()));
+
+        // Both return without waiting for the gap or the delimiter.
+        assert_eq!(block_on(idle.read_until_idle(&mut data)), Ok(3));
+        assert_eq!(data, [1, 2, 3]);
+
+        assert_eq!(block_on(idle.read_until(b'\n', &mut data)), Ok(3));
+        assert_eq!(data, [4, 5, 6]);
+
+        assert_eq!(block_on(idle.read_until(b'\n', &mut data)), Ok(1));
+    }
+
+    #[test]
+    fn break_aborts_the_frame() {
+        let mut link = UartLink::<16>::default();
+        let (a, mut b) = link.split();
+        let clock = MockClock::new();
+        let mut idle = IdleUart::new(a, &clock, GAP);
+        let mut data = [0; 8];
+
+        block_on(async {
+            b.send(&[1]).await.unwrap();
+            b.send_break().await.unwrap();
+        });
+
+        assert_eq!(block_on(idle.read_until_idle(&mut data)), Err(UartError::Break));
+    }
+}
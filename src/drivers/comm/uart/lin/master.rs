@@ -0,0 +1,141 @@
+//! LIN master node.
+
+
+
+use crate::time::{ delay_until, with_timeout, TimeSource };
+
+use super::super::{ UartAsyncBreakDriver, UartAsyncRxDriver, UartConfig };
+use super::{
+    checksum, pid, response_time,
+    Direction, LinError, LinFrame, LinHandler, LinSlot, LIN_DATA_MAX, LIN_SYNC,
+};
+
+
+
+/// LIN master node.
+/// Sends the headers of the bus and runs schedule tables, timed by the time
+/// source. Each slot starts `delay` after the start of the previous one.
+pub struct LinMaster<U, S> {
+    /// Wrapped UART driver.
+    uart: U,
+
+    /// Time source.
+    source: S,
+
+    /// Line configuration, for the response timeouts.
+    config: UartConfig,
+
+    /// Next slot of the schedule table.
+    slot: usize,
+
+    /// Start of the next slot, in microseconds of the time source.
+    next: u64,
+}
+
+impl<U: UartAsyncBreakDriver + UartAsyncRxDriver, S: TimeSource> LinMaster<U, S> {
+    /// Creates a master for a line with the given configuration.
+    pub fn new(uart: U, source: S, config: &UartConfig) -> Self {
+        Self { uart, source, config: *config, slot: 0, next: 0 }
+    }
+
+    /// Consumes the master and returns the UART driver and the time source.
+    pub fn release(self) -> (U, S) {
+        (self.uart, self.source)
+    }
+
+    /// Restarts the schedule from its first slot, e.g. to switch tables.
+    pub fn restart(&mut self) {
+        self.slot = 0;
+        self.next = 0;
+    }
+
+    /// Sends the header and the response of a frame published by the master.
+    pub async fn publish(&mut self, frame: &LinFrame, data: &[u8]) -> Result<(), LinError> {
+        if !frame.is_valid() || (data.len() != frame.len) { return Err(LinError::Length) }
+
+        let pid = self.header(frame.id).await?;
+
+        let mut response = [0; LIN_DATA_MAX + 1];
+        response[..data.len()].copy_from_slice(data);
+        response[data.len()] = checksum(frame.checksum, pid, data);
+
+        self.uart.send(&response[..=data.len()]).await?;
+        self.uart.flush().await?;
+
+        Ok(())
+    }
+
+    /// Sends the header of a frame published by a slave and receives its response.
+    pub async fn request(&mut self, frame: &LinFrame, data: &mut [u8]) -> Result<(), LinError> {
+        if !frame.is_valid() || (data.len() != frame.len) { return Err(LinError::Length) }
+
+        let pid = self.header(frame.id).await?;
+
+        let mut response = [0; LIN_DATA_MAX + 1];
+        let response = &mut response[..=data.len()];
+
+        match with_timeout(&self.source, self.uart.recv(response), response_time(&self.config, data.len())).await {
+            Ok(result) => result?,
+            _ => return Err(LinError::Timeout),
+        }
+
+        if checksum(frame.checksum, pid, &response[..data.len()]) != response[data.len()] { return Err(LinError::Checksum) }
+
+        data.copy_from_slice(&response[..data.len()]);
+
+        Ok(())
+    }
+
+    /// Waits for the next slot of the schedule table and transfers its frame.
+    /// The data of published frames is requested from the handler, and the
+    /// handler receives the response (or the error) of subscribed frames.
+    pub async fn step<H: LinHandler>(&mut self, table: &[LinSlot], handler: &mut H) -> Result<(), LinError> {
+        if table.is_empty() { return Err(LinError::Length) }
+        if self.slot >= table.len() { self.slot = 0 }
+
+        let LinSlot { frame, delay } = table[self.slot];
+
+        // Wait for the start of the slot.
+        let now = self.source.now();
+        delay_until(&self.source, self.next).await;
+
+        self.next = core::cmp::max(now, self.next).saturating_add(delay.as_micros() as u64);
+        self.slot = (self.slot + 1) % table.len();
+
+        let mut data = [0; LIN_DATA_MAX];
+        let data = &mut data[..core::cmp::min(frame.len, LIN_DATA_MAX)];
+
+        match frame.direction {
+            Direction::Publish => {
+                handler.publish(frame.id, data);
+                self.publish(&frame, data).await
+            },
+
+            Direction::Subscribe => {
+                let result = self.request(&frame, data).await;
+                handler.subscribe(frame.id, result.map(|_| &data[..]));
+                result
+            },
+        }
+    }
+
+    /// Runs the schedule table forever. Errors are reported to the handler
+    /// for subscribed frames and otherwise ignored.
+    pub async fn run<H: LinHandler>(&mut self, table: &[LinSlot], handler: &mut H) -> ! {
+        loop {
+            let _ = self.step(table, handler).await;
+        }
+    }
+
+    /// Sends the break, sync and protected identifier of a header.
+    /// Returns the protected identifier.
+    async fn header(&mut self, id: u8) -> Result<u8, LinError> {
+        let pid = pid(id);
+
+        self.uart.send_break().await?;
+        self.uart.send(&[LIN_SYNC, pid]).await?;
+        self.uart.flush().await?;
+
+        Ok(pid)
+    }
+}
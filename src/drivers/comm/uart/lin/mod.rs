@@ -0,0 +1,521 @@
+//! LIN (Local Interconnect Network) protocol layer.
+//! Implements a master node running a schedule table and slave nodes
+//! answering its headers, over asynchronous UART drivers able to generate
+//! breaks (`UartAsyncBreakDriver`) and report them as `UartError::Break`.
+//!
+//! The UART must not receive its own transmissions: transceivers that echo
+//! the bus need the echo suppressed by the driver.
+
+
+
+mod master;
+mod slave;
+
+
+
+use core::time::Duration;
+
+use super::super::{ DriverError, ErrorKind };
+use super::{ UartConfig, UartError };
+
+
+
+pub use self::master::LinMaster;
+pub use self::slave::LinSlave;
+
+
+
+/// Sync byte following the break of a header.
+pub const LIN_SYNC: u8 = 0x55;
+
+/// Maximum length of the data of a frame.
+pub const LIN_DATA_MAX: usize = 8;
+
+/// Identifier of the master request diagnostic frame.
+pub const LIN_MASTER_REQUEST: u8 = 0x3C;
+
+/// Identifier of the slave response diagnostic frame.
+pub const LIN_SLAVE_RESPONSE: u8 = 0x3D;
+
+
+
+/// Errors reported by the LIN layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinError {
+    /// The UART driver reported an error.
+    Uart(UartError),
+
+    /// The response was not received in time.
+    Timeout,
+
+    /// The received checksum does not match the frame.
+    Checksum,
+
+    /// The byte after the break is not the sync byte.
+    Sync,
+
+    /// The parity bits of the protected identifier are wrong.
+    Parity,
+
+    /// The identifier or the data length is out of range.
+    Length,
+}
+
+impl DriverError for LinError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            LinError::Uart(e) => e.kind(),
+            LinError::Timeout => ErrorKind::Timeout,
+            LinError::Checksum | LinError::Sync => ErrorKind::Framing,
+            LinError::Parity => ErrorKind::Parity,
+            LinError::Length => ErrorKind::Other,
+        }
+    }
+}
+
+impl From<UartError> for LinError {
+    fn from(e: UartError) -> Self {
+        LinError::Uart(e)
+    }
+}
+
+
+
+/// Checksum model of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// Data bytes only (LIN 1.x, and diagnostic frames).
+    Classic,
+
+    /// Protected identifier and data bytes (LIN 2.x).
+    Enhanced,
+}
+
+/// Node that transmits the response of a frame, from the point of view of
+/// the local node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The local node transmits the response.
+    Publish,
+
+    /// The local node receives the response.
+    Subscribe,
+}
+
+
+
+/// Frame known to a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinFrame {
+    /// Identifier (0 to 63).
+    pub id: u8,
+
+    /// Length of the data (1 to 8).
+    pub len: usize,
+
+    /// Checksum model.
+    pub checksum: Checksum,
+
+    /// Node that transmits the response.
+    pub direction: Direction,
+}
+
+impl LinFrame {
+    /// Frame with enhanced checksum.
+    pub const fn new(id: u8, len: usize, direction: Direction) -> Self {
+        Self { id, len, checksum: Checksum::Enhanced, direction }
+    }
+
+    /// Configures the checksum model.
+    #[inline]
+    pub const fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Returns `true` if the identifier and the length are in range.
+    pub const fn is_valid(&self) -> bool {
+        (self.id < 64) && (self.len > 0) && (self.len <= LIN_DATA_MAX)
+    }
+}
+
+/// Slot of a schedule table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinSlot {
+    /// Frame transmitted in the slot.
+    pub frame: LinFrame,
+
+    /// Time from the start of the slot to the start of the next one.
+    pub delay: Duration,
+}
+
+
+
+/// Application side of a LIN node.
+pub trait LinHandler {
+    /// Fills the data of a frame published by the node.
+    fn publish(&mut self, id: u8, data: &mut [u8]);
+
+    /// Receives the data of a frame subscribed by the node, or the error
+    /// detected on its response.
+    fn subscribe(&mut self, id: u8, data: Result<&[u8], LinError>);
+}
+
+
+
+/// Protected identifier of an identifier (0 to 63), with its two parity bits.
+pub const fn pid(id: u8) -> u8 {
+    let id = id & 0x3F;
+
+    let p0 = (id ^ (id >> 1) ^ (id >> 2) ^ (id >> 4)) & 1;
+    let p1 = !((id >> 1) ^ (id >> 3) ^ (id >> 4) ^ (id >> 5)) & 1;
+
+    id | (p0 << 6) | (p1 << 7)
+}
+
+/// Identifier of a protected identifier, if its parity bits are correct.
+pub const fn id(pid: u8) -> Option<u8> {
+    match self::pid(pid) == pid {
+        true => Some(pid & 0x3F),
+        _ => None,
+    }
+}
+
+/// Checksum of a frame: inverted sum with carry of the data, and of the
+/// protected identifier with the enhanced model. Diagnostic frames always
+/// use the classic model.
+pub fn checksum(model: Checksum, pid: u8, data: &[u8]) -> u8 {
+    let classic = (model == Checksum::Classic) || matches!(pid & 0x3F, LIN_MASTER_REQUEST | LIN_SLAVE_RESPONSE);
+
+    let init = match classic {
+        true => 0,
+        _ => pid as u16,
+    };
+
+    let sum = data.iter().fold(init, |sum, byte| match sum + *byte as u16 {
+        s if s > 0xFF => s - 0xFF,
+        s => s,
+    });
+
+    !(sum as u8)
+}
+
+/// Maximum duration of a response of `len` data bytes (40 % above its
+/// nominal duration, checksum included).
+pub const fn response_time(config: &UartConfig, len: usize) -> Duration {
+    let nominal = config.character_time().as_micros() as u64 * (len as u64 + 1);
+    Duration::from_micros((nominal * 14).div_ceil(10))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::future::poll_fn;
+    use core::task::Poll;
+    use std::vec::Vec;
+
+    use super::*;
+    use super::super::{ UartAsyncBreakDriver, UartAsyncRxDriver, UartAsyncTxDriver };
+    use super::super::super::mock::{ block_on, join, MockClock, UartLink, UartMock };
+    use crate::time::{ with_timeout, TimeSource };
+
+    /// Frame published by the master.
+    const COMMAND: LinFrame = LinFrame::new(0x10, 4, Direction::Publish);
+
+    /// Frame published by the slave.
+    const STATUS: LinFrame = LinFrame::new(0x11, 2, Direction::Subscribe);
+
+    /// Frames of the slave, seen from its side.
+    const SLAVE: [LinFrame; 2] = [
+        LinFrame::new(0x10, 4, Direction::Subscribe),
+        LinFrame::new(0x11, 2, Direction::Publish),
+    ];
+
+    /// Handler publishing the identifier in every byte and logging subscriptions.
+    #[derive(Default)]
+    struct Log {
+        published: Vec<u8>,
+        received: Vec<(u8, Result<Vec<u8>, LinError>)>,
+    }
+
+    impl LinHandler for Log {
+        fn publish(&mut self, id: u8, data: &mut [u8]) {
+            data.fill(id);
+            self.published.push(id);
+        }
+
+        fn subscribe(&mut self, id: u8, data: Result<&[u8], LinError>) {
+            self.received.push((id, data.map(|d| d.to_vec())));
+        }
+    }
+
+    /// Lets the other future run once.
+    async fn yield_now() {
+        let mut yielded = false;
+
+        poll_fn(|_| match core::mem::replace(&mut yielded, true) {
+            true => Poll::Ready(()),
+            _ => Poll::Pending,
+        }).await
+    }
+
+    /// Receives a header on the raw side of the link. Returns its protected identifier.
+    async fn header(uart: &mut UartMock<'_, 64>) -> u8 {
+        let mut byte = [0; 2];
+
+        assert_eq!(uart.recv(&mut byte[..1]).await, Err(UartError::Break));
+        uart.recv(&mut byte).await.unwrap();
+        assert_eq!(byte[0], LIN_SYNC);
+
+        byte[1]
+    }
+
+    #[test]
+    fn protected_identifiers() {
+        for (id, pid) in [(0x00, 0x80), (0x01, 0xC1), (0x10, 0x50), (0x3C, 0x3C), (0x3D, 0x7D), (0x3F, 0xBF)] {
+            assert_eq!(self::pid(id), pid);
+            assert_eq!(self::id(pid), Some(id));
+        }
+
+        for id in 0..64 {
+            let pid = self::pid(id);
+
+            assert_eq!(self::id(pid ^ 0x40), None);
+            assert_eq!(self::id(pid ^ 0x80), None);
+        }
+    }
+
+    #[test]
+    fn checksums() {
+        let data = [0x55, 0x93, 0xE5];
+
+        assert_eq!(checksum(Checksum::Classic, 0x4A, &data), 0x31);
+        assert_eq!(checksum(Checksum::Enhanced, 0x4A, &data), 0xE6);
+
+        // Diagnostic frames always use the classic model.
+        for id in [LIN_MASTER_REQUEST, LIN_SLAVE_RESPONSE] {
+            assert_eq!(checksum(Checksum::Enhanced, pid(id), &data), 0x31);
+        }
+    }
+
+    #[test]
+    fn publish_and_subscribe() {
+        let mut link = UartLink::<64>::new();
+        let (a, b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut master = LinMaster::new(a, &clock, &UartConfig::new(19200));
+        let mut slave = LinSlave::new(b, &SLAVE);
+        let mut log = Log::default();
+
+        assert_eq!(block_on(join(master.publish(&COMMAND, &[1, 2, 3, 4]), slave.poll(&mut log))), (Ok(()), Ok(0x10)));
+        assert_eq!(log.received, [(0x10, Ok(std::vec![1, 2, 3, 4]))]);
+
+        let mut data = [0; 2];
+        assert_eq!(block_on(join(master.request(&STATUS, &mut data), slave.poll(&mut log))), (Ok(()), Ok(0x11)));
+        assert_eq!(data, [0x11, 0x11]);
+        assert_eq!(log.published, [0x11]);
+
+        // Lengths that do not match the frame.
+        assert_eq!(block_on(master.publish(&COMMAND, &[1, 2])), Err(LinError::Length));
+        assert_eq!(block_on(master.request(&STATUS, &mut [0; 3])), Err(LinError::Length));
+    }
+
+    #[test]
+    fn checksum_model_mismatch() {
+        let mut link = UartLink::<64>::new();
+        let (a, b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut master = LinMaster::new(a, &clock, &UartConfig::new(19200));
+        let mut slave = LinSlave::new(b, &SLAVE);
+        let mut log = Log::default();
+
+        let classic = COMMAND.checksum(Checksum::Classic);
+
+        assert_eq!(block_on(join(master.publish(&classic, &[1, 2, 3, 4]), slave.poll(&mut log))), (Ok(()), Err(LinError::Checksum)));
+        assert_eq!(log.received, [(0x10, Err(LinError::Checksum))]);
+    }
+
+    #[test]
+    fn diagnostic_frames_use_classic_checksum() {
+        let mut link = UartLink::<64>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut master = LinMaster::new(a, &clock, &UartConfig::new(19200));
+        let request = LinFrame::new(LIN_MASTER_REQUEST, 8, Direction::Publish);
+        let data = [0x7F, 0x06, 0xB2, 0x00, 0xFF, 0x7F, 0xFF, 0xFF];
+
+        let (sent, (pid, response)) = block_on(join(master.publish(&request, &data), async {
+            let pid = header(&mut b).await;
+            let mut response = [0; 9];
+            b.recv(&mut response).await.unwrap();
+            (pid, response)
+        }));
+
+        assert_eq!(sent, Ok(()));
+        assert_eq!(pid, 0x3C);
+        assert_eq!(response[..8], data);
+        assert_eq!(response[8], checksum(Checksum::Classic, 0, &data));
+
+        // The slave response is accepted with the classic checksum.
+        let response = LinFrame::new(LIN_SLAVE_RESPONSE, 2, Direction::Subscribe);
+        let mut received = [0; 2];
+
+        let (result, ()) = block_on(join(master.request(&response, &mut received), async {
+            assert_eq!(header(&mut b).await, 0x7D);
+            b.send(&[0x12, 0x34, checksum(Checksum::Classic, 0, &[0x12, 0x34])]).await.unwrap();
+        }));
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(received, [0x12, 0x34]);
+    }
+
+    #[test]
+    fn header_errors() {
+        let mut link = UartLink::<64>::new();
+        let (mut a, b) = link.split();
+
+        let mut slave = LinSlave::new(b, &SLAVE);
+        let mut log = Log::default();
+
+        // Wrong parity bits.
+        block_on(async {
+            a.send_break().await.unwrap();
+            a.send(&[LIN_SYNC, pid(0x10) ^ 0x80]).await.unwrap();
+        });
+
+        assert_eq!(block_on(slave.poll(&mut log)), Err(LinError::Parity));
+
+        // Wrong sync byte.
+        block_on(async {
+            a.send_break().await.unwrap();
+            a.send(&[0x54, pid(0x10)]).await.unwrap();
+        });
+
+        assert_eq!(block_on(slave.poll(&mut log)), Err(LinError::Sync));
+        assert!(log.received.is_empty() && log.published.is_empty());
+    }
+
+    #[test]
+    fn response_timeout() {
+        let mut link = UartLink::<64>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let config = UartConfig::new(19200);
+        let mut master = LinMaster::new(a, &clock, &config);
+        let mut data = [0; 2];
+
+        // No slave answers.
+        let start = clock.now();
+        assert_eq!(block_on(master.request(&STATUS, &mut data)), Err(LinError::Timeout));
+        assert!(clock.now() - start >= response_time(&config, 2).as_micros() as u64);
+
+        // Incomplete response, after the header of the unanswered request.
+        let (result, ()) = block_on(join(master.request(&STATUS, &mut data), async {
+            header(&mut b).await;
+            header(&mut b).await;
+            b.send(&[0x11, 0x11]).await.unwrap();
+        }));
+
+        assert_eq!(result, Err(LinError::Timeout));
+
+        // Frames that are not in the table of the slave are ignored.
+        let (a, _) = master.release();
+        let mut slave = LinSlave::new(b, &SLAVE[..1]);
+        let mut log = Log::default();
+
+        block_on(async {
+            let mut a = a;
+            a.send_break().await.unwrap();
+            a.send(&[LIN_SYNC, pid(0x11)]).await.unwrap();
+        });
+
+        assert!(block_on(with_timeout(&clock, slave.poll(&mut log), Duration::from_millis(10))).is_err());
+        assert!(log.published.is_empty());
+        assert_eq!(slave.release().pending(), 0);
+    }
+
+    #[test]
+    fn slave_resyncs_on_break() {
+        let mut link = UartLink::<64>::new();
+        let (mut a, b) = link.split();
+
+        let mut slave = LinSlave::new(b, &SLAVE);
+        let mut log = Log::default();
+
+        // A response interrupted by a new header.
+        block_on(async {
+            a.send_break().await.unwrap();
+            a.send(&[LIN_SYNC, pid(0x10), 1, 2]).await.unwrap();
+            a.send_break().await.unwrap();
+            a.send(&[LIN_SYNC, pid(0x10), 5, 6, 7, 8, checksum(Checksum::Enhanced, pid(0x10), &[5, 6, 7, 8])]).await.unwrap();
+        });
+
+        assert_eq!(block_on(slave.poll(&mut log)), Ok(0x10));
+        assert_eq!(log.received, [(0x10, Ok(std::vec![5, 6, 7, 8]))]);
+
+        // Bytes before the first break are skipped.
+        block_on(async {
+            a.send(&[0x00, LIN_SYNC, pid(0x11)]).await.unwrap();
+            a.send_break().await.unwrap();
+            a.send(&[LIN_SYNC, pid(0x11)]).await.unwrap();
+        });
+
+        assert_eq!(block_on(slave.poll(&mut log)), Ok(0x11));
+        assert_eq!(log.published, [0x11]);
+    }
+
+    #[test]
+    fn schedule_slots() {
+        let mut link = UartLink::<64>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::new();
+
+        let table = [
+            LinSlot { frame: COMMAND, delay: Duration::from_millis(10) },
+            LinSlot { frame: LinFrame::new(0x12, 1, Direction::Publish), delay: Duration::from_millis(20) },
+        ];
+
+        let mut master = LinMaster::new(a, &clock, &UartConfig::new(19200));
+        let mut log = Log::default();
+
+        let (steps, (starts, ids)) = block_on(join(
+            async {
+                let mut steps = Vec::new();
+                for _ in 0..3 { steps.push(master.step(&table, &mut log).await) }
+                steps
+            },
+            async {
+                let mut starts = Vec::new();
+                let mut ids = Vec::new();
+
+                while starts.len() < 3 {
+                    if b.pending() > 0 {
+                        let at = clock.now();
+                        let id = id(header(&mut b).await).unwrap();
+                        let len = table.iter().find(|slot| slot.frame.id == id).unwrap().frame.len;
+
+                        b.recv(&mut [0; LIN_DATA_MAX + 1][..=len]).await.unwrap();
+
+                        ids.push(id);
+                        starts.push(at);
+                    }
+
+                    clock.advance(Duration::from_micros(100));
+                    yield_now().await;
+                }
+
+                (starts, ids)
+            },
+        ));
+
+        assert_eq!(steps, [Ok(()), Ok(()), Ok(())]);
+        assert_eq!(ids, [0x10, 0x12, 0x10]);
+        assert_eq!(starts, [0, 10_000, 30_000]);
+        assert_eq!(log.published, [0x10, 0x12, 0x10]);
+    }
+}
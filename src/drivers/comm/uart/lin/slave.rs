@@ -0,0 +1,104 @@
+//! LIN slave node.
+
+
+
+use super::super::{ UartAsyncRxDriver, UartAsyncTxDriver, UartError };
+use super::{
+    checksum, id,
+    Direction, LinError, LinFrame, LinHandler, LIN_DATA_MAX, LIN_SYNC,
+};
+
+
+
+/// LIN slave node.
+/// Waits for headers and answers the frames of its table, ignoring the
+/// others. A break received during a response aborts it and starts a new
+/// header, as a master does after a response timeout.
+pub struct LinSlave<'a, U> {
+    /// Wrapped UART driver.
+    uart: U,
+
+    /// Frames published or subscribed by the node.
+    frames: &'a [LinFrame],
+}
+
+impl<'a, U: UartAsyncTxDriver + UartAsyncRxDriver> LinSlave<'a, U> {
+    /// Creates a slave taking part in the given frames.
+    pub const fn new(uart: U, frames: &'a [LinFrame]) -> Self {
+        Self { uart, frames }
+    }
+
+    /// Consumes the slave and returns the UART driver.
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    /// Waits for the header of a frame of the table and handles its response.
+    /// Returns the identifier of the frame.
+    ///
+    /// Errors on the header or on a subscribed response end the call; the
+    /// handler also receives response errors.
+    pub async fn poll<H: LinHandler>(&mut self, handler: &mut H) -> Result<u8, LinError> {
+        let mut byte = [0];
+        let mut synced = false;
+
+        loop {
+            // Wait for a break.
+            while !synced {
+                synced = self.uart.recv(&mut byte).await == Err(UartError::Break);
+            }
+
+            synced = false;
+
+            let mut header = [0; 2];
+
+            match self.uart.recv(&mut header).await {
+                Err(UartError::Break) => { synced = true; continue },
+                result => result?,
+            }
+
+            if header[0] != LIN_SYNC { return Err(LinError::Sync) }
+
+            let Some(id) = id(header[1]) else { return Err(LinError::Parity) };
+            let Some(frame) = self.frames.iter().find(|frame| frame.id == id) else { continue };
+            if !frame.is_valid() { return Err(LinError::Length) }
+
+            let mut response = [0; LIN_DATA_MAX + 1];
+            let response = &mut response[..=frame.len];
+
+            match frame.direction {
+                Direction::Publish => {
+                    handler.publish(id, &mut response[..frame.len]);
+                    response[frame.len] = checksum(frame.checksum, header[1], &response[..frame.len]);
+
+                    self.uart.send(response).await?;
+                    self.uart.flush().await?;
+
+                    return Ok(id);
+                },
+
+                Direction::Subscribe => {
+                    match self.uart.recv(response).await {
+                        Err(UartError::Break) => { synced = true; continue },
+                        Err(e) => {
+                            handler.subscribe(id, Err(e.into()));
+                            return Err(e.into());
+                        },
+                        _ => (),
+                    }
+
+                    let (data, crc) = response.split_at(frame.len);
+
+                    if checksum(frame.checksum, header[1], data) != crc[0] {
+                        handler.subscribe(id, Err(LinError::Checksum));
+                        return Err(LinError::Checksum);
+                    }
+
+                    handler.subscribe(id, Ok(data));
+
+                    return Ok(id);
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! UART Peripheral abstracted driver.
+
+
+
+mod buffered;
+mod config;
+mod idle;
+
+pub mod lin;
+pub mod modbus;
+
+
+
+use core::future::Future;
+
+use crate::drivers::Data;
+
+use super::{ CommunicationError, DriverError, ErrorKind };
+
+
+
+pub use self::buffered::{ BufferedUart, UartStatus };
+pub use self::config::{ DataBits, FlowControl, Parity, StopBits, UartConfig };
+pub use self::idle::IdleUart;
+
+
+
+/// Errors detected on a UART line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartError {
+    /// A character was received without a valid stop bit.
+    Framing,
+
+    /// A character was received with the wrong parity.
+    Parity,
+
+    /// A character was received before the previous one was read.
+    Overrun,
+
+    /// Noise was detected while sampling a character.
+    Noise,
+
+    /// A break condition was detected on the line.
+    Break,
+
+    /// The requested line configuration is not supported by the peripheral.
+    Unsupported,
+
+    /// Error without a UART line equivalent, reported by a wrapped driver.
+    Other,
+}
+
+impl DriverError for UartError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            UartError::Framing | UartError::Break => ErrorKind::Framing,
+            UartError::Parity => ErrorKind::Parity,
+            UartError::Overrun => ErrorKind::Overrun,
+            UartError::Noise => ErrorKind::Bus,
+            UartError::Unsupported | UartError::Other => ErrorKind::Other,
+        }
+    }
+}
+
+
+
+/// UART Driver common trait.
+pub trait UartDriver: CommunicationError<Error = UartError> {
+    /// Applies the given line configuration.
+    fn configure(&mut self, config: &UartConfig) -> Result<(), Self::Error>;
+}
+
+
+/// UART Driver for data transmission.
+/// Generic over the word type, which defaults to bytes (`u16` for 9-bit data).
+pub trait UartTxDriver<W: Data = u8>: UartDriver {
+    /// Sends all the words, blocking until they have been queued for transmission.
+    fn send(&mut self, data: &[W]) -> Result<(), Self::Error>;
+
+    /// Blocks until all the queued words have been transmitted.
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+
+/// UART Driver for data reception.
+/// Generic over the word type, which defaults to bytes (`u16` for 9-bit data).
+pub trait UartRxDriver<W: Data = u8>: UartDriver {
+    /// Receives words until the buffer is full.
+    fn recv(&mut self, data: &mut [W]) -> Result<(), Self::Error>;
+}
+
+
+/// UART Driver with non-blocking access to the data registers, for interrupt
+/// driven operation. Called from the UART interrupt.
+pub trait UartIrqDriver<W: Data = u8>: UartDriver {
+    /// Reads a received word. Returns `None` if no word is available, or the
+    /// line error detected with the word.
+    fn try_recv(&mut self) -> Option<Result<W, UartError>>;
+
+    /// Queues a word for transmission. Returns `Ok(false)` if the transmitter
+    /// cannot accept more words yet.
+    fn try_send(&mut self, word: W) -> Result<bool, UartError>;
+
+    /// Enables / Disables the interrupt raised when the transmitter can accept a word.
+    fn tx_interrupt(&mut self, enable: bool);
+
+    /// Returns `true` once the last queued word has been shifted out on the line.
+    fn tx_complete(&mut self) -> bool;
+}
+
+
+/// UART Driver for data transmission and reception.
+pub trait UartDuplexDriver<W: Data = u8>: UartTxDriver<W> + UartRxDriver<W> {
+    /// Sends and receives data simultaneously.
+    fn send_recv(&mut self, send: &[W], recv: &mut [W]) -> Result<(), Self::Error>;
+
+    /// Sends data and then receives data sequentially.
+    fn send_then_recv(&mut self, send: &[W], recv: &mut [W]) -> Result<(), Self::Error> {
+        self.send(send)?;
+        self.flush()?;
+        self.recv(recv)
+    }
+}
+
+
+
+/// Asynchronous UART Driver for data transmission.
+/// Mirrors the operations of `UartTxDriver`.
+///
+/// The returned futures borrow both the driver and the buffers for `'a`.
+/// Dropping a future before it completes cancels the transfer: before `drop`
+/// returns the implementation must stop the peripheral (and any DMA) from
+/// accessing the buffer. Words already on the line are not recalled.
+pub trait UartAsyncTxDriver<W: Data = u8>: UartDriver {
+    type WriteFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type FlushFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+
+    /// Sends all the words.
+    fn send<'a>(&'a mut self, data: &'a [W]) -> Self::WriteFuture<'a>;
+
+    /// Waits until all the queued words have been transmitted.
+    fn flush<'a>(&'a mut self) -> Self::FlushFuture<'a>;
+}
+
+
+/// Asynchronous UART Driver for data reception.
+/// Mirrors the operations of `UartRxDriver`.
+///
+/// Dropping a future before it completes cancels the reception: before `drop`
+/// returns the implementation must stop the peripheral (and any DMA) from
+/// accessing the buffer. The contents of the buffer are then unspecified.
+pub trait UartAsyncRxDriver<W: Data = u8>: UartDriver {
+    type ReadFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+
+    /// Receives words until the buffer is full.
+    fn recv<'a>(&'a mut self, data: &'a mut [W]) -> Self::ReadFuture<'a>;
+}
+
+
+/// Asynchronous UART Driver for variable length reception.
+///
+/// Both operations wait indefinitely for the first word (wrap them with
+/// `time::with_timeout` to bound the wait) and then store words in the buffer
+/// until their end condition or until the buffer is full. They complete with
+/// the number of words stored.
+///
+/// A line error completes the operation with the error, and the words already
+/// stored are discarded. Words received after completion are not guaranteed
+/// to be kept for the next reception. Dropping the future follows the same
+/// rules as `UartAsyncRxDriver`.
+///
+/// Peripherals without idle line or character match detection can use the
+/// software fallback `IdleUart`, based on an inter-byte timeout.
+pub trait UartAsyncFrameDriver<W: Data = u8>: UartAsyncRxDriver<W> {
+    type ReadUntilFuture<'a>: Future<Output = Result<usize, Self::Error>> + 'a where Self: 'a, W: 'a;
+
+    /// Receives words until the line stays idle for at least one character time.
+    fn read_until_idle<'a>(&'a mut self, data: &'a mut [W]) -> Self::ReadUntilFuture<'a>;
+
+    /// Receives words until the delimiter is received. The delimiter is
+    /// stored in the buffer and counted.
+    fn read_until<'a>(&'a mut self, delimiter: W, data: &'a mut [W]) -> Self::ReadUntilFuture<'a>;
+}
+
+
+/// Asynchronous UART Driver that generates break conditions.
+/// The receiver of the peer reports a break as `UartError::Break`.
+pub trait UartAsyncBreakDriver<W: Data = u8>: UartAsyncTxDriver<W> {
+    type BreakFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+
+    /// Waits until all the queued words have been transmitted, then holds
+    /// the line low for at least 13 bit times.
+    fn send_break<'a>(&'a mut self) -> Self::BreakFuture<'a>;
+}
+
+
+/// Asynchronous UART Driver for data transmission and reception.
+/// Mirrors the operations of `UartDuplexDriver`.
+pub trait UartAsyncDuplexDriver<W: Data = u8>: UartAsyncTxDriver<W> + UartAsyncRxDriver<W> {
+    type SendRecvFuture<'a>: Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+
+    /// Sends and receives data simultaneously.
+    fn send_recv<'a>(&'a mut self, send: &'a [W], recv: &'a mut [W]) -> Self::SendRecvFuture<'a>;
+}
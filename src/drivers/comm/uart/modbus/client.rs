@@ -0,0 +1,249 @@
+//! Modbus RTU client (master).
+
+
+
+use core::time::Duration;
+
+use crate::time::{ delay_until, with_timeout, TimeSource };
+
+use super::super::{ UartAsyncFrameDriver, UartAsyncTxDriver, UartConfig };
+use super::{
+    check, frame_gap, seal, word,
+    Exception, FunctionCode, ModbusError, MODBUS_ADU_MAX, MODBUS_BROADCAST,
+    READ_BITS_MAX, READ_REGISTERS_MAX, READ_WRITE_REGISTERS_MAX,
+    WRITE_BITS_MAX, WRITE_REGISTERS_MAX, COIL_ON,
+};
+
+
+
+/// Modbus RTU client.
+/// Issues one request at a time and waits for the response of the server.
+/// Write requests to `MODBUS_BROADCAST` complete once sent, read requests
+/// are rejected with `ModbusError::Broadcast` as no server answers them.
+pub struct ModbusClient<U, S> {
+    /// Wrapped UART driver.
+    uart: U,
+
+    /// Time source.
+    source: S,
+
+    /// Silence between frames.
+    gap: Duration,
+
+    /// Maximum time to wait for a response.
+    timeout: Duration,
+
+    /// End of the last frame on the line, in microseconds of the time source.
+    last: u64,
+
+    /// Frame buffer.
+    frame: [u8; MODBUS_ADU_MAX],
+}
+
+impl<U: UartAsyncTxDriver + UartAsyncFrameDriver, S: TimeSource> ModbusClient<U, S> {
+    /// Creates a client for a line with the given configuration.
+    /// The response timeout defaults to 1 second.
+    pub fn new(uart: U, source: S, config: &UartConfig) -> Self {
+        Self { uart, source, gap: frame_gap(config), timeout: Duration::from_secs(1), last: 0, frame: [0; MODBUS_ADU_MAX] }
+    }
+
+    /// Configures the maximum time to wait for a response.
+    pub fn timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Consumes the client and returns the UART driver and the time source.
+    pub fn release(self) -> (U, S) {
+        (self.uart, self.source)
+    }
+
+    /// Read Coils (0x01).
+    pub async fn read_coils(&mut self, unit: u8, address: u16, coils: &mut [bool]) -> Result<(), ModbusError> {
+        self.read_bits(unit, FunctionCode::ReadCoils, address, coils).await
+    }
+
+    /// Read Discrete Inputs (0x02).
+    pub async fn read_discrete_inputs(&mut self, unit: u8, address: u16, inputs: &mut [bool]) -> Result<(), ModbusError> {
+        self.read_bits(unit, FunctionCode::ReadDiscreteInputs, address, inputs).await
+    }
+
+    /// Read Holding Registers (0x03).
+    pub async fn read_holding_registers(&mut self, unit: u8, address: u16, registers: &mut [u16]) -> Result<(), ModbusError> {
+        self.read_registers(unit, FunctionCode::ReadHoldingRegisters, address, registers).await
+    }
+
+    /// Read Input Registers (0x04).
+    pub async fn read_input_registers(&mut self, unit: u8, address: u16, registers: &mut [u16]) -> Result<(), ModbusError> {
+        self.read_registers(unit, FunctionCode::ReadInputRegisters, address, registers).await
+    }
+
+    /// Write Single Coil (0x05).
+    pub async fn write_single_coil(&mut self, unit: u8, address: u16, value: bool) -> Result<(), ModbusError> {
+        let value = if value { COIL_ON } else { 0 };
+        let len = self.request(unit, FunctionCode::WriteSingleCoil, &[address, value]);
+
+        self.echo(len).await
+    }
+
+    /// Write Single Register (0x06).
+    pub async fn write_single_register(&mut self, unit: u8, address: u16, value: u16) -> Result<(), ModbusError> {
+        let len = self.request(unit, FunctionCode::WriteSingleRegister, &[address, value]);
+
+        self.echo(len).await
+    }
+
+    /// Write Multiple Coils (0x0F).
+    pub async fn write_multiple_coils(&mut self, unit: u8, address: u16, coils: &[bool]) -> Result<(), ModbusError> {
+        if coils.is_empty() || (coils.len() > WRITE_BITS_MAX) { return Err(ModbusError::Length) }
+
+        let len = self.request(unit, FunctionCode::WriteMultipleCoils, &[address, coils.len() as u16]);
+        let bytes = coils.len().div_ceil(8);
+
+        self.frame[len] = bytes as u8;
+        self.frame[len+1..len+1+bytes].fill(0);
+
+        for (i, coil) in coils.iter().enumerate() {
+            self.frame[len + 1 + (i / 8)] |= (*coil as u8) << (i % 8);
+        }
+
+        self.echo(len + 1 + bytes).await
+    }
+
+    /// Write Multiple Registers (0x10).
+    pub async fn write_multiple_registers(&mut self, unit: u8, address: u16, registers: &[u16]) -> Result<(), ModbusError> {
+        if registers.is_empty() || (registers.len() > WRITE_REGISTERS_MAX) { return Err(ModbusError::Length) }
+
+        let len = self.request(unit, FunctionCode::WriteMultipleRegisters, &[address, registers.len() as u16]);
+        let len = self.values(len, registers);
+
+        self.echo(len).await
+    }
+
+    /// Read / Write Multiple Registers (0x17). The write is performed before the read.
+    pub async fn read_write_multiple_registers(&mut self, unit: u8, read_address: u16, read: &mut [u16], write_address: u16, write: &[u16]) -> Result<(), ModbusError> {
+        if unit == MODBUS_BROADCAST { return Err(ModbusError::Broadcast) }
+        if read.is_empty() || (read.len() > READ_REGISTERS_MAX) { return Err(ModbusError::Length) }
+        if write.is_empty() || (write.len() > READ_WRITE_REGISTERS_MAX) { return Err(ModbusError::Length) }
+
+        let header = [read_address, read.len() as u16, write_address, write.len() as u16];
+        let len = self.request(unit, FunctionCode::ReadWriteMultipleRegisters, &header);
+        let len = self.values(len, write);
+
+        let n = self.transaction(len).await?;
+
+        self.registers(n, read)
+    }
+
+    /// Reads coils or discrete inputs.
+    async fn read_bits(&mut self, unit: u8, function: FunctionCode, address: u16, bits: &mut [bool]) -> Result<(), ModbusError> {
+        if unit == MODBUS_BROADCAST { return Err(ModbusError::Broadcast) }
+        if bits.is_empty() || (bits.len() > READ_BITS_MAX) { return Err(ModbusError::Length) }
+
+        let len = self.request(unit, function, &[address, bits.len() as u16]);
+        let n = self.transaction(len).await?;
+
+        let bytes = bits.len().div_ceil(8);
+        if (n != 3 + bytes) || (self.frame[2] as usize != bytes) { return Err(ModbusError::Malformed) }
+
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = (self.frame[3 + (i / 8)] >> (i % 8)) & 1 != 0;
+        }
+
+        Ok(())
+    }
+
+    /// Reads holding or input registers.
+    async fn read_registers(&mut self, unit: u8, function: FunctionCode, address: u16, registers: &mut [u16]) -> Result<(), ModbusError> {
+        if unit == MODBUS_BROADCAST { return Err(ModbusError::Broadcast) }
+        if registers.is_empty() || (registers.len() > READ_REGISTERS_MAX) { return Err(ModbusError::Length) }
+
+        let len = self.request(unit, function, &[address, registers.len() as u16]);
+        let n = self.transaction(len).await?;
+
+        self.registers(n, registers)
+    }
+
+    /// Sends a write request, whose response echoes the address and the
+    /// value or quantity.
+    async fn echo(&mut self, len: usize) -> Result<(), ModbusError> {
+        let mut request = [0u8; 6];
+        request.copy_from_slice(&self.frame[..6]);
+
+        let n = self.transaction(len).await?;
+
+        match n == 0 || self.frame[..n] == request {
+            true => Ok(()),
+            _ => Err(ModbusError::Malformed),
+        }
+    }
+
+    /// Builds the start of a request. Returns its length.
+    fn request(&mut self, unit: u8, function: FunctionCode, header: &[u16]) -> usize {
+        self.frame[0] = unit;
+        self.frame[1] = function as u8;
+
+        for (i, value) in header.iter().enumerate() {
+            self.frame[2 + (2 * i)..4 + (2 * i)].copy_from_slice(&value.to_be_bytes());
+        }
+
+        2 + (2 * header.len())
+    }
+
+    /// Appends the byte count and the register values to the request.
+    /// Returns its length.
+    fn values(&mut self, len: usize, registers: &[u16]) -> usize {
+        self.frame[len] = (2 * registers.len()) as u8;
+
+        for (i, value) in registers.iter().enumerate() {
+            self.frame[len + 1 + (2 * i)..len + 3 + (2 * i)].copy_from_slice(&value.to_be_bytes());
+        }
+
+        len + 1 + (2 * registers.len())
+    }
+
+    /// Decodes the registers of a read response of `n` bytes.
+    fn registers(&self, n: usize, registers: &mut [u16]) -> Result<(), ModbusError> {
+        let bytes = 2 * registers.len();
+        if (n != 3 + bytes) || (self.frame[2] as usize != bytes) { return Err(ModbusError::Malformed) }
+
+        for (i, register) in registers.iter_mut().enumerate() {
+            *register = word(&self.frame, 3 + (2 * i));
+        }
+
+        Ok(())
+    }
+
+    /// Sends the request of `len` bytes and receives the response.
+    /// Returns the length of the response without CRC, or 0 for broadcasts.
+    async fn transaction(&mut self, len: usize) -> Result<usize, ModbusError> {
+        let unit = self.frame[0];
+        let function = self.frame[1];
+
+        let len = seal(&mut self.frame, len);
+
+        // Keep the line silent between frames.
+        delay_until(&self.source, self.last.saturating_add(self.gap.as_micros() as u64)).await;
+
+        self.uart.send(&self.frame[..len]).await?;
+        self.uart.flush().await?;
+        self.last = self.source.now();
+
+        if unit == MODBUS_BROADCAST { return Ok(0) }
+
+        let n = match with_timeout(&self.source, self.uart.read_until_idle(&mut self.frame), self.timeout).await {
+            Ok(result) => result?,
+            _ => return Err(ModbusError::Timeout),
+        };
+
+        self.last = self.source.now();
+
+        if !check(&self.frame[..n]) { return Err(ModbusError::Crc) }
+        if self.frame[0] != unit { return Err(ModbusError::Malformed) }
+
+        match self.frame[1] {
+            f if f == function => Ok(n - 2),
+            f if (f == function | 0x80) && (n == 5) => Err(ModbusError::Exception(Exception::from_code(self.frame[2]))),
+            _ => Err(ModbusError::Malformed),
+        }
+    }
+}
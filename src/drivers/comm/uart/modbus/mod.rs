@@ -0,0 +1,507 @@
+//! Modbus RTU protocol layer.
+//! Implements a client (master) and a server (slave) over asynchronous UART
+//! drivers. Frames are delimited by 3.5 character times of silence, which the
+//! driver detects with `read_until_idle` (use `IdleUart` with `frame_gap` on
+//! peripherals without idle line detection).
+
+
+
+mod client;
+mod server;
+
+
+
+use core::time::Duration;
+
+use crate::crc::{ Crc, CRC_16_MODBUS };
+
+use super::super::{ DriverError, ErrorKind };
+use super::{ UartConfig, UartError };
+
+
+
+pub use self::client::ModbusClient;
+pub use self::server::{ ModbusServer, RegisterMap };
+
+
+
+/// Maximum length of a frame (address, PDU and CRC).
+pub const MODBUS_ADU_MAX: usize = 256;
+
+/// Broadcast address. Requests to it are executed by all servers without response.
+pub const MODBUS_BROADCAST: u8 = 0;
+
+
+
+/// Errors reported by the Modbus layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModbusError {
+    /// The UART driver reported an error.
+    Uart(UartError),
+
+    /// No response was received in time.
+    Timeout,
+
+    /// The received CRC does not match the frame.
+    Crc,
+
+    /// The frame is too short or does not match the request.
+    Malformed,
+
+    /// The request exceeds the quantities allowed by the protocol.
+    Length,
+
+    /// A read request was addressed to `MODBUS_BROADCAST`.
+    Broadcast,
+
+    /// The server answered with an exception.
+    Exception(Exception),
+}
+
+impl DriverError for ModbusError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            ModbusError::Uart(e) => e.kind(),
+            ModbusError::Timeout => ErrorKind::Timeout,
+            ModbusError::Crc | ModbusError::Malformed => ErrorKind::Framing,
+            ModbusError::Length | ModbusError::Broadcast | ModbusError::Exception(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl From<UartError> for ModbusError {
+    fn from(e: UartError) -> Self {
+        ModbusError::Uart(e)
+    }
+}
+
+
+
+/// Exception codes of a Modbus response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    IllegalFunction,
+    IllegalDataAddress,
+    IllegalDataValue,
+    ServerDeviceFailure,
+    Acknowledge,
+    ServerDeviceBusy,
+    MemoryParityError,
+    GatewayPathUnavailable,
+    GatewayTargetFailed,
+
+    /// Exception code not defined by the specification.
+    Unknown(u8),
+}
+
+impl Exception {
+    /// Code of the exception on the wire.
+    pub const fn code(&self) -> u8 {
+        match self {
+            Exception::IllegalFunction => 0x01,
+            Exception::IllegalDataAddress => 0x02,
+            Exception::IllegalDataValue => 0x03,
+            Exception::ServerDeviceFailure => 0x04,
+            Exception::Acknowledge => 0x05,
+            Exception::ServerDeviceBusy => 0x06,
+            Exception::MemoryParityError => 0x08,
+            Exception::GatewayPathUnavailable => 0x0A,
+            Exception::GatewayTargetFailed => 0x0B,
+            Exception::Unknown(code) => *code,
+        }
+    }
+
+    /// Exception of the given code.
+    pub const fn from_code(code: u8) -> Self {
+        match code {
+            0x01 => Exception::IllegalFunction,
+            0x02 => Exception::IllegalDataAddress,
+            0x03 => Exception::IllegalDataValue,
+            0x04 => Exception::ServerDeviceFailure,
+            0x05 => Exception::Acknowledge,
+            0x06 => Exception::ServerDeviceBusy,
+            0x08 => Exception::MemoryParityError,
+            0x0A => Exception::GatewayPathUnavailable,
+            0x0B => Exception::GatewayTargetFailed,
+            code => Exception::Unknown(code),
+        }
+    }
+}
+
+
+
+/// Supported function codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FunctionCode {
+    ReadCoils = 0x01,
+    ReadDiscreteInputs = 0x02,
+    ReadHoldingRegisters = 0x03,
+    ReadInputRegisters = 0x04,
+    WriteSingleCoil = 0x05,
+    WriteSingleRegister = 0x06,
+    WriteMultipleCoils = 0x0F,
+    WriteMultipleRegisters = 0x10,
+    ReadWriteMultipleRegisters = 0x17,
+}
+
+impl FunctionCode {
+    /// Function of the given code, if supported.
+    pub const fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x01 => Some(FunctionCode::ReadCoils),
+            0x02 => Some(FunctionCode::ReadDiscreteInputs),
+            0x03 => Some(FunctionCode::ReadHoldingRegisters),
+            0x04 => Some(FunctionCode::ReadInputRegisters),
+            0x05 => Some(FunctionCode::WriteSingleCoil),
+            0x06 => Some(FunctionCode::WriteSingleRegister),
+            0x0F => Some(FunctionCode::WriteMultipleCoils),
+            0x10 => Some(FunctionCode::WriteMultipleRegisters),
+            0x17 => Some(FunctionCode::ReadWriteMultipleRegisters),
+            _ => None,
+        }
+    }
+}
+
+
+
+/// Maximum number of coils or discrete inputs read in a request.
+const READ_BITS_MAX: usize = 2000;
+
+/// Maximum number of registers read in a request.
+const READ_REGISTERS_MAX: usize = 125;
+
+/// Maximum number of coils written in a request.
+const WRITE_BITS_MAX: usize = 1968;
+
+/// Maximum number of registers written in a request.
+const WRITE_REGISTERS_MAX: usize = 123;
+
+/// Maximum number of registers written in a read / write request.
+const READ_WRITE_REGISTERS_MAX: usize = 121;
+
+/// Value of a coil set to ON in a Write Single Coil request.
+const COIL_ON: u16 = 0xFF00;
+
+/// CRC of the frames.
+static CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_MODBUS);
+
+
+
+/// Silence that delimits frames (3.5 character times).
+/// Fixed to 1.75 ms above 19200 baud, as recommended by the specification.
+pub const fn frame_gap(config: &UartConfig) -> Duration {
+    match config.baud > 19200 {
+        true => Duration::from_micros(1750),
+        _ => Duration::from_micros((config.character_time().as_micros() as u64 * 7).div_ceil(2)),
+    }
+}
+
+
+
+/// Appends the CRC to the first `len` bytes of the frame.
+/// Returns the length of the sealed frame.
+fn seal(frame: &mut [u8], len: usize) -> usize {
+    let [lo, hi] = CRC.checksum(&frame[..len]).to_le_bytes();
+
+    frame[len] = lo;
+    frame[len + 1] = hi;
+
+    len + 2
+}
+
+/// Returns `true` if the frame is long enough and its CRC matches.
+fn check(frame: &[u8]) -> bool {
+    match frame.len() {
+        0..=3 => false,
+        n => CRC.checksum(&frame[..n-2]).to_le_bytes() == frame[n-2..],
+    }
+}
+
+/// Reads a big endian word.
+fn word(data: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([data[i], data[i+1]])
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{ Context, Poll, Waker };
+
+    use super::*;
+    use super::super::{ UartAsyncFrameDriver, UartAsyncTxDriver };
+    use super::super::super::mock::{ block_on, join, MockClock, UartLink };
+
+    const UNIT: u8 = 7;
+
+    /// Register map of the tests.
+    struct Map {
+        coils: [bool; 20],
+        inputs: [bool; 4],
+        holding: [u16; 32],
+        input: [u16; 4],
+    }
+
+    impl Map {
+        fn new() -> Self {
+            Self { coils: [false; 20], inputs: [true, false, false, true], holding: [0; 32], input: [1, 2, 3, 4] }
+        }
+    }
+
+    impl RegisterMap for Map {
+        fn read_coil(&mut self, address: u16) -> Result<bool, Exception> {
+            self.coils.get(address as usize).copied().ok_or(Exception::IllegalDataAddress)
+        }
+
+        fn read_discrete_input(&mut self, address: u16) -> Result<bool, Exception> {
+            self.inputs.get(address as usize).copied().ok_or(Exception::IllegalDataAddress)
+        }
+
+        fn read_holding_registers(&mut self, address: u16, registers: &mut [u16]) -> Result<(), Exception> {
+            let a = address as usize;
+            registers.copy_from_slice(self.holding.get(a..a + registers.len()).ok_or(Exception::IllegalDataAddress)?);
+            Ok(())
+        }
+
+        fn read_input_registers(&mut self, address: u16, registers: &mut [u16]) -> Result<(), Exception> {
+            let a = address as usize;
+            registers.copy_from_slice(self.input.get(a..a + registers.len()).ok_or(Exception::IllegalDataAddress)?);
+            Ok(())
+        }
+
+        fn write_coil(&mut self, address: u16, value: bool) -> Result<(), Exception> {
+            *self.coils.get_mut(address as usize).ok_or(Exception::IllegalDataAddress)? = value;
+            Ok(())
+        }
+
+        fn write_registers(&mut self, address: u16, registers: &[u16]) -> Result<(), Exception> {
+            let a = address as usize;
+            self.holding.get_mut(a..a + registers.len()).ok_or(Exception::IllegalDataAddress)?.copy_from_slice(registers);
+            Ok(())
+        }
+    }
+
+    /// Runs a client request against one poll of the server.
+    macro_rules! exchange {
+        ($server:expr, $map:expr, $request:expr) => {{
+            let (response, poll) = block_on(join($request, $server.poll($map)));
+            assert_eq!(poll, Ok(()));
+            response
+        }};
+    }
+
+    #[test]
+    fn frame_gap_at_9600() {
+        assert_eq!(frame_gap(&UartConfig::new(9600)), Duration::from_micros(3647));
+    }
+
+    #[test]
+    fn request_frame_matches_reference() {
+        let mut link = UartLink::<64>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut client = ModbusClient::new(a, &clock, &UartConfig::new(115200));
+        client.timeout(Duration::from_millis(1));
+
+        assert_eq!(block_on(client.read_holding_registers(1, 0, &mut [0; 10])), Err(ModbusError::Timeout));
+
+        let mut frame = [0u8; 16];
+        let n = block_on(b.read_until_idle(&mut frame)).unwrap();
+        assert_eq!(frame[..n], [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]);
+    }
+
+    #[test]
+    fn function_codes() {
+        let mut link = UartLink::<512>::new();
+        let (a, b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut client = ModbusClient::new(a, &clock, &UartConfig::new(9600));
+        client.timeout(Duration::from_millis(50));
+        let mut server = ModbusServer::new(b, UNIT);
+        let mut map = Map::new();
+
+        // 0x06 Write Single Register, 0x10 Write Multiple Registers, 0x03 Read Holding Registers.
+        assert_eq!(exchange!(server, &mut map, client.write_single_register(UNIT, 3, 0xBEEF)), Ok(()));
+        assert_eq!(exchange!(server, &mut map, client.write_multiple_registers(UNIT, 4, &[1, 2, 3])), Ok(()));
+
+        let mut registers = [0u16; 5];
+        assert_eq!(exchange!(server, &mut map, client.read_holding_registers(UNIT, 2, &mut registers)), Ok(()));
+        assert_eq!(registers, [0, 0xBEEF, 1, 2, 3]);
+
+        // 0x04 Read Input Registers.
+        let mut registers = [0u16; 2];
+        assert_eq!(exchange!(server, &mut map, client.read_input_registers(UNIT, 2, &mut registers)), Ok(()));
+        assert_eq!(registers, [3, 4]);
+
+        // 0x05 Write Single Coil, 0x0F Write Multiple Coils, 0x01 Read Coils.
+        assert_eq!(exchange!(server, &mut map, client.write_single_coil(UNIT, 2, true)), Ok(()));
+
+        let written = [true, false, true, true, false, false, false, false, true, true];
+        assert_eq!(exchange!(server, &mut map, client.write_multiple_coils(UNIT, 8, &written)), Ok(()));
+        assert_eq!(map.coils[8..18], written);
+
+        let mut coils = [false; 19];
+        assert_eq!(exchange!(server, &mut map, client.read_coils(UNIT, 1, &mut coils)), Ok(()));
+        assert_eq!(coils, [
+            false, true, false, false, false, false, false, true, false, true,
+            true, false, false, false, false, true, true, false, false,
+        ]);
+
+        // 0x02 Read Discrete Inputs.
+        let mut inputs = [false; 4];
+        assert_eq!(exchange!(server, &mut map, client.read_discrete_inputs(UNIT, 0, &mut inputs)), Ok(()));
+        assert_eq!(inputs, [true, false, false, true]);
+
+        // 0x17 Read/Write Multiple Registers: the write happens before the read.
+        let mut registers = [0u16; 3];
+        assert_eq!(exchange!(server, &mut map, client.read_write_multiple_registers(UNIT, 0, &mut registers, 1, &[9, 8])), Ok(()));
+        assert_eq!(registers, [0, 9, 8]);
+    }
+
+    #[test]
+    fn exception_responses() {
+        let mut link = UartLink::<512>::new();
+        let (a, b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut client = ModbusClient::new(a, &clock, &UartConfig::new(9600));
+        client.timeout(Duration::from_millis(50));
+        let mut server = ModbusServer::new(b, UNIT);
+        let mut map = Map::new();
+
+        assert_eq!(
+            exchange!(server, &mut map, client.read_holding_registers(UNIT, 31, &mut [0; 2])),
+            Err(ModbusError::Exception(Exception::IllegalDataAddress)),
+        );
+
+        // Ranges past the end of the address space.
+        assert_eq!(
+            exchange!(server, &mut map, client.read_holding_registers(UNIT, 0xFFFF, &mut [0; 2])),
+            Err(ModbusError::Exception(Exception::IllegalDataAddress)),
+        );
+
+        // Accesses the map does not implement.
+        struct Empty;
+        impl RegisterMap for Empty {}
+
+        assert_eq!(
+            exchange!(server, &mut Empty, client.read_coils(UNIT, 0, &mut [false; 2])),
+            Err(ModbusError::Exception(Exception::IllegalFunction)),
+        );
+
+        // Quantities over the protocol limit are rejected before sending.
+        assert_eq!(block_on(client.read_holding_registers(UNIT, 0, &mut [0; 126])), Err(ModbusError::Length));
+    }
+
+    #[test]
+    fn broadcast_and_other_units() {
+        let mut link = UartLink::<512>::new();
+        let (a, b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut client = ModbusClient::new(a, &clock, &UartConfig::new(9600));
+        client.timeout(Duration::from_millis(50));
+        let mut server = ModbusServer::new(b, UNIT);
+        let mut map = Map::new();
+
+        // Executed by the server without response.
+        assert_eq!(exchange!(server, &mut map, client.write_single_register(MODBUS_BROADCAST, 0, 0x55)), Ok(()));
+        assert_eq!(map.holding[0], 0x55);
+
+        // Reads are rejected before sending, as no server answers them.
+        assert_eq!(block_on(client.read_holding_registers(MODBUS_BROADCAST, 0, &mut [0; 1])), Err(ModbusError::Broadcast));
+        assert_eq!(block_on(client.read_coils(MODBUS_BROADCAST, 0, &mut [false; 1])), Err(ModbusError::Broadcast));
+        assert_eq!(
+            block_on(client.read_write_multiple_registers(MODBUS_BROADCAST, 0, &mut [0; 1], 0, &[1])),
+            Err(ModbusError::Broadcast),
+        );
+
+        let (a, b) = (client.release().0, server.release());
+        assert_eq!((a.pending(), b.pending()), (0, 0));
+
+        // Requests to other units are ignored and time out.
+        let mut client = ModbusClient::new(a, &clock, &UartConfig::new(9600));
+        client.timeout(Duration::from_millis(50));
+        let mut server = ModbusServer::new(b, UNIT);
+
+        assert_eq!(exchange!(server, &mut map, client.write_single_register(8, 0, 1)), Err(ModbusError::Timeout));
+        assert_eq!(map.holding[0], 0x55);
+    }
+
+    #[test]
+    fn requests_are_separated_by_the_frame_gap() {
+        let mut link = UartLink::<64>::new();
+        let (a, b) = link.split();
+        let clock = MockClock::new();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut client = ModbusClient::new(a, &clock, &UartConfig::new(9600));
+        clock.advance(Duration::from_millis(10));
+
+        assert_eq!(block_on(client.write_single_register(MODBUS_BROADCAST, 0, 1)), Ok(()));
+        assert_eq!(b.pending(), 8);
+
+        {
+            let mut request = pin!(client.write_single_register(MODBUS_BROADCAST, 0, 2));
+
+            // 3.5 characters at 9600 baud after the end of the previous frame.
+            assert!(request.as_mut().poll(&mut cx).is_pending());
+
+            clock.advance(Duration::from_micros(3646));
+            assert!(request.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(b.pending(), 8);
+
+            clock.advance(Duration::from_micros(1));
+            assert_eq!(request.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(b.pending(), 16);
+        }
+    }
+
+    #[test]
+    fn corrupted_request_is_dropped() {
+        let mut link = UartLink::<64>::new();
+        let (a, b) = link.split();
+
+        // Read Holding Registers with the last CRC byte flipped.
+        b.inject(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCC]);
+
+        let mut server = ModbusServer::new(b, 1);
+        let mut map = Map::new();
+
+        assert_eq!(block_on(server.poll(&mut map)), Err(ModbusError::Crc));
+        assert_eq!(a.pending(), 0);
+    }
+
+    #[test]
+    fn corrupted_response_is_reported() {
+        let mut link = UartLink::<64>::new();
+        let (a, mut b) = link.split();
+        let clock = MockClock::ticking(10);
+
+        let mut client = ModbusClient::new(a, &clock, &UartConfig::new(115200));
+        client.timeout(Duration::from_millis(50));
+
+        // Answers the request with a corrupted CRC.
+        let peer = async {
+            let mut frame = [0u8; 16];
+            let n = b.read_until_idle(&mut frame).await.unwrap();
+
+            let mut response = [0x01, 0x03, 0x02, 0x12, 0x34, 0, 0];
+            seal(&mut response, 5);
+            response[6] ^= 0x01;
+
+            b.send(&response).await.unwrap();
+            n
+        };
+
+        let (result, n) = block_on(join(client.read_holding_registers(1, 0, &mut [0; 1]), peer));
+
+        assert_eq!(n, 8);
+        assert_eq!(result, Err(ModbusError::Crc));
+    }
+}
@@ -0,0 +1,283 @@
+//! Modbus RTU server (slave).
+
+
+
+use super::super::{ UartAsyncFrameDriver, UartAsyncTxDriver };
+use super::{
+    check, seal, word,
+    Exception, FunctionCode, ModbusError, MODBUS_ADU_MAX, MODBUS_BROADCAST,
+    READ_BITS_MAX, READ_REGISTERS_MAX, READ_WRITE_REGISTERS_MAX,
+    WRITE_BITS_MAX, WRITE_REGISTERS_MAX, COIL_ON,
+};
+
+
+
+/// Data model of a Modbus server.
+/// Each method answers one kind of access; the default implementations
+/// reject it with `IllegalFunction`. Ranges have already been checked
+/// against the protocol limits and the 16-bit address space.
+pub trait RegisterMap {
+    /// Reads a coil.
+    fn read_coil(&mut self, address: u16) -> Result<bool, Exception> {
+        let _ = address;
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Reads a discrete input.
+    fn read_discrete_input(&mut self, address: u16) -> Result<bool, Exception> {
+        let _ = address;
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Reads consecutive holding registers.
+    fn read_holding_registers(&mut self, address: u16, registers: &mut [u16]) -> Result<(), Exception> {
+        let _ = (address, registers);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Reads consecutive input registers.
+    fn read_input_registers(&mut self, address: u16, registers: &mut [u16]) -> Result<(), Exception> {
+        let _ = (address, registers);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Writes a coil.
+    fn write_coil(&mut self, address: u16, value: bool) -> Result<(), Exception> {
+        let _ = (address, value);
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Writes consecutive holding registers.
+    fn write_registers(&mut self, address: u16, registers: &[u16]) -> Result<(), Exception> {
+        let _ = (address, registers);
+        Err(Exception::IllegalFunction)
+    }
+}
+
+
+
+/// Modbus RTU server.
+/// Answers the requests addressed to its unit and executes broadcasts
+/// silently. Frames with a wrong CRC are dropped without answer.
+pub struct ModbusServer<U> {
+    /// Wrapped UART driver.
+    uart: U,
+
+    /// Unit address.
+    unit: u8,
+
+    /// Received request.
+    request: [u8; MODBUS_ADU_MAX],
+
+    /// Response being sent.
+    response: [u8; MODBUS_ADU_MAX],
+}
+
+impl<U: UartAsyncTxDriver + UartAsyncFrameDriver> ModbusServer<U> {
+    /// Creates a server answering to the given unit address.
+    pub const fn new(uart: U, unit: u8) -> Self {
+        Self { uart, unit, request: [0; MODBUS_ADU_MAX], response: [0; MODBUS_ADU_MAX] }
+    }
+
+    /// Consumes the server and returns the UART driver.
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    /// Receives a frame and, if addressed to this server, executes it on the
+    /// register map and answers. Should be called in a loop.
+    pub async fn poll<M: RegisterMap>(&mut self, map: &mut M) -> Result<(), ModbusError> {
+        let n = self.uart.read_until_idle(&mut self.request).await?;
+
+        if n < 4 { return Err(ModbusError::Malformed) }
+        if !check(&self.request[..n]) { return Err(ModbusError::Crc) }
+
+        let unit = self.request[0];
+        if (unit != self.unit) && (unit != MODBUS_BROADCAST) { return Ok(()) }
+
+        let function = self.request[1];
+
+        let len = match dispatch(map, &self.request[1..n-2], &mut self.response[1..]) {
+            Ok(len) => len,
+            Err(exception) => {
+                self.response[1] = function | 0x80;
+                self.response[2] = exception.code();
+                2
+            },
+        };
+
+        if unit == MODBUS_BROADCAST { return Ok(()) }
+
+        self.response[0] = self.unit;
+        let len = seal(&mut self.response, 1 + len);
+
+        self.uart.send(&self.response[..len]).await?;
+        self.uart.flush().await?;
+
+        Ok(())
+    }
+}
+
+
+
+/// Executes the request PDU on the register map and writes the response PDU.
+/// Returns the length of the response.
+fn dispatch<M: RegisterMap>(map: &mut M, request: &[u8], response: &mut [u8]) -> Result<usize, Exception> {
+    let function = FunctionCode::from_code(request[0]).ok_or(Exception::IllegalFunction)?;
+
+    // All supported requests start with an address and a quantity / value.
+    if request.len() < 5 { return Err(Exception::IllegalDataValue) }
+
+    let address = word(request, 1);
+    let value = word(request, 3);
+    let count = value as usize;
+
+    response[0] = request[0];
+
+    match function {
+        FunctionCode::ReadCoils | FunctionCode::ReadDiscreteInputs => {
+            quantity(request, 5, count, READ_BITS_MAX)?;
+            range(address, count)?;
+
+            let bytes = count.div_ceil(8);
+            response[1] = bytes as u8;
+            response[2..2 + bytes].fill(0);
+
+            for i in 0..count {
+                let a = address + i as u16;
+
+                let bit = match function {
+                    FunctionCode::ReadCoils => map.read_coil(a)?,
+                    _ => map.read_discrete_input(a)?,
+                };
+
+                response[2 + (i / 8)] |= (bit as u8) << (i % 8);
+            }
+
+            Ok(2 + bytes)
+        },
+
+        FunctionCode::ReadHoldingRegisters | FunctionCode::ReadInputRegisters => {
+            quantity(request, 5, count, READ_REGISTERS_MAX)?;
+            range(address, count)?;
+
+            let mut registers = [0u16; READ_REGISTERS_MAX];
+
+            match function {
+                FunctionCode::ReadHoldingRegisters => map.read_holding_registers(address, &mut registers[..count])?,
+                _ => map.read_input_registers(address, &mut registers[..count])?,
+            }
+
+            Ok(1 + registers_out(&registers[..count], &mut response[1..]))
+        },
+
+        FunctionCode::WriteSingleCoil => {
+            if request.len() != 5 { return Err(Exception::IllegalDataValue) }
+
+            let coil = match value {
+                COIL_ON => true,
+                0 => false,
+                _ => return Err(Exception::IllegalDataValue),
+            };
+
+            map.write_coil(address, coil)?;
+
+            response[..5].copy_from_slice(request);
+            Ok(5)
+        },
+
+        FunctionCode::WriteSingleRegister => {
+            if request.len() != 5 { return Err(Exception::IllegalDataValue) }
+
+            map.write_registers(address, &[value])?;
+
+            response[..5].copy_from_slice(request);
+            Ok(5)
+        },
+
+        FunctionCode::WriteMultipleCoils => {
+            let bytes = count.div_ceil(8);
+
+            quantity(request, 6 + bytes, count, WRITE_BITS_MAX)?;
+            if request[5] as usize != bytes { return Err(Exception::IllegalDataValue) }
+            range(address, count)?;
+
+            for i in 0..count {
+                map.write_coil(address + i as u16, (request[6 + (i / 8)] >> (i % 8)) & 1 != 0)?;
+            }
+
+            response[..5].copy_from_slice(&request[..5]);
+            Ok(5)
+        },
+
+        FunctionCode::WriteMultipleRegisters => {
+            quantity(request, 6 + (2 * count), count, WRITE_REGISTERS_MAX)?;
+            if request[5] as usize != 2 * count { return Err(Exception::IllegalDataValue) }
+            range(address, count)?;
+
+            let mut registers = [0u16; WRITE_REGISTERS_MAX];
+            registers_in(&request[6..], &mut registers[..count]);
+
+            map.write_registers(address, &registers[..count])?;
+
+            response[..5].copy_from_slice(&request[..5]);
+            Ok(5)
+        },
+
+        FunctionCode::ReadWriteMultipleRegisters => {
+            if request.len() < 10 { return Err(Exception::IllegalDataValue) }
+
+            let waddress = word(request, 5);
+            let wcount = word(request, 7) as usize;
+
+            quantity(request, 10 + (2 * wcount), count, READ_REGISTERS_MAX)?;
+            quantity(request, 10 + (2 * wcount), wcount, READ_WRITE_REGISTERS_MAX)?;
+            if request[9] as usize != 2 * wcount { return Err(Exception::IllegalDataValue) }
+            range(address, count)?;
+            range(waddress, wcount)?;
+
+            let mut registers = [0u16; READ_REGISTERS_MAX];
+            registers_in(&request[10..], &mut registers[..wcount]);
+            map.write_registers(waddress, &registers[..wcount])?;
+
+            map.read_holding_registers(address, &mut registers[..count])?;
+
+            Ok(1 + registers_out(&registers[..count], &mut response[1..]))
+        },
+    }
+}
+
+/// Checks the length of the request and the quantity of items.
+fn quantity(request: &[u8], len: usize, count: usize, max: usize) -> Result<(), Exception> {
+    match (request.len() == len) && (1..=max).contains(&count) {
+        true => Ok(()),
+        _ => Err(Exception::IllegalDataValue),
+    }
+}
+
+/// Checks that the items fit in the address space.
+fn range(address: u16, count: usize) -> Result<(), Exception> {
+    match (address as usize + count) <= 0x10000 {
+        true => Ok(()),
+        _ => Err(Exception::IllegalDataAddress),
+    }
+}
+
+/// Decodes big endian register values.
+fn registers_in(data: &[u8], registers: &mut [u16]) {
+    for (i, register) in registers.iter_mut().enumerate() {
+        *register = word(data, 2 * i);
+    }
+}
+
+/// Writes the byte count and the big endian register values.
+/// Returns the number of bytes written.
+fn registers_out(registers: &[u16], out: &mut [u8]) -> usize {
+    out[0] = (2 * registers.len()) as u8;
+
+    for (i, register) in registers.iter().enumerate() {
+        out[1 + (2 * i)..3 + (2 * i)].copy_from_slice(&register.to_be_bytes());
+    }
+
+    1 + (2 * registers.len())
+}
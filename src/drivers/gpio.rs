@@ -0,0 +1,54 @@
+//! GPIO pin traits.
+//! Minimal pin abstractions used by drivers that need to control or sample
+//! individual lines (chip selects, bit-banged buses, bus recovery...).
+
+
+
+/// Digital output pin.
+pub trait OutputPin {
+    /// Drives the pin high.
+    fn set_high(&mut self);
+
+    /// Drives the pin low.
+    fn set_low(&mut self);
+
+    /// Drives the pin to the given level.
+    #[inline]
+    fn set(&mut self, high: bool) {
+        if high { self.set_high() }
+        else { self.set_low() }
+    }
+}
+
+impl<P: OutputPin> OutputPin for &mut P {
+    #[inline]
+    fn set_high(&mut self) {
+        P::set_high(self)
+    }
+
+    #[inline]
+    fn set_low(&mut self) {
+        P::set_low(self)
+    }
+}
+
+
+
+/// Digital input pin.
+pub trait InputPin {
+    /// Returns `true` if the pin is high.
+    fn is_high(&self) -> bool;
+
+    /// Returns `true` if the pin is low.
+    #[inline]
+    fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+}
+
+impl<P: InputPin> InputPin for &mut P {
+    #[inline]
+    fn is_high(&self) -> bool {
+        P::is_high(self)
+    }
+}
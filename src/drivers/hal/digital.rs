@@ -0,0 +1,273 @@
+//! GPIO pin and delay adapters.
+
+
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital as hal;
+use embedded_hal_async::delay as asynch;
+
+use crate::drivers::gpio::{ InputPin, OutputPin };
+use crate::time::Delay;
+
+
+
+/// Exposes a micro GPIO pin as an `embedded_hal::digital` pin.
+pub struct HalPin<P> {
+    /// Wrapped pin.
+    pin: P,
+}
+
+impl<P> HalPin<P> {
+    /// Wraps the given pin.
+    pub const fn new(pin: P) -> Self {
+        Self { pin }
+    }
+
+    /// Consumes the adapter and returns the pin.
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+impl<P> hal::ErrorType for HalPin<P> {
+    type Error = Infallible;
+}
+
+impl<P: OutputPin> hal::OutputPin for HalPin<P> {
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_high();
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_low();
+        Ok(())
+    }
+}
+
+impl<P: InputPin> hal::InputPin for HalPin<P> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.pin.is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.pin.is_low())
+    }
+}
+
+
+
+/// Exposes an `embedded_hal::digital` pin as a micro GPIO pin.
+/// Errors reported by the pin are ignored (MCU pins are infallible), and a
+/// failed read returns low.
+pub struct MicroPin<P> {
+    /// Wrapped pin. embedded-hal reads a pin through a mutable reference.
+    pin: RefCell<P>,
+}
+
+impl<P> MicroPin<P> {
+    /// Wraps the given pin.
+    pub const fn new(pin: P) -> Self {
+        Self { pin: RefCell::new(pin) }
+    }
+
+    /// Consumes the adapter and returns the pin.
+    pub fn release(self) -> P {
+        self.pin.into_inner()
+    }
+}
+
+impl<P: hal::OutputPin> OutputPin for MicroPin<P> {
+    fn set_high(&mut self) {
+        let _ = self.pin.get_mut().set_high();
+    }
+
+    fn set_low(&mut self) {
+        let _ = self.pin.get_mut().set_low();
+    }
+}
+
+impl<P: hal::InputPin> InputPin for MicroPin<P> {
+    fn is_high(&self) -> bool {
+        self.pin.borrow_mut().is_high().unwrap_or(false)
+    }
+}
+
+
+
+/// Exposes a micro delay source as an `embedded_hal::delay::DelayNs` and an
+/// `embedded_hal_async::delay::DelayNs`. Delays are rounded up to whole microseconds.
+/// Asynchronous delays block the executor while they run.
+pub struct HalDelay<D> {
+    /// Wrapped delay source.
+    delay: D,
+}
+
+impl<D> HalDelay<D> {
+    /// Wraps the given delay source.
+    pub const fn new(delay: D) -> Self {
+        Self { delay }
+    }
+
+    /// Consumes the adapter and returns the delay source.
+    pub fn release(self) -> D {
+        self.delay
+    }
+}
+
+impl<D: Delay> DelayNs for HalDelay<D> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delay.delay_us(ns.saturating_add(999) / 1000);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+    }
+}
+
+impl<D: Delay> asynch::DelayNs for HalDelay<D> {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.delay.delay_us(ns.saturating_add(999) / 1000);
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us);
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+    }
+}
+
+
+
+/// Exposes an `embedded_hal::delay::DelayNs` as a micro delay source.
+pub struct MicroDelay<D> {
+    /// Wrapped delay source.
+    delay: D,
+}
+
+impl<D> MicroDelay<D> {
+    /// Wraps the given delay source.
+    pub const fn new(delay: D) -> Self {
+        Self { delay }
+    }
+
+    /// Consumes the adapter and returns the delay source.
+    pub fn release(self) -> D {
+        self.delay
+    }
+}
+
+impl<D: DelayNs> Delay for MicroDelay<D> {
+    fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use embedded_hal::digital::{ InputPin as _, OutputPin as _ };
+
+    use super::*;
+
+    /// Push-pull pin reading back its output level.
+    #[derive(Default)]
+    struct Pin {
+        /// Output level.
+        high: bool,
+
+        /// Number of level changes requested.
+        writes: usize,
+    }
+
+    impl OutputPin for Pin {
+        fn set_high(&mut self) {
+            self.high = true;
+            self.writes += 1;
+        }
+
+        fn set_low(&mut self) {
+            self.high = false;
+            self.writes += 1;
+        }
+    }
+
+    impl InputPin for Pin {
+        fn is_high(&self) -> bool {
+            self.high
+        }
+    }
+
+    /// Delay source recording the delays.
+    #[derive(Default)]
+    struct Recorder(Vec<u32>);
+
+    impl Delay for Recorder {
+        fn delay_us(&mut self, us: u32) {
+            self.0.push(us)
+        }
+    }
+
+    #[test]
+    fn hal_pin() {
+        let mut pin = HalPin::new(Pin::default());
+
+        assert_eq!(pin.set_high(), Ok(()));
+        assert_eq!(pin.is_high(), Ok(true));
+        assert_eq!(pin.set_low(), Ok(()));
+        assert_eq!(pin.is_low(), Ok(true));
+        assert_eq!(pin.release().writes, 2);
+    }
+
+    #[test]
+    fn micro_pin_round_trip() {
+        // Round trip through both adapters.
+        let mut pin = MicroPin::new(HalPin::new(Pin::default()));
+
+        OutputPin::set_high(&mut pin);
+        assert!(InputPin::is_high(&pin));
+
+        OutputPin::set_low(&mut pin);
+        assert!(InputPin::is_low(&pin));
+        assert_eq!(pin.release().release().writes, 2);
+    }
+
+    #[test]
+    fn hal_delay_rounds_up_to_microseconds() {
+        let mut delay = HalDelay::new(Recorder::default());
+
+        DelayNs::delay_ns(&mut delay, 1);
+        DelayNs::delay_ns(&mut delay, 2000);
+        DelayNs::delay_us(&mut delay, 7);
+        DelayNs::delay_ms(&mut delay, 2);
+
+        assert_eq!(delay.release().0, [1, 2, 7, 1000, 1000]);
+    }
+
+    #[test]
+    fn micro_delay_round_trip() {
+        // Round trip through both adapters.
+        let mut delay = MicroDelay::new(HalDelay::new(Recorder::default()));
+
+        delay.delay_us(5);
+        delay.delay_ms(1);
+
+        assert_eq!(delay.release().release().0, [5, 1000]);
+    }
+}
@@ -0,0 +1,111 @@
+//! Errors of the embedded-hal adapters.
+
+
+
+use core::fmt::Debug;
+
+use embedded_hal::i2c::{ self as i2c, NoAcknowledgeSource };
+use embedded_hal::spi;
+
+use crate::drivers::comm::{ DriverError, ErrorKind };
+
+
+
+/// Errors reported to embedded-hal by the `Hal*` adapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HalError<E> {
+    /// The wrapped driver reported an error.
+    Driver(E),
+
+    /// The request has no equivalent in the wrapped driver.
+    Unsupported,
+}
+
+impl<E> From<E> for HalError<E> {
+    fn from(e: E) -> Self {
+        HalError::Driver(e)
+    }
+}
+
+impl<E: DriverError> i2c::Error for HalError<E> {
+    fn kind(&self) -> i2c::ErrorKind {
+        let kind = match self {
+            HalError::Driver(e) => e.kind(),
+            HalError::Unsupported => ErrorKind::Other,
+        };
+
+        match kind {
+            ErrorKind::AddressNack => i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            ErrorKind::DataNack => i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data),
+            ErrorKind::ArbitrationLost => i2c::ErrorKind::ArbitrationLoss,
+            ErrorKind::Bus => i2c::ErrorKind::Bus,
+            ErrorKind::Overrun => i2c::ErrorKind::Overrun,
+            _ => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+impl<E: DriverError> spi::Error for HalError<E> {
+    fn kind(&self) -> spi::ErrorKind {
+        let kind = match self {
+            HalError::Driver(e) => e.kind(),
+            HalError::Unsupported => ErrorKind::Other,
+        };
+
+        match kind {
+            ErrorKind::Overrun => spi::ErrorKind::Overrun,
+            ErrorKind::Framing => spi::ErrorKind::FrameFormat,
+            _ => spi::ErrorKind::Other,
+        }
+    }
+}
+
+
+
+/// Errors reported by the `Micro*` adapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicroError<E> {
+    /// The wrapped embedded-hal driver reported an error, classified when it was received.
+    Driver(E, ErrorKind),
+
+    /// The request has no equivalent in the wrapped embedded-hal driver.
+    Unsupported,
+}
+
+impl<E> MicroError<E> {
+    /// Wraps an embedded-hal I2C error.
+    pub fn i2c(e: E) -> Self where E: i2c::Error {
+        let kind = match e.kind() {
+            i2c::ErrorKind::Bus => ErrorKind::Bus,
+            i2c::ErrorKind::ArbitrationLoss => ErrorKind::ArbitrationLost,
+            i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => ErrorKind::DataNack,
+            // A NACK of unknown source is most likely a missing target.
+            i2c::ErrorKind::NoAcknowledge(_) => ErrorKind::AddressNack,
+            i2c::ErrorKind::Overrun => ErrorKind::Overrun,
+            _ => ErrorKind::Other,
+        };
+
+        MicroError::Driver(e, kind)
+    }
+
+    /// Wraps an embedded-hal SPI error.
+    pub fn spi(e: E) -> Self where E: spi::Error {
+        let kind = match e.kind() {
+            spi::ErrorKind::Overrun => ErrorKind::Overrun,
+            spi::ErrorKind::FrameFormat => ErrorKind::Framing,
+            spi::ErrorKind::ModeFault | spi::ErrorKind::ChipSelectFault => ErrorKind::Bus,
+            _ => ErrorKind::Other,
+        };
+
+        MicroError::Driver(e, kind)
+    }
+}
+
+impl<E: Debug> DriverError for MicroError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            MicroError::Driver(_, kind) => *kind,
+            MicroError::Unsupported => ErrorKind::Other,
+        }
+    }
+}
@@ -0,0 +1,478 @@
+//! I2C adapters.
+
+
+
+use core::future::Future;
+
+use embedded_hal::i2c::{ self as hal, Operation, SevenBitAddress, TenBitAddress };
+use embedded_hal_async::i2c as asynch;
+
+use crate::drivers::comm::{
+    CommunicationError, I2CAddress, I2CAddress7bit, I2CAddress10bit, I2CAsyncDriver, I2CDriver, I2COperation,
+};
+
+use super::{ HalError, MicroError };
+
+
+
+/// Maximum number of operations of a converted transaction.
+/// Operations are converted on the stack, longer transactions fail as unsupported.
+const OPERATIONS: usize = 16;
+
+
+
+/// Exposes an `I2CDriver` as an `embedded_hal::i2c::I2c` bus, and an
+/// `I2CAsyncDriver` as an `embedded_hal_async::i2c::I2c` bus.
+pub struct HalI2C<D> {
+    /// Wrapped driver.
+    bus: D,
+}
+
+impl<D> HalI2C<D> {
+    /// Wraps the given driver.
+    pub const fn new(bus: D) -> Self {
+        Self { bus }
+    }
+
+    /// Consumes the adapter and returns the driver.
+    pub fn release(self) -> D {
+        self.bus
+    }
+
+    /// Converts the operations and executes them as a single transaction.
+    fn execute<A: I2CAddress>(&mut self, addr: A, operations: &mut [Operation<'_>]) -> Result<(), HalError<D::Error>>
+        where D: I2CDriver<A>
+    {
+        let (mut converted, n) = micro(operations).ok_or(HalError::Unsupported)?;
+
+        Ok(self.bus.transaction(addr, &mut converted[..n])?)
+    }
+
+    /// Converts the operations and executes them as a single asynchronous transaction.
+    async fn execute_async<A: I2CAddress>(&mut self, addr: A, operations: &mut [Operation<'_>]) -> Result<(), HalError<D::Error>>
+        where D: I2CAsyncDriver<A>
+    {
+        let (mut converted, n) = micro(operations).ok_or(HalError::Unsupported)?;
+
+        Ok(self.bus.transaction(addr, &mut converted[..n]).await?)
+    }
+}
+
+impl<D: CommunicationError> hal::ErrorType for HalI2C<D> {
+    type Error = HalError<D::Error>;
+}
+
+impl<D: I2CDriver<I2CAddress7bit>> hal::I2c<SevenBitAddress> for HalI2C<D> {
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.receive(I2CAddress7bit::new(address), read)?)
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.send(I2CAddress7bit::new(address), write)?)
+    }
+
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer(I2CAddress7bit::new(address), write, read)?)
+    }
+
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.execute(I2CAddress7bit::new(address), operations)
+    }
+}
+
+impl<D: I2CDriver<I2CAddress10bit>> hal::I2c<TenBitAddress> for HalI2C<D> {
+    fn read(&mut self, address: u16, read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.receive(I2CAddress10bit::new(address), read)?)
+    }
+
+    fn write(&mut self, address: u16, write: &[u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.send(I2CAddress10bit::new(address), write)?)
+    }
+
+    fn write_read(&mut self, address: u16, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer(I2CAddress10bit::new(address), write, read)?)
+    }
+
+    fn transaction(&mut self, address: u16, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.execute(I2CAddress10bit::new(address), operations)
+    }
+}
+
+impl<D: I2CAsyncDriver<I2CAddress7bit>> asynch::I2c<SevenBitAddress> for HalI2C<D> {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.receive(I2CAddress7bit::new(address), read).await?)
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.send(I2CAddress7bit::new(address), write).await?)
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer(I2CAddress7bit::new(address), write, read).await?)
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.execute_async(I2CAddress7bit::new(address), operations).await
+    }
+}
+
+impl<D: I2CAsyncDriver<I2CAddress10bit>> asynch::I2c<TenBitAddress> for HalI2C<D> {
+    async fn read(&mut self, address: u16, read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.receive(I2CAddress10bit::new(address), read).await?)
+    }
+
+    async fn write(&mut self, address: u16, write: &[u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.send(I2CAddress10bit::new(address), write).await?)
+    }
+
+    async fn write_read(&mut self, address: u16, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer(I2CAddress10bit::new(address), write, read).await?)
+    }
+
+    async fn transaction(&mut self, address: u16, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.execute_async(I2CAddress10bit::new(address), operations).await
+    }
+}
+
+
+
+/// Exposes an `embedded_hal::i2c::I2c` bus as an `I2CDriver`, and an
+/// `embedded_hal_async::i2c::I2c` bus as an `I2CAsyncDriver`.
+pub struct MicroI2C<D> {
+    /// Wrapped bus.
+    bus: D,
+}
+
+impl<D> MicroI2C<D> {
+    /// Wraps the given bus.
+    pub const fn new(bus: D) -> Self {
+        Self { bus }
+    }
+
+    /// Consumes the adapter and returns the bus.
+    pub fn release(self) -> D {
+        self.bus
+    }
+
+    /// Converts the operations and executes them as a single transaction.
+    fn execute<A: hal::AddressMode>(&mut self, addr: A, operations: &mut [I2COperation<'_>]) -> Result<(), MicroError<D::Error>>
+        where D: hal::I2c<A>
+    {
+        let (mut converted, n) = embedded(operations).ok_or(MicroError::Unsupported)?;
+
+        self.bus.transaction(addr, &mut converted[..n]).map_err(MicroError::i2c)
+    }
+}
+
+impl<D: hal::ErrorType> CommunicationError for MicroI2C<D> {
+    type Error = MicroError<D::Error>;
+}
+
+impl<D: hal::I2c<SevenBitAddress>> I2CDriver<I2CAddress7bit> for MicroI2C<D> {
+    fn transaction(&mut self, addr: I2CAddress7bit, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error> {
+        self.execute(addr.0, operations)
+    }
+
+    fn send(&mut self, addr: I2CAddress7bit, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.bus.write(addr.0, buffer).map_err(MicroError::i2c)
+    }
+
+    fn receive(&mut self, addr: I2CAddress7bit, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.read(addr.0, buffer).map_err(MicroError::i2c)
+    }
+
+    fn transfer(&mut self, addr: I2CAddress7bit, send: &[u8], receive: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.write_read(addr.0, send, receive).map_err(MicroError::i2c)
+    }
+}
+
+impl<D: hal::I2c<TenBitAddress>> I2CDriver<I2CAddress10bit> for MicroI2C<D> {
+    fn transaction(&mut self, addr: I2CAddress10bit, operations: &mut [I2COperation<'_>]) -> Result<(), Self::Error> {
+        self.execute(addr.0, operations)
+    }
+
+    fn send(&mut self, addr: I2CAddress10bit, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.bus.write(addr.0, buffer).map_err(MicroError::i2c)
+    }
+
+    fn receive(&mut self, addr: I2CAddress10bit, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.read(addr.0, buffer).map_err(MicroError::i2c)
+    }
+
+    fn transfer(&mut self, addr: I2CAddress10bit, send: &[u8], receive: &mut [u8]) -> Result<(), Self::Error> {
+        self.bus.write_read(addr.0, send, receive).map_err(MicroError::i2c)
+    }
+}
+
+impl<D: asynch::I2c<SevenBitAddress>> I2CAsyncDriver<I2CAddress7bit> for MicroI2C<D> {
+    type WriteFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type ReadFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type WriteReadFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type TransactionFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+
+    fn transaction<'a>(&'a mut self, addr: I2CAddress7bit, operations: &'a mut [I2COperation<'a>]) -> Self::TransactionFuture<'a> {
+        async move {
+            let (mut converted, n) = embedded(operations).ok_or(MicroError::Unsupported)?;
+
+            self.bus.transaction(addr.0, &mut converted[..n]).await.map_err(MicroError::i2c)
+        }
+    }
+
+    fn send<'a>(&'a mut self, addr: I2CAddress7bit, data: &'a [u8]) -> Self::WriteFuture<'a> {
+        async move { self.bus.write(addr.0, data).await.map_err(MicroError::i2c) }
+    }
+
+    fn receive<'a>(&'a mut self, addr: I2CAddress7bit, recv: &'a mut [u8]) -> Self::ReadFuture<'a> {
+        async move { self.bus.read(addr.0, recv).await.map_err(MicroError::i2c) }
+    }
+
+    fn transfer<'a>(&'a mut self, addr: I2CAddress7bit, send: &'a [u8], recv: &'a mut [u8]) -> Self::WriteReadFuture<'a> {
+        async move { self.bus.write_read(addr.0, send, recv).await.map_err(MicroError::i2c) }
+    }
+}
+
+impl<D: asynch::I2c<TenBitAddress>> I2CAsyncDriver<I2CAddress10bit> for MicroI2C<D> {
+    type WriteFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type ReadFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type WriteReadFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+    type TransactionFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a;
+
+    fn transaction<'a>(&'a mut self, addr: I2CAddress10bit, operations: &'a mut [I2COperation<'a>]) -> Self::TransactionFuture<'a> {
+        async move {
+            let (mut converted, n) = embedded(operations).ok_or(MicroError::Unsupported)?;
+
+            self.bus.transaction(addr.0, &mut converted[..n]).await.map_err(MicroError::i2c)
+        }
+    }
+
+    fn send<'a>(&'a mut self, addr: I2CAddress10bit, data: &'a [u8]) -> Self::WriteFuture<'a> {
+        async move { self.bus.write(addr.0, data).await.map_err(MicroError::i2c) }
+    }
+
+    fn receive<'a>(&'a mut self, addr: I2CAddress10bit, recv: &'a mut [u8]) -> Self::ReadFuture<'a> {
+        async move { self.bus.read(addr.0, recv).await.map_err(MicroError::i2c) }
+    }
+
+    fn transfer<'a>(&'a mut self, addr: I2CAddress10bit, send: &'a [u8], recv: &'a mut [u8]) -> Self::WriteReadFuture<'a> {
+        async move { self.bus.write_read(addr.0, send, recv).await.map_err(MicroError::i2c) }
+    }
+}
+
+
+
+/// Converts embedded-hal operations into micro operations.
+/// Returns the converted operations and their number, or `None` if there are too many.
+fn micro<'b>(operations: &'b mut [Operation<'_>]) -> Option<([I2COperation<'b>; OPERATIONS], usize)> {
+    let n = operations.len();
+    if n > OPERATIONS { return None }
+
+    let mut converted: [I2COperation<'b>; OPERATIONS] = core::array::from_fn(|_| I2COperation::Write(&[]));
+
+    for (c, operation) in converted.iter_mut().zip(operations.iter_mut()) {
+        *c = match operation {
+            Operation::Read(r) => I2COperation::Read(r),
+            Operation::Write(w) => I2COperation::Write(w),
+        };
+    }
+
+    Some((converted, n))
+}
+
+/// Converts micro operations into embedded-hal operations.
+/// Returns the converted operations and their number, or `None` if there are too many.
+fn embedded<'b>(operations: &'b mut [I2COperation<'_>]) -> Option<([Operation<'b>; OPERATIONS], usize)> {
+    // A write-read expands to two operations.
+    let n = operations.iter().map(|o| match o { I2COperation::WriteRead(..) => 2, _ => 1 }).sum();
+    if n > OPERATIONS { return None }
+
+    let mut converted: [Operation<'b>; OPERATIONS] = core::array::from_fn(|_| Operation::Write(&[]));
+    let mut slots = converted.iter_mut();
+
+    for operation in operations.iter_mut() {
+        match operation {
+            I2COperation::Write(w) => *slots.next()? = Operation::Write(w),
+            I2COperation::Read(r) => *slots.next()? = Operation::Read(r),
+            I2COperation::WriteRead(w, r) => {
+                *slots.next()? = Operation::Write(w);
+                *slots.next()? = Operation::Read(r);
+            },
+        }
+    }
+
+    Some((converted, n))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{ Context, Waker };
+
+    use embedded_hal::i2c::Error;
+
+    use super::*;
+    use crate::drivers::comm::{ DriverError, ErrorKind };
+    use crate::drivers::comm::mock::{ block_on, I2CExpectation, I2CMock, I2CScript, MockError };
+
+    const TARGET: u8 = 0x50;
+
+    fn mock() -> I2CMock<8> {
+        I2CMock::new(I2CAddress7bit::new(TARGET), [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17])
+    }
+
+    #[test]
+    fn hal_async_operations() {
+        let mut i2c = HalI2C::new(mock());
+        let mut data = [0; 2];
+
+        block_on(async {
+            assert_eq!(asynch::I2c::write(&mut i2c, TARGET, &[0x02, 0xAA]).await, Ok(()));
+            assert_eq!(asynch::I2c::write_read(&mut i2c, TARGET, &[0x01], &mut data).await, Ok(()));
+        });
+
+        assert_eq!(data, [0x11, 0xAA]);
+
+        let (mut a, mut b) = ([0; 1], [0; 2]);
+        let mut operations = [Operation::Write(&[0x05]), Operation::Read(&mut a), Operation::Read(&mut b)];
+
+        assert_eq!(block_on(asynch::I2c::transaction(&mut i2c, TARGET, &mut operations)), Ok(()));
+        assert_eq!((a, b), ([0x15], [0x16, 0x17]));
+        assert_eq!(i2c.release().transactions(), 3);
+    }
+
+    #[test]
+    fn hal_async_errors_are_classified() {
+        let mut i2c = HalI2C::new(mock());
+
+        let e = block_on(asynch::I2c::write(&mut i2c, 0x51, &[0x00])).unwrap_err();
+
+        assert_eq!(e, HalError::Driver(MockError::AddressNack));
+        assert_eq!(e.kind(), hal::ErrorKind::NoAcknowledge(hal::NoAcknowledgeSource::Address));
+    }
+
+    #[test]
+    fn hal_async_cancellation_reaches_driver() {
+        let mut i2c = HalI2C::new(mock());
+
+        {
+            let mut transfer = pin!(asynch::I2c::write(&mut i2c, TARGET, &[0x00, 1, 2, 3]));
+            assert!(transfer.as_mut().poll(&mut Context::from_waker(Waker::noop())).is_pending());
+        }
+
+        let mock = i2c.release();
+        assert_eq!(mock.aborts(), 1);
+        assert!(!mock.busy());
+    }
+
+    #[test]
+    fn micro_async_operations() {
+        // Round trip through both adapters.
+        let mut i2c = MicroI2C::new(HalI2C::new(mock()));
+        let addr = I2CAddress7bit::new(TARGET);
+        let mut data = [0; 2];
+
+        block_on(async {
+            assert_eq!(I2CAsyncDriver::send(&mut i2c, addr, &[0x03, 0xBB]).await, Ok(()));
+            assert_eq!(I2CAsyncDriver::transfer(&mut i2c, addr, &[0x02], &mut data).await, Ok(()));
+        });
+
+        assert_eq!(data, [0x12, 0xBB]);
+
+        let mut recv = [0; 1];
+        assert_eq!(block_on(I2CAsyncDriver::receive(&mut i2c, addr, &mut recv)), Ok(()));
+        assert_eq!(recv, [0x14]);
+
+        let mut read = [0; 2];
+        let mut operations = [I2COperation::Write(&[0x06]), I2COperation::Read(&mut read)];
+        assert_eq!(block_on(I2CAsyncDriver::transaction(&mut i2c, addr, &mut operations)), Ok(()));
+        assert_eq!(read, [0x16, 0x17]);
+    }
+
+    #[test]
+    fn micro_async_errors_are_classified() {
+        let mut i2c = MicroI2C::new(HalI2C::new(mock()));
+
+        let e = block_on(I2CAsyncDriver::send(&mut i2c, I2CAddress7bit::new(0x51), &[0x00])).unwrap_err();
+
+        assert_eq!(DriverError::kind(&e), ErrorKind::AddressNack);
+    }
+
+    #[test]
+    fn hal_blocking_operations() {
+        let mut i2c = HalI2C::new(mock());
+        let mut data = [0; 2];
+
+        assert_eq!(hal::I2c::write(&mut i2c, TARGET, &[0x02, 0xAA]), Ok(()));
+        assert_eq!(hal::I2c::write_read(&mut i2c, TARGET, &[0x01], &mut data), Ok(()));
+        assert_eq!(data, [0x11, 0xAA]);
+
+        assert_eq!(hal::I2c::read(&mut i2c, TARGET, &mut data), Ok(()));
+        assert_eq!(data, [0x13, 0x14]);
+
+        let (mut a, mut b) = ([0; 1], [0; 2]);
+        let mut operations = [Operation::Write(&[0x05]), Operation::Read(&mut a), Operation::Read(&mut b)];
+
+        assert_eq!(hal::I2c::transaction(&mut i2c, TARGET, &mut operations), Ok(()));
+        assert_eq!((a, b), ([0x15], [0x16, 0x17]));
+        assert_eq!(i2c.release().transactions(), 4);
+    }
+
+    #[test]
+    fn hal_blocking_rejects_long_transactions() {
+        let mut i2c = HalI2C::new(mock());
+        let mut operations: [Operation; OPERATIONS + 1] = core::array::from_fn(|_| Operation::Write(&[0x00]));
+
+        assert_eq!(hal::I2c::transaction(&mut i2c, TARGET, &mut operations), Err(HalError::Unsupported));
+        assert_eq!(hal::I2c::transaction(&mut i2c, TARGET, &mut operations[..OPERATIONS]), Ok(()));
+        assert_eq!(i2c.release().transactions(), 1);
+    }
+
+    #[test]
+    fn micro_blocking_operations() {
+        // Round trip through both adapters, checked against a script.
+        let addr = I2CAddress7bit::new(TARGET);
+        let script = [
+            I2CExpectation::write(addr, &[0x03, 0xBB]),
+            I2CExpectation::write_read(addr, &[0x02], &[0x12, 0xBB]),
+            I2CExpectation::read(addr, &[0x14]),
+            // The write-read is expanded, and merged with the adjacent read.
+            I2CExpectation::write_read(addr, &[0x06], &[0x16, 0x17, 0x18]),
+        ];
+
+        let mut i2c = MicroI2C::new(HalI2C::new(I2CScript::new(&script)));
+        let mut data = [0; 2];
+
+        assert_eq!(I2CDriver::send(&mut i2c, addr, &[0x03, 0xBB]), Ok(()));
+        assert_eq!(I2CDriver::transfer(&mut i2c, addr, &[0x02], &mut data), Ok(()));
+        assert_eq!(data, [0x12, 0xBB]);
+
+        let mut recv = [0; 1];
+        assert_eq!(I2CDriver::receive(&mut i2c, addr, &mut recv), Ok(()));
+        assert_eq!(recv, [0x14]);
+
+        let (mut a, mut b) = ([0; 2], [0; 1]);
+        let mut operations = [I2COperation::WriteRead(&[0x06], &mut a), I2COperation::Read(&mut b)];
+        assert_eq!(I2CDriver::transaction(&mut i2c, addr, &mut operations), Ok(()));
+        assert_eq!((a, b), ([0x16, 0x17], [0x18]));
+
+        i2c.release().release().done();
+    }
+
+    #[test]
+    fn micro_blocking_rejects_long_transactions() {
+        // Each write-read expands to two operations, the script expects no transaction.
+        let mut i2c = MicroI2C::new(HalI2C::new(I2CScript::new(&[])));
+        let addr = I2CAddress7bit::new(TARGET);
+
+        let mut buffers = [[0u8; 1]; OPERATIONS / 2 + 1];
+        let mut operations: [I2COperation; OPERATIONS / 2 + 1] = {
+            let mut buffers = buffers.iter_mut();
+            core::array::from_fn(|_| I2COperation::WriteRead(&[0x00], buffers.next().unwrap()))
+        };
+
+        assert_eq!(I2CDriver::transaction(&mut i2c, addr, &mut operations), Err(MicroError::Unsupported));
+        assert_eq!(buffers, [[0]; OPERATIONS / 2 + 1]);
+    }
+}
@@ -0,0 +1,23 @@
+//! embedded-hal adapters.
+//! Wrappers that convert between the driver traits of this crate and the
+//! `embedded-hal` 1.0 traits (`embedded-hal-async` for asynchronous drivers
+//! and `embedded-io` for serial ports), so the device drivers of the ecosystem
+//! can run on micro drivers and vice versa.
+//! `Hal*` types expose a micro driver as an embedded-hal implementation and
+//! `Micro*` types expose an embedded-hal implementation as a micro driver.
+
+
+
+mod digital;
+mod error;
+mod i2c;
+mod serial;
+mod spi;
+
+
+
+pub use self::digital::{ HalDelay, HalPin, MicroDelay, MicroPin };
+pub use self::error::{ HalError, MicroError };
+pub use self::i2c::{ HalI2C, MicroI2C };
+pub use self::serial::{ HalUart, MicroUart };
+pub use self::spi::{ HalSPI, HalSPIDevice, MicroSPI };
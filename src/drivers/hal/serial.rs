@@ -0,0 +1,216 @@
+//! Serial port adapters.
+//! embedded-hal 1.0 leaves blocking serial ports to `embedded-io`.
+
+
+
+use embedded_io::{ self as io, ErrorKind };
+
+use crate::drivers::comm::{
+    CommunicationError, UartConfig, UartDriver, UartError, UartRxDriver, UartTxDriver,
+};
+
+
+
+impl io::Error for UartError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            UartError::Framing | UartError::Parity | UartError::Noise | UartError::Break => ErrorKind::InvalidData,
+            UartError::Unsupported => ErrorKind::Unsupported,
+            UartError::Overrun | UartError::Other => ErrorKind::Other,
+        }
+    }
+}
+
+
+
+/// Exposes a UART driver as an `embedded_io` serial port.
+/// Reads return as soon as one byte has been received.
+pub struct HalUart<D> {
+    /// Wrapped driver.
+    uart: D,
+}
+
+impl<D> HalUart<D> {
+    /// Wraps the given driver.
+    pub const fn new(uart: D) -> Self {
+        Self { uart }
+    }
+
+    /// Consumes the adapter and returns the driver.
+    pub fn release(self) -> D {
+        self.uart
+    }
+}
+
+impl<D: UartDriver> io::ErrorType for HalUart<D> {
+    type Error = UartError;
+}
+
+impl<D: UartTxDriver> io::Write for HalUart<D> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.uart.send(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        UartTxDriver::flush(&mut self.uart)
+    }
+}
+
+impl<D: UartRxDriver> io::Read for HalUart<D> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() { return Ok(0) }
+
+        self.uart.recv(&mut buf[..1])?;
+        Ok(1)
+    }
+}
+
+
+
+/// Exposes an `embedded_io` serial port as a UART driver.
+/// The line is configured by the HAL, `configure` fails with
+/// `UartError::Unsupported`. Errors of the port are reported as
+/// `UartError::Other`.
+pub struct MicroUart<D> {
+    /// Wrapped serial port.
+    port: D,
+}
+
+impl<D> MicroUart<D> {
+    /// Wraps the given serial port.
+    pub const fn new(port: D) -> Self {
+        Self { port }
+    }
+
+    /// Consumes the adapter and returns the serial port.
+    pub fn release(self) -> D {
+        self.port
+    }
+}
+
+impl<D: io::ErrorType> CommunicationError for MicroUart<D> {
+    type Error = UartError;
+}
+
+impl<D: io::ErrorType> UartDriver for MicroUart<D> {
+    fn configure(&mut self, _: &UartConfig) -> Result<(), Self::Error> {
+        Err(UartError::Unsupported)
+    }
+}
+
+impl<D: io::Write> UartTxDriver for MicroUart<D> {
+    fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.port.write_all(data).map_err(|_| UartError::Other)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.port.flush().map_err(|_| UartError::Other)
+    }
+}
+
+impl<D: io::Read> UartRxDriver for MicroUart<D> {
+    fn recv(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        self.port.read_exact(data).map_err(|_| UartError::Other)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use embedded_io::{ Read, Write };
+
+    use super::*;
+
+    /// Serial port with its transmitter wired to its receiver.
+    #[derive(Default)]
+    struct Loopback {
+        /// Bytes in flight.
+        line: VecDeque<u8>,
+
+        /// Number of flushes.
+        flushes: usize,
+    }
+
+    impl CommunicationError for Loopback {
+        type Error = UartError;
+    }
+
+    impl UartDriver for Loopback {
+        fn configure(&mut self, _: &UartConfig) -> Result<(), UartError> {
+            Ok(())
+        }
+    }
+
+    impl UartTxDriver for Loopback {
+        fn send(&mut self, data: &[u8]) -> Result<(), UartError> {
+            self.line.extend(data);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), UartError> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    impl UartRxDriver for Loopback {
+        fn recv(&mut self, data: &mut [u8]) -> Result<(), UartError> {
+            if data.len() > self.line.len() { return Err(UartError::Overrun) }
+
+            data.iter_mut().for_each(|d| *d = self.line.pop_front().unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hal_reads_one_byte_at_a_time() {
+        let mut port = HalUart::new(Loopback::default());
+
+        assert_eq!(port.write(&[1, 2, 3]), Ok(3));
+        assert_eq!(port.flush(), Ok(()));
+
+        let mut data = [0; 4];
+        assert_eq!(port.read(&mut data), Ok(1));
+        assert_eq!(port.read(&mut data[1..]), Ok(1));
+        assert_eq!(port.read(&mut []), Ok(0));
+        assert_eq!(data, [1, 2, 0, 0]);
+
+        let uart = port.release();
+        assert_eq!(uart.line, [3]);
+        assert_eq!(uart.flushes, 1);
+    }
+
+    #[test]
+    fn hal_errors_are_classified() {
+        let mut port = HalUart::new(Loopback::default());
+
+        let e = port.read(&mut [0; 1]).unwrap_err();
+
+        assert_eq!(e, UartError::Overrun);
+        assert_eq!(io::Error::kind(&e), ErrorKind::Other);
+        assert_eq!(io::Error::kind(&UartError::Framing), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn micro_round_trip() {
+        // Round trip through both adapters.
+        let mut uart = MicroUart::new(HalUart::new(Loopback::default()));
+
+        assert_eq!(UartTxDriver::send(&mut uart, &[4, 5, 6]), Ok(()));
+        assert_eq!(UartTxDriver::flush(&mut uart), Ok(()));
+
+        let mut data = [0; 3];
+        assert_eq!(UartRxDriver::recv(&mut uart, &mut data), Ok(()));
+        assert_eq!(data, [4, 5, 6]);
+
+        // Errors of the port are not classified.
+        assert_eq!(UartRxDriver::recv(&mut uart, &mut data), Err(UartError::Other));
+        assert_eq!(uart.configure(&UartConfig::default()), Err(UartError::Unsupported));
+
+        assert!(uart.release().release().line.is_empty());
+    }
+}
@@ -0,0 +1,539 @@
+//! SPI adapters.
+
+
+
+use core::future::Future;
+
+use embedded_hal::spi::{ self as hal, Operation };
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi as asynch;
+
+use crate::drivers::Data;
+use crate::drivers::comm::{
+    ChipSelect, CommunicationError, SPIAsyncDriver, SPIConfig, SPIDriver, SPIOperation,
+};
+use crate::drivers::gpio::OutputPin;
+use crate::time::Delay;
+
+use super::{ HalError, MicroError };
+
+
+
+/// Exposes a `SPIDriver` as an `embedded_hal::spi::SpiBus`, and a
+/// `SPIAsyncDriver` as an `embedded_hal_async::spi::SpiBus`.
+pub struct HalSPI<D> {
+    /// Wrapped driver.
+    bus: D,
+}
+
+impl<D> HalSPI<D> {
+    /// Wraps the given driver.
+    pub const fn new(bus: D) -> Self {
+        Self { bus }
+    }
+
+    /// Consumes the adapter and returns the driver.
+    pub fn release(self) -> D {
+        self.bus
+    }
+}
+
+impl<D: CommunicationError> hal::ErrorType for HalSPI<D> {
+    type Error = HalError<D::Error>;
+}
+
+impl<W: Data + 'static, D: SPIDriver<W>> hal::SpiBus<W> for HalSPI<D> {
+    fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        Ok(self.bus.read(words)?)
+    }
+
+    fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        Ok(self.bus.write(words)?)
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer(read, write)?)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer_in_place(words)?)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(self.bus.flush()?)
+    }
+}
+
+impl<W: Data + 'static, D: SPIAsyncDriver<W>> asynch::SpiBus<W> for HalSPI<D> {
+    async fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        Ok(self.bus.read(words).await?)
+    }
+
+    async fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        Ok(self.bus.write(words).await?)
+    }
+
+    async fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer(read, write).await?)
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        Ok(self.bus.transfer_in_place(words).await?)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // `SPIAsyncDriver` has no flush: its futures only complete once all
+        // the words have been shifted, so there is nothing left to wait for.
+        Ok(())
+    }
+}
+
+
+
+/// Exposes a `SPIDriver` and a chip select pin as an `embedded_hal::spi::SpiDevice`,
+/// and a `SPIAsyncDriver` and a chip select pin as an `embedded_hal_async::spi::SpiDevice`.
+/// The delay source executes the delay operations of the transactions, a
+/// micro `Delay` for the blocking device and an `embedded_hal_async::delay::DelayNs`
+/// for the asynchronous one.
+pub struct HalSPIDevice<D, P, T> {
+    /// Wrapped driver.
+    bus: D,
+
+    /// Chip select pin.
+    cs: P,
+
+    /// Delay source.
+    delay: T,
+}
+
+impl<D, P: OutputPin, T> HalSPIDevice<D, P, T> {
+    /// Wraps the given driver and deasserts the chip select.
+    pub fn new(bus: D, mut cs: P, delay: T) -> Self {
+        cs.set_high();
+
+        Self { bus, cs, delay }
+    }
+
+    /// Consumes the adapter and returns the driver, the chip select and the delay source.
+    pub fn release(self) -> (D, P, T) {
+        (self.bus, self.cs, self.delay)
+    }
+}
+
+impl<D: CommunicationError, P, T> hal::ErrorType for HalSPIDevice<D, P, T> {
+    type Error = HalError<D::Error>;
+}
+
+impl<W, D, P, T> hal::SpiDevice<W> for HalSPIDevice<D, P, T>
+    where W: Data + 'static, D: SPIDriver<W>, P: OutputPin, T: Delay
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, W>]) -> Result<(), Self::Error> {
+        let delay = &mut self.delay;
+        let mut bus = ChipSelect::new(&mut self.bus, &mut self.cs);
+
+        for operation in operations.iter_mut() {
+            match operation {
+                Operation::Read(r) => bus.read(r)?,
+                Operation::Write(w) => bus.write(w)?,
+                Operation::Transfer(r, w) => bus.transfer(r, w)?,
+                Operation::TransferInPlace(rw) => bus.transfer_in_place(rw)?,
+                Operation::DelayNs(ns) => {
+                    bus.flush()?;
+                    delay.delay_us(ns.saturating_add(999) / 1000);
+                },
+            }
+        }
+
+        Ok(bus.flush()?)
+    }
+}
+
+impl<W, D, P, T> asynch::SpiDevice<W> for HalSPIDevice<D, P, T>
+    where W: Data + 'static, D: SPIAsyncDriver<W>, P: OutputPin, T: DelayNs
+{
+    async fn transaction(&mut self, operations: &mut [Operation<'_, W>]) -> Result<(), Self::Error> {
+        let delay = &mut self.delay;
+        let mut bus = ChipSelect::new(&mut self.bus, &mut self.cs);
+
+        for operation in operations.iter_mut() {
+            match operation {
+                Operation::Read(r) => bus.read(r).await?,
+                Operation::Write(w) => bus.write(w).await?,
+                Operation::Transfer(r, w) => bus.transfer(r, w).await?,
+                Operation::TransferInPlace(rw) => bus.transfer_in_place(rw).await?,
+                Operation::DelayNs(ns) => delay.delay_ns(*ns).await,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+
+/// Exposes an `embedded_hal::spi::SpiBus` as a `SPIDriver`, and an
+/// `embedded_hal_async::spi::SpiBus` as a `SPIAsyncDriver`.
+/// embedded-hal buses are configured by their HAL, `configure` fails with
+/// `MicroError::Unsupported`.
+pub struct MicroSPI<D> {
+    /// Wrapped bus.
+    bus: D,
+}
+
+impl<D> MicroSPI<D> {
+    /// Wraps the given bus.
+    pub const fn new(bus: D) -> Self {
+        Self { bus }
+    }
+
+    /// Consumes the adapter and returns the bus.
+    pub fn release(self) -> D {
+        self.bus
+    }
+}
+
+impl<D: hal::ErrorType> CommunicationError for MicroSPI<D> {
+    type Error = MicroError<D::Error>;
+}
+
+impl<W: Data + 'static, D: hal::SpiBus<W>> SPIDriver<W> for MicroSPI<D> {
+    fn configure(&mut self, _: &SPIConfig) -> Result<(), Self::Error> {
+        Err(MicroError::Unsupported)
+    }
+
+    fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.bus.transfer(read, write).map_err(MicroError::spi)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.bus.transfer_in_place(words).map_err(MicroError::spi)
+    }
+
+    fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.bus.write(words).map_err(MicroError::spi)
+    }
+
+    fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.bus.read(words).map_err(MicroError::spi)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.bus.flush().map_err(MicroError::spi)
+    }
+}
+
+impl<W: Data + 'static, D: asynch::SpiBus<W>> SPIAsyncDriver<W> for MicroSPI<D> {
+    type ReadFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type WriteFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type TransferFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type TransferInPlaceFuture<'a> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a;
+    type TransactionFuture<'a, P> = impl Future<Output = Result<(), Self::Error>> + 'a where Self: 'a, W: 'a, P: OutputPin + 'a;
+
+    fn configure(&mut self, _: &SPIConfig) -> Result<(), Self::Error> {
+        Err(MicroError::Unsupported)
+    }
+
+    fn read<'a>(&'a mut self, words: &'a mut [W]) -> Self::ReadFuture<'a> {
+        async move { self.bus.read(words).await.map_err(MicroError::spi) }
+    }
+
+    fn write<'a>(&'a mut self, words: &'a [W]) -> Self::WriteFuture<'a> {
+        async move { self.bus.write(words).await.map_err(MicroError::spi) }
+    }
+
+    fn transfer<'a>(&'a mut self, read: &'a mut [W], write: &'a [W]) -> Self::TransferFuture<'a> {
+        async move { self.bus.transfer(read, write).await.map_err(MicroError::spi) }
+    }
+
+    fn transfer_in_place<'a>(&'a mut self, words: &'a mut [W]) -> Self::TransferInPlaceFuture<'a> {
+        async move { self.bus.transfer_in_place(words).await.map_err(MicroError::spi) }
+    }
+
+    fn transaction<'a, P: OutputPin + 'a>(&'a mut self, cs: &'a mut P, operations: &'a mut [SPIOperation<'a, W>]) -> Self::TransactionFuture<'a, P> {
+        async move {
+            let mut bus = ChipSelect::new(&mut self.bus, cs);
+
+            for operation in operations.iter_mut() {
+                match operation {
+                    SPIOperation::Read(r) => bus.read(r).await.map_err(MicroError::spi)?,
+                    SPIOperation::Write(w) => bus.write(w).await.map_err(MicroError::spi)?,
+                    SPIOperation::Transfer(r, w) => bus.transfer(r, w).await.map_err(MicroError::spi)?,
+                    SPIOperation::TransferInPlace(rw) => bus.transfer_in_place(rw).await.map_err(MicroError::spi)?,
+                }
+            }
+
+            bus.flush().await.map_err(MicroError::spi)
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use std::vec::Vec;
+
+    use embedded_hal_async::spi::{ SpiBus, SpiDevice };
+
+    use super::*;
+    use crate::drivers::comm::mock::block_on;
+    use crate::drivers::hal::HalDelay;
+
+    /// Asynchronous SPI bus with MISO wired to MOSI.
+    struct Loopback {
+        /// Words shifted out.
+        sent: Vec<u8>,
+
+        /// Number of blocking flushes.
+        flushes: usize,
+    }
+
+    impl Loopback {
+        fn new() -> Self {
+            Self { sent: Vec::new(), flushes: 0 }
+        }
+    }
+
+    impl Loopback {
+        fn shift(&mut self, read: &mut [u8], write: &[u8]) {
+            for i in 0..read.len().max(write.len()) {
+                let word = write.get(i).copied().unwrap_or(0xFF);
+                self.sent.push(word);
+
+                if let Some(r) = read.get_mut(i) { *r = word }
+            }
+        }
+    }
+
+    impl CommunicationError for Loopback {
+        type Error = Infallible;
+    }
+
+    impl SPIDriver<u8> for Loopback {
+        fn configure(&mut self, _: &SPIConfig) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Infallible> {
+            self.shift(read, write);
+            Ok(())
+        }
+
+        fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+            let write = words.to_vec();
+            self.shift(words, &write);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    impl SPIAsyncDriver<u8> for Loopback {
+        type ReadFuture<'a> = impl Future<Output = Result<(), Infallible>> + 'a;
+        type WriteFuture<'a> = impl Future<Output = Result<(), Infallible>> + 'a;
+        type TransferFuture<'a> = impl Future<Output = Result<(), Infallible>> + 'a;
+        type TransferInPlaceFuture<'a> = impl Future<Output = Result<(), Infallible>> + 'a;
+        type TransactionFuture<'a, P> = impl Future<Output = Result<(), Infallible>> + 'a where P: OutputPin + 'a;
+
+        fn configure(&mut self, _: &SPIConfig) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn read<'a>(&'a mut self, words: &'a mut [u8]) -> Self::ReadFuture<'a> {
+            async move { self.shift(words, &[]); Ok(()) }
+        }
+
+        fn write<'a>(&'a mut self, words: &'a [u8]) -> Self::WriteFuture<'a> {
+            async move { self.shift(&mut [], words); Ok(()) }
+        }
+
+        fn transfer<'a>(&'a mut self, read: &'a mut [u8], write: &'a [u8]) -> Self::TransferFuture<'a> {
+            async move { self.shift(read, write); Ok(()) }
+        }
+
+        fn transfer_in_place<'a>(&'a mut self, words: &'a mut [u8]) -> Self::TransferInPlaceFuture<'a> {
+            async move {
+                let write = words.to_vec();
+                self.shift(words, &write);
+                Ok(())
+            }
+        }
+
+        fn transaction<'a, P: OutputPin + 'a>(&'a mut self, cs: &'a mut P, operations: &'a mut [SPIOperation<'a, u8>]) -> Self::TransactionFuture<'a, P> {
+            async move {
+                let mut bus = ChipSelect::new(self, cs);
+
+                for operation in operations.iter_mut() {
+                    match operation {
+                        SPIOperation::Read(r) => bus.shift(r, &[]),
+                        SPIOperation::Write(w) => bus.shift(&mut [], w),
+                        SPIOperation::Transfer(r, w) => bus.shift(r, w),
+                        SPIOperation::TransferInPlace(rw) => { let w = rw.to_vec(); bus.shift(rw, &w) },
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Chip select recording its levels.
+    #[derive(Default)]
+    struct Pin(Vec<bool>);
+
+    impl OutputPin for Pin {
+        fn set_high(&mut self) {
+            self.0.push(true)
+        }
+
+        fn set_low(&mut self) {
+            self.0.push(false)
+        }
+    }
+
+    /// Delay source recording the delays.
+    #[derive(Default)]
+    struct Recorder(Vec<u32>);
+
+    impl Delay for Recorder {
+        fn delay_us(&mut self, us: u32) {
+            self.0.push(us)
+        }
+    }
+
+    #[test]
+    fn hal_async_bus() {
+        let mut spi = HalSPI::new(Loopback::new());
+        let mut read = [0; 3];
+        let mut words = [7, 8];
+
+        block_on(async {
+            assert_eq!(SpiBus::transfer(&mut spi, &mut read, &[1, 2]).await, Ok(()));
+            assert_eq!(SpiBus::transfer_in_place(&mut spi, &mut words).await, Ok(()));
+            assert_eq!(SpiBus::write(&mut spi, &[9]).await, Ok(()));
+            assert_eq!(SpiBus::<u8>::flush(&mut spi).await, Ok(()));
+        });
+
+        assert_eq!(read, [1, 2, 0xFF]);
+        assert_eq!(words, [7, 8]);
+        assert_eq!(spi.release().sent, [1, 2, 0xFF, 7, 8, 9]);
+    }
+
+    #[test]
+    fn hal_async_device_asserts_chip_select_and_delays() {
+        let mut device = HalSPIDevice::new(Loopback::new(), Pin::default(), HalDelay::new(Recorder::default()));
+        let mut read = [0; 1];
+
+        let mut operations = [
+            Operation::Write(&[0x9F]),
+            Operation::DelayNs(1500),
+            Operation::Transfer(&mut read, &[0x42]),
+        ];
+
+        assert_eq!(block_on(SpiDevice::transaction(&mut device, &mut operations)), Ok(()));
+        assert_eq!(read, [0x42]);
+
+        let (bus, cs, delay) = device.release();
+        assert_eq!(bus.sent, [0x9F, 0x42]);
+        assert_eq!(cs.0, [true, false, true]);
+        assert_eq!(delay.release().0, [2]);
+    }
+
+    #[test]
+    fn micro_async_bus() {
+        // Round trip through both adapters.
+        let mut spi = MicroSPI::new(HalSPI::new(Loopback::new()));
+        let mut cs = Pin::default();
+        let mut read = [0; 2];
+        let mut words = [3, 4];
+
+        block_on(async {
+            assert_eq!(SPIAsyncDriver::transfer(&mut spi, &mut read, &[5, 6]).await, Ok(()));
+            assert_eq!(SPIAsyncDriver::transfer_in_place(&mut spi, &mut words).await, Ok(()));
+        });
+
+        assert_eq!(read, [5, 6]);
+        assert_eq!(words, [3, 4]);
+
+        let mut recv = [0; 1];
+        let mut operations = [SPIOperation::Write(&[0x0B]), SPIOperation::Read(&mut recv)];
+        assert_eq!(block_on(SPIAsyncDriver::transaction(&mut spi, &mut cs, &mut operations)), Ok(()));
+
+        assert_eq!(recv, [0xFF]);
+        assert_eq!(cs.0, [false, true]);
+        assert!(SPIAsyncDriver::<u8>::configure(&mut spi, &SPIConfig::new(1_000_000)).is_err());
+        assert_eq!(spi.release().release().sent, [5, 6, 3, 4, 0x0B, 0xFF]);
+    }
+
+    #[test]
+    fn hal_blocking_bus() {
+        let mut spi = HalSPI::new(Loopback::new());
+        let mut read = [0; 3];
+        let mut words = [7, 8];
+
+        assert_eq!(hal::SpiBus::transfer(&mut spi, &mut read, &[1, 2]), Ok(()));
+        assert_eq!(hal::SpiBus::transfer_in_place(&mut spi, &mut words), Ok(()));
+        assert_eq!(hal::SpiBus::write(&mut spi, &[9]), Ok(()));
+        assert_eq!(hal::SpiBus::read(&mut spi, &mut read[..1]), Ok(()));
+        assert_eq!(hal::SpiBus::<u8>::flush(&mut spi), Ok(()));
+
+        assert_eq!(read, [0xFF, 2, 0xFF]);
+        assert_eq!(words, [7, 8]);
+
+        let bus = spi.release();
+        assert_eq!(bus.sent, [1, 2, 0xFF, 7, 8, 9, 0xFF]);
+        assert_eq!(bus.flushes, 1);
+    }
+
+    #[test]
+    fn hal_blocking_device_flushes_before_delays() {
+        let mut device = HalSPIDevice::new(Loopback::new(), Pin::default(), Recorder::default());
+        let mut read = [0; 1];
+
+        let mut operations = [
+            Operation::Write(&[0x9F]),
+            Operation::DelayNs(1500),
+            Operation::Transfer(&mut read, &[0x42]),
+        ];
+
+        assert_eq!(hal::SpiDevice::transaction(&mut device, &mut operations), Ok(()));
+        assert_eq!(read, [0x42]);
+
+        let (bus, cs, delay) = device.release();
+        assert_eq!(bus.sent, [0x9F, 0x42]);
+        assert_eq!(bus.flushes, 2);
+        assert_eq!(cs.0, [true, false, true]);
+        assert_eq!(delay.0, [2]);
+    }
+
+    #[test]
+    fn micro_blocking_bus() {
+        // Round trip through both adapters.
+        let mut spi = MicroSPI::new(HalSPI::new(Loopback::new()));
+        let mut cs = Pin::default();
+        let mut read = [0; 2];
+        let mut words = [3, 4];
+
+        assert_eq!(SPIDriver::transfer(&mut spi, &mut read, &[5, 6]), Ok(()));
+        assert_eq!(SPIDriver::transfer_in_place(&mut spi, &mut words), Ok(()));
+        assert_eq!(read, [5, 6]);
+        assert_eq!(words, [3, 4]);
+
+        let mut recv = [0; 1];
+        let mut operations = [SPIOperation::Write(&[0x0B]), SPIOperation::Read(&mut recv)];
+        assert_eq!(SPIDriver::transaction(&mut spi, &mut cs, &mut operations), Ok(()));
+
+        assert_eq!(recv, [0xFF]);
+        assert_eq!(cs.0, [false, true]);
+        assert_eq!(SPIDriver::<u8>::configure(&mut spi, &SPIConfig::new(1_000_000)), Err(MicroError::Unsupported));
+
+        let bus = spi.release().release();
+        assert_eq!(bus.sent, [5, 6, 3, 4, 0x0B, 0xFF]);
+        assert_eq!(bus.flushes, 1);
+    }
+}
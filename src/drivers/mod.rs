@@ -7,11 +7,14 @@
 
 pub mod comm;
 
+pub mod gpio;
+
+pub mod hal;
+
 //pub mod dma;
 
 //pub mod usb;
 
-/*
 
 /// Common trait for data types that can be operated on.
 pub trait Data: Copy + Sized {
@@ -29,6 +32,7 @@ impl Data for u32 {}
 impl Data for i64 {}
 impl Data for u64 {}
 
+/*
 
 /// Basic async/blocking handle type to be wrapped by specialized handles.
 /// Bits 8 through 31 are available for each wrapper's specific errors.
@@ -0,0 +1,368 @@
+//! Deferred work queue.
+//! Allows ISRs to push expensive work (bottom halves) out of interrupt context.
+//! Jobs are `UserHandler`s that are queued from any ISR and executed later
+//! by a single low priority servicer (usually PendSV).
+
+
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{ AtomicU8, AtomicU32, Ordering };
+
+use super::UserHandler;
+
+
+
+/// Slot state: free to be claimed by a producer.
+const EMPTY: u8 = 0;
+
+/// Slot state: claimed by a producer, the job is being written.
+const CLAIMED: u8 = 1;
+
+/// Slot state: the job is ready to be serviced.
+const READY: u8 = 2;
+
+
+
+/// Lock-free queue of deferred jobs.
+/// Any number of ISRs may push jobs concurrently, but only one context
+/// (the PendSV handler) may service the queue.
+pub struct DeferredQueue<const N: usize> {
+    /// Job slots.
+    slots: [Slot; N],
+
+    /// Sequence counter, keeps FIFO order between jobs of the same priority.
+    sequence: AtomicU32,
+
+    /// Number of jobs rejected because the queue was full.
+    overflows: AtomicU32,
+}
+
+unsafe impl<const N: usize> Sync for DeferredQueue<N> {}
+
+impl<const N: usize> DeferredQueue<N> {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const SLOT: Slot = Slot::empty();
+
+        Self { slots: [SLOT; N], sequence: AtomicU32::new(0), overflows: AtomicU32::new(0) }
+    }
+
+    /// Queues a job with the given priority.
+    /// Lower values are serviced first, following the Cortex-M convention.
+    /// If the queue is full the job is given back and the overflow is recorded.
+    pub fn push(&self, job: UserHandler, priority: u8) -> Result<(), UserHandler> {
+        for slot in self.slots.iter() {
+            if !claim(&slot.state) { continue }
+
+            // The slot is exclusively owned until it is marked as ready.
+            unsafe {
+                *slot.job.get() = job;
+                *slot.priority.get() = priority;
+                *slot.sequence.get() = increment(&self.sequence);
+            }
+
+            slot.state.store(READY, Ordering::Release);
+
+            return Ok(());
+        }
+
+        increment(&self.overflows);
+
+        Err(job)
+    }
+
+    /// Runs all the queued jobs in priority order, including the ones queued
+    /// while servicing. Returns the number of jobs executed.
+    /// Must only be called from one context at a time.
+    pub fn service(&self) -> usize {
+        let mut n = 0;
+
+        while let Some(slot) = self.next() {
+            // Take the job out and release the slot before running it.
+            let job = unsafe { core::mem::replace(&mut *slot.job.get(), UserHandler::empty()) };
+            slot.state.store(EMPTY, Ordering::Release);
+
+            job.call();
+
+            n += 1;
+        }
+
+        n
+    }
+
+    /// Returns the number of jobs waiting to be serviced.
+    pub fn pending(&self) -> usize {
+        self.slots.iter()
+            .filter(|slot| slot.state.load(Ordering::Acquire) == READY)
+            .count()
+    }
+
+    /// Returns the number of jobs rejected because the queue was full.
+    pub fn overflows(&self) -> u32 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the most urgent ready slot.
+    fn next(&self) -> Option<&Slot> {
+        let mut best: Option<(&Slot, u8, u32)> = None;
+
+        for slot in self.slots.iter() {
+            if slot.state.load(Ordering::Acquire) != READY { continue }
+
+            let (priority, sequence) = unsafe { (*slot.priority.get(), *slot.sequence.get()) };
+
+            let better = match best {
+                None => true,
+                Some((_, p, s)) => (priority < p) || ((priority == p) && ((sequence.wrapping_sub(s) as i32) < 0)),
+            };
+
+            if better { best = Some((slot, priority, sequence)) }
+        }
+
+        best.map(|(slot, _, _)| slot)
+    }
+}
+
+#[cfg(feature = "arm")]
+impl<const N: usize> DeferredQueue<N> {
+    /// Queues a job and pends PendSV to service it.
+    /// If the queue is full the job is given back and PendSV is not pended.
+    pub fn defer(&self, job: UserHandler, priority: u8) -> Result<(), UserHandler> {
+        self.push(job, priority)?;
+
+        crate::irq::ExceptionControl::get().pend(crate::irq::Exception::PendSV);
+
+        Ok(())
+    }
+
+    /// Installs the given PendSV handler at the lowest exception priority.
+    /// The handler must call `service` on the queue.
+    pub fn install(ec: &mut crate::irq::ExceptionControl, handler: fn()) {
+        ec.handler(crate::irq::Exception::PendSV, handler);
+        ec.priority(crate::irq::Exception::PendSV, 0xFF);
+    }
+}
+
+impl<const N: usize> Default for DeferredQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+/// A single job slot of the queue.
+struct Slot {
+    /// State of the slot.
+    state: AtomicU8,
+
+    /// Priority of the job.
+    priority: UnsafeCell<u8>,
+
+    /// Sequence number of the job.
+    sequence: UnsafeCell<u32>,
+
+    /// Queued job.
+    job: UnsafeCell<UserHandler>,
+}
+
+impl Slot {
+    /// Static initializer.
+    const fn empty() -> Self {
+        Self {
+            state: AtomicU8::new(EMPTY),
+            priority: UnsafeCell::new(0),
+            sequence: UnsafeCell::new(0),
+            job: UnsafeCell::new(UserHandler::empty()),
+        }
+    }
+}
+
+
+
+/// Tries to claim an empty slot.
+#[cfg(target_has_atomic = "8")]
+#[inline(always)]
+fn claim(state: &AtomicU8) -> bool {
+    state.compare_exchange(EMPTY, CLAIMED, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+}
+
+/// Tries to claim an empty slot.
+/// Targets without CAS instructions (ARMv6-M) claim inside a critical section.
+#[cfg(all(not(target_has_atomic = "8"), feature = "arm"))]
+#[inline(always)]
+fn claim(state: &AtomicU8) -> bool {
+    crate::asm::critical(|| {
+        if state.load(Ordering::Acquire) != EMPTY { return false }
+        state.store(CLAIMED, Ordering::Release);
+        true
+    })
+}
+
+/// Increments the counter and returns its previous value.
+#[cfg(target_has_atomic = "32")]
+#[inline(always)]
+fn increment(counter: &AtomicU32) -> u32 {
+    counter.fetch_add(1, Ordering::AcqRel)
+}
+
+/// Increments the counter and returns its previous value.
+/// Targets without CAS instructions (ARMv6-M) increment inside a critical section.
+#[cfg(all(not(target_has_atomic = "32"), feature = "arm"))]
+#[inline(always)]
+fn increment(counter: &AtomicU32) -> u32 {
+    crate::asm::critical(|| {
+        let n = counter.load(Ordering::Acquire);
+        counter.store(n.wrapping_add(1), Ordering::Release);
+        n
+    })
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    /// Log of executed jobs.
+    struct Log {
+        /// Tags of the executed jobs.
+        tags: [AtomicU8; 16],
+
+        /// Number of executed jobs.
+        len: AtomicUsize,
+    }
+
+    impl Log {
+        const fn new() -> Self {
+            #[allow(clippy::declare_interior_mutable_const)]
+            const TAG: AtomicU8 = AtomicU8::new(0);
+
+            Self { tags: [TAG; 16], len: AtomicUsize::new(0) }
+        }
+
+        fn push(&self, tag: u8) {
+            self.tags[self.len.fetch_add(1, Ordering::AcqRel)].store(tag, Ordering::Release);
+        }
+
+        fn tags(&self) -> ([u8; 16], usize) {
+            let mut tags = [0; 16];
+            tags.iter_mut().zip(self.tags.iter()).for_each(|(t, a)| *t = a.load(Ordering::Acquire));
+            (tags, self.len.load(Ordering::Acquire))
+        }
+    }
+
+    /// Declares a log and jobs that record their tag in it.
+    macro_rules! jobs {
+        ($log:ident: $($name:ident = $tag:expr),*) => {
+            static $log: Log = Log::new();
+            $( fn $name() { $log.push($tag) } )*
+        };
+    }
+
+    #[test]
+    fn priority_then_sequence_order() {
+        jobs!(LOG: a = 1, b = 2, c = 3, d = 4, e = 5);
+
+        let queue = DeferredQueue::<8>::new();
+
+        for (job, priority) in [(a as fn(), 3), (b, 1), (c, 3), (d, 0), (e, 1)] {
+            assert!(queue.push(UserHandler::isolated(job), priority).is_ok());
+        }
+
+        assert_eq!(queue.pending(), 5);
+        assert_eq!(queue.service(), 5);
+        assert_eq!(queue.pending(), 0);
+
+        let (tags, len) = LOG.tags();
+        assert_eq!(tags[..len], [4, 2, 5, 1, 3]);
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        jobs!(LOG: a = 1, b = 2, c = 3);
+
+        let queue = DeferredQueue::<4>::new();
+        queue.sequence.store(u32::MAX - 1, Ordering::Relaxed);
+
+        for job in [a as fn(), b, c] {
+            assert!(queue.push(UserHandler::isolated(job), 7).is_ok());
+        }
+
+        queue.service();
+
+        let (tags, len) = LOG.tags();
+        assert_eq!(tags[..len], [1, 2, 3]);
+    }
+
+    #[test]
+    fn jobs_queued_while_servicing_run() {
+        static QUEUE: DeferredQueue<2> = DeferredQueue::new();
+
+        jobs!(LOG: a = 1, b = 2);
+
+        fn requeue() {
+            a();
+
+            // The slot of the running job is already free.
+            assert_eq!(QUEUE.slots.iter().filter(|slot| slot.state.load(Ordering::Acquire) == EMPTY).count(), 2);
+            assert!(QUEUE.push(UserHandler::isolated(b), 0).is_ok());
+        }
+
+        assert!(QUEUE.push(UserHandler::isolated(requeue), 0).is_ok());
+        assert_eq!(QUEUE.service(), 2);
+
+        let (tags, len) = LOG.tags();
+        assert_eq!(tags[..len], [1, 2]);
+    }
+
+    #[test]
+    fn slot_states() {
+        jobs!(LOG: a = 1);
+
+        let queue = DeferredQueue::<2>::new();
+        let states = || [queue.slots[0].state.load(Ordering::Acquire), queue.slots[1].state.load(Ordering::Acquire)];
+
+        assert_eq!(states(), [EMPTY, EMPTY]);
+
+        // A producer interrupted while writing its job.
+        assert!(claim(&queue.slots[0].state));
+        assert!(!claim(&queue.slots[0].state));
+        assert_eq!(states(), [CLAIMED, EMPTY]);
+
+        assert!(queue.push(UserHandler::isolated(a), 0).is_ok());
+        assert_eq!(states(), [CLAIMED, READY]);
+
+        // Claimed slots are neither pending nor serviced.
+        assert_eq!(queue.pending(), 1);
+        assert_eq!(queue.service(), 1);
+        assert_eq!(states(), [CLAIMED, EMPTY]);
+    }
+
+    #[test]
+    fn overflows() {
+        jobs!(LOG: a = 1, b = 2, c = 3);
+
+        let queue = DeferredQueue::<2>::new();
+
+        assert!(queue.push(UserHandler::isolated(a), 0).is_ok());
+        assert!(queue.push(UserHandler::isolated(b), 0).is_ok());
+        assert_eq!(queue.overflows(), 0);
+
+        // The rejected job is given back.
+        let job = queue.push(UserHandler::isolated(c), 0).unwrap_err();
+        assert_eq!(queue.overflows(), 1);
+
+        job.call();
+        assert_eq!(queue.service(), 2);
+
+        let (tags, len) = LOG.tags();
+        assert_eq!(tags[..len], [3, 1, 2]);
+
+        assert!(queue.push(UserHandler::isolated(c), 0).is_ok());
+        assert_eq!(queue.overflows(), 1);
+    }
+}
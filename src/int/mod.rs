@@ -5,8 +5,16 @@
 
 
 
+mod deferred;
 mod user;
 
+#[cfg(any(test, feature = "stats"))]
+mod stats;
 
 
+
+pub use deferred::DeferredQueue;
 pub use user::UserHandler;
+
+#[cfg(any(test, feature = "stats"))]
+pub use stats::{ Clock, IRQSnapshot, IRQStats, StatsTable };
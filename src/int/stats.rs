@@ -0,0 +1,388 @@
+//! Interrupt statistics.
+//! Records invocation count, execution time and nesting depth of each IRQ.
+//! Execution times are inclusive: time spent in nested ISRs is added to the
+//! time of the preempted ISR. Entry latency (from the IRQ being raised to its
+//! handler starting) is not measured, as the time at which the IRQ was raised
+//! is not available to software.
+
+
+
+use core::sync::atomic::{ AtomicU32, Ordering };
+
+
+
+/// Source of cycle counts used to measure execution time.
+/// On ARM this is the DWT cycle counter, on the host any monotonic counter
+/// can be plugged in.
+pub trait Clock {
+    /// Returns the current cycle count. The counter is allowed to wrap.
+    fn cycles(&self) -> u32;
+}
+
+impl Clock for fn() -> u32 {
+    fn cycles(&self) -> u32 {
+        self()
+    }
+}
+
+
+
+/// Statistics of a single IRQ.
+/// Only the ISR of this IRQ writes these values, so plain atomic loads and
+/// stores are enough on all Cortex-M cores (an ISR cannot preempt itself).
+pub struct IRQStats {
+    /// Number of invocations.
+    count: AtomicU32,
+
+    /// Minimum execution time in cycles.
+    min: AtomicU32,
+
+    /// Maximum execution time in cycles.
+    max: AtomicU32,
+
+    /// Lower half of the total execution time in cycles.
+    totallo: AtomicU32,
+
+    /// Upper half of the total execution time in cycles.
+    totalhi: AtomicU32,
+
+    /// Maximum nesting depth at which this IRQ was executed.
+    depth: AtomicU32,
+}
+
+impl IRQStats {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        Self {
+            count: AtomicU32::new(0),
+            min: AtomicU32::new(u32::MAX),
+            max: AtomicU32::new(0),
+            totallo: AtomicU32::new(0),
+            totalhi: AtomicU32::new(0),
+            depth: AtomicU32::new(0),
+        }
+    }
+
+    /// Records one invocation.
+    fn record(&self, cycles: u32, depth: u32) {
+        if cycles < self.min.load(Ordering::Relaxed) { self.min.store(cycles, Ordering::Relaxed) }
+        if cycles > self.max.load(Ordering::Relaxed) { self.max.store(cycles, Ordering::Relaxed) }
+        if depth  > self.depth.load(Ordering::Relaxed) { self.depth.store(depth, Ordering::Relaxed) }
+
+        let (lo, carry) = self.totallo.load(Ordering::Relaxed).overflowing_add(cycles);
+        self.totallo.store(lo, Ordering::Relaxed);
+        if carry { self.totalhi.store(self.totalhi.load(Ordering::Relaxed).wrapping_add(1), Ordering::Relaxed) }
+
+        // The count is written last, readers use it to detect torn snapshots.
+        self.count.store(self.count.load(Ordering::Relaxed).wrapping_add(1), Ordering::Release);
+    }
+
+    /// Takes a consistent snapshot of the statistics.
+    /// Must not be called from a context that can preempt the ISR of this IRQ.
+    pub fn snapshot(&self) -> IRQSnapshot {
+        loop {
+            let count = self.count.load(Ordering::Acquire);
+
+            let snapshot = IRQSnapshot {
+                irq: 0,
+                count,
+                min: if count == 0 { 0 } else { self.min.load(Ordering::Relaxed) },
+                max: self.max.load(Ordering::Relaxed),
+                total: ((self.totalhi.load(Ordering::Relaxed) as u64) << 32) | (self.totallo.load(Ordering::Relaxed) as u64),
+                depth: self.depth.load(Ordering::Relaxed),
+            };
+
+            if self.count.load(Ordering::Acquire) == count { return snapshot }
+        }
+    }
+
+    /// Clears the statistics.
+    /// Must not be called from a context that can preempt the ISR of this IRQ.
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.min.store(u32::MAX, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+        self.totallo.store(0, Ordering::Relaxed);
+        self.totalhi.store(0, Ordering::Relaxed);
+        self.depth.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for IRQStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+/// Statistics of a table of IRQs.
+pub struct StatsTable<const N: usize> {
+    /// Per IRQ statistics.
+    irqs: [IRQStats; N],
+
+    /// Current nesting depth.
+    /// Nested ISRs restore it before returning, so loads and stores are enough.
+    depth: AtomicU32,
+}
+
+impl<const N: usize> StatsTable<N> {
+    /// Static initializer.
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const STATS: IRQStats = IRQStats::new();
+
+        Self { irqs: [STATS; N], depth: AtomicU32::new(0) }
+    }
+
+    /// Executes the given function measuring it as an invocation of the given IRQ.
+    /// IRQs out of the table range are executed without being measured.
+    pub fn measure<C: Clock, T, F: FnOnce() -> T>(&self, irq: usize, clock: &C, f: F) -> T {
+        let stats = match self.irqs.get(irq) {
+            Some(stats) => stats,
+            _ => return f(),
+        };
+
+        // Enter a new nesting level.
+        let depth = self.depth.load(Ordering::Acquire) + 1;
+        self.depth.store(depth, Ordering::Release);
+
+        let start = clock.cycles();
+        let r = f();
+        let cycles = clock.cycles().wrapping_sub(start);
+
+        // Exit the nesting level.
+        self.depth.store(depth - 1, Ordering::Release);
+
+        stats.record(cycles, depth);
+
+        r
+    }
+
+    /// Returns the statistics of the given IRQ.
+    pub fn get(&self, irq: usize) -> Option<&IRQStats> {
+        self.irqs.get(irq)
+    }
+
+    /// Returns a snapshot of the given IRQ.
+    pub fn snapshot(&self, irq: usize) -> Option<IRQSnapshot> {
+        self.irqs.get(irq).map(|stats| IRQSnapshot { irq, ..stats.snapshot() })
+    }
+
+    /// Returns an iterator over the snapshots of all the IRQs that have been invoked.
+    pub fn snapshots(&self) -> impl Iterator<Item = IRQSnapshot> + '_ {
+        (0..N).filter_map(move |irq| self.snapshot(irq)).filter(|s| s.count != 0)
+    }
+
+    /// Writes a line for each IRQ that has been invoked.
+    /// Intended to dump the statistics over a serial console.
+    pub fn dump<W: core::fmt::Write>(&self, w: &mut W) -> core::fmt::Result {
+        for snapshot in self.snapshots() {
+            writeln!(w, "{}", snapshot)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the statistics of all IRQs.
+    pub fn reset(&self) {
+        for stats in self.irqs.iter() {
+            stats.reset();
+        }
+    }
+}
+
+impl<const N: usize> Default for StatsTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+
+/// Point in time copy of the statistics of an IRQ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IRQSnapshot {
+    /// IRQ number.
+    pub irq: usize,
+
+    /// Number of invocations.
+    pub count: u32,
+
+    /// Minimum execution time in cycles.
+    pub min: u32,
+
+    /// Maximum execution time in cycles.
+    pub max: u32,
+
+    /// Total execution time in cycles.
+    pub total: u64,
+
+    /// Maximum nesting depth.
+    pub depth: u32,
+}
+
+impl IRQSnapshot {
+    /// Average execution time in cycles.
+    pub fn average(&self) -> u32 {
+        match self.count {
+            0 => 0,
+            n => (self.total / n as u64) as u32,
+        }
+    }
+}
+
+impl core::fmt::Display for IRQSnapshot {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "IRQ {:3}: count {:10} | min {:10} | max {:10} | avg {:10} | depth {}",
+            self.irq, self.count, self.min, self.max, self.average(), self.depth)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::fmt::Write;
+
+    use super::*;
+
+    /// Clock advanced by hand.
+    struct FakeClock(Cell<u32>);
+
+    impl FakeClock {
+        fn advance(&self, cycles: u32) {
+            self.0.set(self.0.get().wrapping_add(cycles));
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn cycles(&self) -> u32 {
+            self.0.get()
+        }
+    }
+
+    /// Fixed capacity text buffer.
+    struct Text {
+        data: [u8; 512],
+        len: usize,
+    }
+
+    impl Text {
+        fn new() -> Self {
+            Self { data: [0; 512], len: 0 }
+        }
+
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.data[..self.len]).unwrap()
+        }
+    }
+
+    impl Write for Text {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            let end = self.len + s.len();
+            self.data.get_mut(self.len..end).ok_or(core::fmt::Error)?.copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn min_max_average() {
+        let table = StatsTable::<4>::new();
+        let clock = FakeClock(Cell::new(0));
+
+        for cycles in [100, 40, 70] {
+            assert_eq!(table.measure(2, &clock, || { clock.advance(cycles); cycles }), cycles);
+        }
+
+        let snapshot = table.snapshot(2).unwrap();
+
+        assert_eq!(snapshot, IRQSnapshot { irq: 2, count: 3, min: 40, max: 100, total: 210, depth: 1 });
+        assert_eq!(snapshot.average(), 70);
+
+        // IRQs never invoked report zeros.
+        assert_eq!(table.snapshot(1), Some(IRQSnapshot { irq: 1, count: 0, min: 0, max: 0, total: 0, depth: 0 }));
+        assert_eq!(table.snapshot(1).unwrap().average(), 0);
+        assert_eq!(table.snapshot(4), None);
+    }
+
+    #[test]
+    fn counter_wraps() {
+        let table = StatsTable::<1>::new();
+        let clock = FakeClock(Cell::new(u32::MAX - 10));
+
+        table.measure(0, &clock, || clock.advance(30));
+
+        assert_eq!(table.snapshot(0).unwrap().max, 30);
+    }
+
+    #[test]
+    fn total_carries_into_upper_half() {
+        let table = StatsTable::<1>::new();
+        let clock = FakeClock(Cell::new(0));
+
+        for _ in 0..3 {
+            table.measure(0, &clock, || clock.advance(u32::MAX / 2));
+        }
+
+        let snapshot = table.snapshot(0).unwrap();
+
+        assert_eq!(snapshot.total, 3 * (u32::MAX / 2) as u64);
+        assert_eq!(snapshot.average(), u32::MAX / 2);
+    }
+
+    #[test]
+    fn nesting() {
+        let table = StatsTable::<4>::new();
+        let clock = FakeClock(Cell::new(0));
+
+        table.measure(0, &clock, || {
+            clock.advance(10);
+
+            table.measure(1, &clock, || {
+                clock.advance(5);
+                table.measure(3, &clock, || clock.advance(2));
+            });
+
+            clock.advance(10);
+        });
+
+        // Out of range IRQs run without being measured.
+        assert_eq!(table.measure(9, &clock, || 7), 7);
+
+        let depths: [u32; 4] = core::array::from_fn(|irq| table.snapshot(irq).unwrap().depth);
+        let totals: [u64; 4] = core::array::from_fn(|irq| table.snapshot(irq).unwrap().total);
+
+        assert_eq!(depths, [1, 2, 0, 3]);
+        assert_eq!(totals, [27, 7, 0, 2]);
+        assert_eq!(table.depth.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn dump_and_reset() {
+        let table = StatsTable::<16>::new();
+        let clock = FakeClock(Cell::new(0));
+
+        table.measure(3, &clock, || clock.advance(120));
+        table.measure(3, &clock, || clock.advance(80));
+        table.measure(12, &clock, || clock.advance(5));
+
+        let mut text = Text::new();
+        table.dump(&mut text).unwrap();
+
+        assert_eq!(text.as_str(), concat!(
+            "IRQ   3: count          2 | min         80 | max        120 | avg        100 | depth 1\n",
+            "IRQ  12: count          1 | min          5 | max          5 | avg          5 | depth 1\n",
+        ));
+
+        table.reset();
+
+        let mut text = Text::new();
+        table.dump(&mut text).unwrap();
+
+        assert_eq!(text.as_str(), "");
+        assert_eq!(table.snapshots().count(), 0);
+    }
+}
@@ -43,6 +43,14 @@ impl UserHandler {
         }
     }
 
+    /// Calls the given handler function measuring it as an invocation of the given IRQ.
+    #[cfg(feature = "stats")]
+    pub fn measured<C: super::Clock, const N: usize>(&self, irq: usize, stats: &super::StatsTable<N>, clock: &C) {
+        if self.init {
+            stats.measure(irq, clock, || self.call())
+        }
+    }
+
     /// Sets the handler of the `UserContext`.
     pub const fn isolated(handler: fn()) -> Self {
         // Set the handler pointer.
@@ -70,4 +78,19 @@ impl UserHandler {
 
         UserHandler { handler, context, init }
     }
+
+    /// Initializes a `UserHandler` with a context shared with other execution contexts.
+    /// The handler receives a shared reference, so the context must be `Sync`.
+    pub const fn shared<T: Sync>(handler: fn(&T), context: &'static T) -> Self {
+        // Set the handler pointer.
+        let handler = handler as *const ();
+
+        // Set the context.
+        let context = Some( context as *const T as *mut () );
+
+        // Indicate as initialized.
+        let init = true;
+
+        UserHandler { handler, context, init }
+    }
 }
@@ -8,12 +8,24 @@
 #![feature(const_mut_refs)]
 #![feature(const_trait_impl)]
 #![feature(generic_associated_types)]
+#![feature(impl_trait_in_assoc_type)]
+
+
+
+#[cfg(any(test, feature = "mock"))]
+extern crate std;
 
 
 
 /// Buffer utilities.
 pub mod buffer;
 
+/// Framing codecs for byte streams.
+pub mod codec;
+
+/// Cyclic Redundancy Checks.
+pub mod crc;
+
 /// Interrupt utilities.
 pub mod int;
 
@@ -26,6 +38,9 @@ pub mod res;
 /// Drivers for different peripherals (internal and external).
 pub mod drivers;
 
+/// Time sources and delays.
+pub mod time;
+
 
 #[cfg(feature = "arm")]
 mod arm;
@@ -0,0 +1,68 @@
+//! Time related abstractions.
+
+
+
+mod sleep;
+mod timeout;
+
+
+
+pub use self::sleep::{ delay_until, sleep, Sleep };
+pub use self::timeout::{ with_timeout, Elapsed, Timeout };
+
+
+
+use core::task::Waker;
+
+
+
+/// Blocking delay source.
+pub trait Delay {
+    /// Blocks for at least the given number of microseconds.
+    fn delay_us(&mut self, us: u32);
+
+    /// Blocks for at least the given number of milliseconds.
+    fn delay_ms(&mut self, ms: u32) {
+        for _ in 0..ms {
+            self.delay_us(1000);
+        }
+    }
+}
+
+impl<D: Delay> Delay for &mut D {
+    fn delay_us(&mut self, us: u32) {
+        D::delay_us(self, us)
+    }
+}
+
+
+
+/// Monotonic time source.
+pub trait TimeSource {
+    /// Microseconds elapsed since an arbitrary origin. Must not wrap.
+    fn now(&self) -> u64;
+
+    /// Requests the waker to be woken once `now()` reaches the deadline.
+    /// Sources without an alarm keep the default, which wakes immediately
+    /// and makes the executor poll again (busy waiting).
+    fn wake_at(&self, deadline: u64, waker: &Waker) {
+        let _ = deadline;
+        waker.wake_by_ref();
+    }
+}
+
+impl<T: TimeSource> TimeSource for &T {
+    fn now(&self) -> u64 {
+        T::now(self)
+    }
+
+    fn wake_at(&self, deadline: u64, waker: &Waker) {
+        T::wake_at(self, deadline, waker)
+    }
+}
+
+impl TimeSource for fn() -> u64 {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
@@ -0,0 +1,88 @@
+//! Timer futures.
+
+
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{ Context, Poll };
+use core::time::Duration;
+
+use super::TimeSource;
+
+
+
+/// Waits until the duration has elapsed.
+pub fn sleep<S: TimeSource>(source: S, duration: Duration) -> Sleep<S> {
+    let deadline = source.now().saturating_add(duration.as_micros() as u64);
+
+    Sleep { source, deadline }
+}
+
+/// Waits until the time source reaches the deadline, in microseconds of the
+/// time source. Completes immediately if the deadline has passed.
+pub fn delay_until<S: TimeSource>(source: S, deadline: u64) -> Sleep<S> {
+    Sleep { source, deadline }
+}
+
+
+
+/// Future returned by `sleep` and `delay_until`.
+pub struct Sleep<S> {
+    /// Time source.
+    source: S,
+
+    /// Deadline in microseconds of the time source.
+    deadline: u64,
+}
+
+impl<S: TimeSource> Future for Sleep<S> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.source.now() >= self.deadline { return Poll::Ready(()) }
+
+        self.source.wake_at(self.deadline, cx.waker());
+
+        Poll::Pending
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use core::task::Waker;
+
+    use super::*;
+    use crate::drivers::comm::mock::{ block_on, MockClock };
+
+    #[test]
+    fn sleep_follows_the_clock() {
+        let clock = MockClock::new();
+        clock.advance(Duration::from_micros(1000));
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut sleep = pin!(sleep(&clock, Duration::from_micros(500)));
+
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+
+        clock.advance(Duration::from_micros(499));
+        assert!(sleep.as_mut().poll(&mut cx).is_pending());
+
+        clock.advance(Duration::from_micros(1));
+        assert_eq!(sleep.as_mut().poll(&mut cx), Poll::Ready(()));
+    }
+
+    #[test]
+    fn delay_until_an_absolute_deadline() {
+        let clock = MockClock::ticking(10);
+
+        block_on(delay_until(&clock, 1000));
+        assert!(clock.now() >= 1000);
+
+        // A deadline in the past completes at once.
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(pin!(delay_until(&clock, 0)).poll(&mut cx), Poll::Ready(()));
+    }
+}
@@ -0,0 +1,150 @@
+//! Timeout combinator for futures.
+
+
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{ Context, Poll };
+use core::time::Duration;
+
+use crate::drivers::comm::{ DriverError, ErrorKind };
+
+use super::TimeSource;
+
+
+
+/// Error returned when a future did not complete before its deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl DriverError for Elapsed {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Timeout
+    }
+}
+
+
+
+/// Runs the future until it completes or the duration elapses.
+/// On timeout the inner future is dropped before `Elapsed` is returned, so a
+/// driver future runs its cancellation path (aborting the peripheral and
+/// releasing the bus) before the caller can start a new transfer.
+pub fn with_timeout<S: TimeSource, F: Future>(source: S, future: F, duration: Duration) -> Timeout<S, F> {
+    let deadline = source.now().saturating_add(duration.as_micros() as u64);
+
+    Timeout { source, deadline, future: Some(future) }
+}
+
+
+
+/// Future returned by `with_timeout`.
+pub struct Timeout<S, F> {
+    /// Time source.
+    source: S,
+
+    /// Deadline in microseconds of the time source.
+    deadline: u64,
+
+    /// Inner future. Dropped on completion or timeout.
+    future: Option<F>,
+}
+
+impl<S: TimeSource, F: Future> Future for Timeout<S, F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The inner future is never moved out, only dropped in place.
+        let this = unsafe { self.get_unchecked_mut() };
+
+        let future = match this.future.as_mut() {
+            Some(future) => unsafe { Pin::new_unchecked(future) },
+            _ => return Poll::Ready(Err(Elapsed)),
+        };
+
+        if let Poll::Ready(output) = future.poll(cx) {
+            this.future = None;
+            return Poll::Ready(Ok(output));
+        }
+
+        if this.source.now() >= this.deadline {
+            this.future = None;
+            return Poll::Ready(Err(Elapsed));
+        }
+
+        this.source.wake_at(this.deadline, cx.waker());
+
+        Poll::Pending
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use core::task::Waker;
+
+    use super::*;
+    use crate::drivers::comm::{ I2CAddress7bit, I2CAsyncDriver };
+    use crate::drivers::comm::mock::{ block_on, I2CMock, MockClock };
+
+    const TARGET: I2CAddress7bit = I2CAddress7bit::new(0x50);
+
+    #[test]
+    fn stalled_transfer_times_out_and_releases_bus() {
+        let mut mock = I2CMock::new(TARGET, [0u8; 4]);
+        let clock = MockClock::ticking(100);
+        mock.stall(true);
+
+        let result = block_on(with_timeout(&clock, mock.send(TARGET, &[0x00, 0xAA]), Duration::from_millis(10)));
+
+        assert_eq!(result, Err(Elapsed));
+        assert_eq!(mock.aborts(), 1);
+        assert!(!mock.busy());
+        assert!(clock.now() >= 10_000);
+
+        // The bus accepts a new transfer once the target recovers.
+        mock.stall(false);
+        let result = block_on(with_timeout(&clock, mock.send(TARGET, &[0x00, 0xAA]), Duration::from_millis(10)));
+
+        assert_eq!(result, Ok(Ok(())));
+        assert_eq!(mock.memory()[0], 0xAA);
+        assert_eq!(mock.aborts(), 1);
+    }
+
+    #[test]
+    fn completed_transfer_is_returned() {
+        let mut mock = I2CMock::new(TARGET, [0x11, 0x22]);
+        let clock = MockClock::ticking(1);
+        let mut data = [0; 2];
+
+        let result = block_on(with_timeout(&clock, mock.transfer(TARGET, &[0x00], &mut data), Duration::from_millis(1)));
+
+        assert_eq!(result, Ok(Ok(())));
+        assert_eq!(data, [0x11, 0x22]);
+        assert_eq!(mock.aborts(), 0);
+    }
+
+    #[test]
+    fn deadline_follows_the_clock() {
+        let mut mock = I2CMock::new(TARGET, [0u8; 4]);
+        let clock = MockClock::new();
+        mock.stall(true);
+
+        {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut timeout = pin!(with_timeout(&clock, mock.send(TARGET, &[0x00]), Duration::from_micros(500)));
+
+            assert!(timeout.as_mut().poll(&mut cx).is_pending());
+
+            clock.advance(Duration::from_micros(499));
+            assert!(timeout.as_mut().poll(&mut cx).is_pending());
+
+            clock.advance(Duration::from_micros(1));
+            assert_eq!(timeout.as_mut().poll(&mut cx), Poll::Ready(Err(Elapsed)));
+        }
+
+        assert_eq!(mock.aborts(), 1);
+        assert!(!mock.busy());
+    }
+}